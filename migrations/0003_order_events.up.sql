@@ -0,0 +1,13 @@
+CREATE TABLE order_events (
+    id TEXT PRIMARY KEY,
+    order_id TEXT NOT NULL,
+    field TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    actor_id TEXT,
+    note TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_order_events_order_id ON order_events (order_id, created_at);
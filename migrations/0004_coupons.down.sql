@@ -0,0 +1,3 @@
+ALTER TABLE orders DROP COLUMN coupon_code;
+DROP TABLE IF EXISTS coupon_redemptions;
+DROP TABLE IF EXISTS coupons;
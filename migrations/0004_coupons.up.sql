@@ -0,0 +1,33 @@
+CREATE TABLE coupons (
+    id TEXT PRIMARY KEY,
+    code TEXT UNIQUE NOT NULL,
+    kind TEXT NOT NULL,
+    value REAL NOT NULL DEFAULT 0,
+    currency TEXT,
+    min_order_value REAL,
+    max_uses BIGINT,
+    max_uses_per_user BIGINT,
+    used_count BIGINT NOT NULL DEFAULT 0,
+    starts_at TEXT,
+    ends_at TEXT,
+    product_ids TEXT NOT NULL DEFAULT '[]',
+    category_ids TEXT NOT NULL DEFAULT '[]',
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE coupon_redemptions (
+    id TEXT PRIMARY KEY,
+    coupon_id TEXT NOT NULL,
+    order_id TEXT NOT NULL UNIQUE,
+    user_id TEXT NOT NULL,
+    discount_amount REAL NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (coupon_id) REFERENCES coupons(id) ON DELETE CASCADE,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_coupon_redemptions_user ON coupon_redemptions (coupon_id, user_id);
+
+ALTER TABLE orders ADD COLUMN coupon_code TEXT;
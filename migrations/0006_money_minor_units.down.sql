@@ -0,0 +1,114 @@
+ALTER TABLE coupons ADD COLUMN value_real REAL NOT NULL DEFAULT 0;
+UPDATE coupons SET value_real = CAST(value AS REAL);
+ALTER TABLE coupons DROP COLUMN value;
+ALTER TABLE coupons RENAME COLUMN value_real TO value;
+
+ALTER TABLE coupons ADD COLUMN min_order_value_real REAL;
+UPDATE coupons SET min_order_value_real = CAST(min_order_value AS REAL);
+ALTER TABLE coupons DROP COLUMN min_order_value;
+ALTER TABLE coupons RENAME COLUMN min_order_value_real TO min_order_value;
+
+ALTER TABLE coupon_redemptions ADD COLUMN discount_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE coupon_redemptions SET discount_amount_real = discount_amount * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE coupon_redemptions DROP COLUMN discount_amount;
+ALTER TABLE coupon_redemptions RENAME COLUMN discount_amount_real TO discount_amount;
+ALTER TABLE coupon_redemptions DROP COLUMN currency;
+
+ALTER TABLE products ADD COLUMN price_real REAL NOT NULL DEFAULT 0;
+UPDATE products SET price_real = price * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE products DROP COLUMN price;
+ALTER TABLE products RENAME COLUMN price_real TO price;
+
+ALTER TABLE products ADD COLUMN sale_price_real REAL;
+UPDATE products SET sale_price_real = sale_price * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE products DROP COLUMN sale_price;
+ALTER TABLE products RENAME COLUMN sale_price_real TO sale_price;
+
+ALTER TABLE products ADD COLUMN cost_price_real REAL;
+UPDATE products SET cost_price_real = cost_price * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE products DROP COLUMN cost_price;
+ALTER TABLE products RENAME COLUMN cost_price_real TO cost_price;
+
+ALTER TABLE orders ADD COLUMN subtotal_real REAL NOT NULL DEFAULT 0;
+UPDATE orders SET subtotal_real = subtotal * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE orders DROP COLUMN subtotal;
+ALTER TABLE orders RENAME COLUMN subtotal_real TO subtotal;
+
+ALTER TABLE orders ADD COLUMN tax_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE orders SET tax_amount_real = tax_amount * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE orders DROP COLUMN tax_amount;
+ALTER TABLE orders RENAME COLUMN tax_amount_real TO tax_amount;
+
+ALTER TABLE orders ADD COLUMN shipping_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE orders SET shipping_amount_real = shipping_amount * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE orders DROP COLUMN shipping_amount;
+ALTER TABLE orders RENAME COLUMN shipping_amount_real TO shipping_amount;
+
+ALTER TABLE orders ADD COLUMN discount_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE orders SET discount_amount_real = discount_amount * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE orders DROP COLUMN discount_amount;
+ALTER TABLE orders RENAME COLUMN discount_amount_real TO discount_amount;
+
+ALTER TABLE orders ADD COLUMN total_real REAL NOT NULL DEFAULT 0;
+UPDATE orders SET total_real = total * 1.0 / CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE orders DROP COLUMN total;
+ALTER TABLE orders RENAME COLUMN total_real TO total;
+
+ALTER TABLE order_items ADD COLUMN unit_price_real REAL NOT NULL DEFAULT 0;
+UPDATE order_items SET unit_price_real = unit_price * 1.0 / CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE order_items DROP COLUMN unit_price;
+ALTER TABLE order_items RENAME COLUMN unit_price_real TO unit_price;
+
+ALTER TABLE order_items ADD COLUMN total_price_real REAL NOT NULL DEFAULT 0;
+UPDATE order_items SET total_price_real = total_price * 1.0 / CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE order_items DROP COLUMN total_price;
+ALTER TABLE order_items RENAME COLUMN total_price_real TO total_price;
+
+ALTER TABLE order_items ADD COLUMN tax_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE order_items SET tax_amount_real = tax_amount * 1.0 / CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE order_items DROP COLUMN tax_amount;
+ALTER TABLE order_items RENAME COLUMN tax_amount_real TO tax_amount;
+
+ALTER TABLE order_items ADD COLUMN discount_amount_real REAL NOT NULL DEFAULT 0;
+UPDATE order_items SET discount_amount_real = discount_amount * 1.0 / CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END;
+ALTER TABLE order_items DROP COLUMN discount_amount;
+ALTER TABLE order_items RENAME COLUMN discount_amount_real TO discount_amount;
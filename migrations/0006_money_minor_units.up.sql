@@ -0,0 +1,126 @@
+-- Money columns become BIGINT counts of the currency's minor unit (cents for
+-- USD, yen for JPY, fils for KWD), matching `money::minor_unit_digits`.
+-- SQLite and Postgres both support ADD, DROP and RENAME COLUMN, so each
+-- column is rebuilt in place.
+
+ALTER TABLE products ADD COLUMN price_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE products SET price_minor = CAST(ROUND(price * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE products DROP COLUMN price;
+ALTER TABLE products RENAME COLUMN price_minor TO price;
+
+ALTER TABLE products ADD COLUMN sale_price_minor BIGINT;
+UPDATE products SET sale_price_minor = CAST(ROUND(sale_price * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE products DROP COLUMN sale_price;
+ALTER TABLE products RENAME COLUMN sale_price_minor TO sale_price;
+
+ALTER TABLE products ADD COLUMN cost_price_minor BIGINT;
+UPDATE products SET cost_price_minor = CAST(ROUND(cost_price * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE products DROP COLUMN cost_price;
+ALTER TABLE products RENAME COLUMN cost_price_minor TO cost_price;
+
+ALTER TABLE orders ADD COLUMN subtotal_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET subtotal_minor = CAST(ROUND(subtotal * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE orders DROP COLUMN subtotal;
+ALTER TABLE orders RENAME COLUMN subtotal_minor TO subtotal;
+
+ALTER TABLE orders ADD COLUMN tax_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET tax_amount_minor = CAST(ROUND(tax_amount * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE orders DROP COLUMN tax_amount;
+ALTER TABLE orders RENAME COLUMN tax_amount_minor TO tax_amount;
+
+ALTER TABLE orders ADD COLUMN shipping_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET shipping_amount_minor = CAST(ROUND(shipping_amount * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE orders DROP COLUMN shipping_amount;
+ALTER TABLE orders RENAME COLUMN shipping_amount_minor TO shipping_amount;
+
+ALTER TABLE orders ADD COLUMN discount_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET discount_amount_minor = CAST(ROUND(discount_amount * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE orders DROP COLUMN discount_amount;
+ALTER TABLE orders RENAME COLUMN discount_amount_minor TO discount_amount;
+
+ALTER TABLE orders ADD COLUMN total_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET total_minor = CAST(ROUND(total * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE orders DROP COLUMN total;
+ALTER TABLE orders RENAME COLUMN total_minor TO total;
+
+ALTER TABLE order_items ADD COLUMN unit_price_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE order_items SET unit_price_minor = CAST(ROUND(unit_price * CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE order_items DROP COLUMN unit_price;
+ALTER TABLE order_items RENAME COLUMN unit_price_minor TO unit_price;
+
+ALTER TABLE order_items ADD COLUMN total_price_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE order_items SET total_price_minor = CAST(ROUND(total_price * CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE order_items DROP COLUMN total_price;
+ALTER TABLE order_items RENAME COLUMN total_price_minor TO total_price;
+
+ALTER TABLE order_items ADD COLUMN tax_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE order_items SET tax_amount_minor = CAST(ROUND(tax_amount * CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE order_items DROP COLUMN tax_amount;
+ALTER TABLE order_items RENAME COLUMN tax_amount_minor TO tax_amount;
+
+ALTER TABLE order_items ADD COLUMN discount_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE order_items SET discount_amount_minor = CAST(ROUND(discount_amount * CASE
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER((SELECT currency FROM orders WHERE orders.id = order_items.order_id)) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE order_items DROP COLUMN discount_amount;
+ALTER TABLE order_items RENAME COLUMN discount_amount_minor TO discount_amount;
+
+-- Redemptions record the order's currency alongside the discount
+ALTER TABLE coupon_redemptions ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+UPDATE coupon_redemptions SET currency = (
+    SELECT currency FROM orders WHERE orders.id = coupon_redemptions.order_id
+) WHERE EXISTS (SELECT 1 FROM orders WHERE orders.id = coupon_redemptions.order_id);
+
+ALTER TABLE coupon_redemptions ADD COLUMN discount_amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE coupon_redemptions SET discount_amount_minor = CAST(ROUND(discount_amount * CASE
+    WHEN UPPER(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG', 'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN UPPER(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    ELSE 100 END) AS BIGINT);
+ALTER TABLE coupon_redemptions DROP COLUMN discount_amount;
+ALTER TABLE coupon_redemptions RENAME COLUMN discount_amount_minor TO discount_amount;
+
+-- A coupon's value is a percentage or an amount in an optional currency, so
+-- it is kept as an exact decimal string rather than minor units
+ALTER TABLE coupons ADD COLUMN value_text TEXT NOT NULL DEFAULT '0';
+UPDATE coupons SET value_text = CAST(value AS TEXT);
+ALTER TABLE coupons DROP COLUMN value;
+ALTER TABLE coupons RENAME COLUMN value_text TO value;
+
+ALTER TABLE coupons ADD COLUMN min_order_value_text TEXT;
+UPDATE coupons SET min_order_value_text = CAST(min_order_value AS TEXT);
+ALTER TABLE coupons DROP COLUMN min_order_value;
+ALTER TABLE coupons RENAME COLUMN min_order_value_text TO min_order_value;
@@ -0,0 +1,3 @@
+ALTER TABLE orders ADD COLUMN catalog_currency TEXT NOT NULL DEFAULT 'USD';
+ALTER TABLE orders ADD COLUMN exchange_rate TEXT NOT NULL DEFAULT '1';
+UPDATE orders SET catalog_currency = currency;
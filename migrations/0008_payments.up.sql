@@ -0,0 +1,34 @@
+CREATE TABLE payment_intents (
+    id TEXT PRIMARY KEY,
+    order_id TEXT NOT NULL,
+    gateway TEXT NOT NULL,
+    gateway_reference TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    currency TEXT NOT NULL,
+    status TEXT NOT NULL,
+    payment_method TEXT,
+    client_secret TEXT NOT NULL,
+    next_action_url TEXT,
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_payment_intents_order ON payment_intents (order_id);
+CREATE UNIQUE INDEX idx_payment_intents_reference ON payment_intents (gateway, gateway_reference);
+
+CREATE TABLE refunds (
+    id TEXT PRIMARY KEY,
+    payment_intent_id TEXT NOT NULL,
+    gateway_reference TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    currency TEXT NOT NULL,
+    status TEXT NOT NULL,
+    reason TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (payment_intent_id) REFERENCES payment_intents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_refunds_payment_intent ON refunds (payment_intent_id);
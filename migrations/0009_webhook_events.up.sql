@@ -0,0 +1,13 @@
+CREATE TABLE webhook_events (
+    id TEXT PRIMARY KEY,
+    gateway TEXT NOT NULL,
+    event_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    received_at TEXT NOT NULL,
+    processed_at TEXT
+);
+
+CREATE UNIQUE INDEX idx_webhook_events_event ON webhook_events (gateway, event_id);
+CREATE INDEX idx_webhook_events_status ON webhook_events (status);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS refund_items;
+ALTER TABLE refunds DROP COLUMN restock;
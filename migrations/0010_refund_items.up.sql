@@ -0,0 +1,14 @@
+-- Refunds can cover specific order lines and put them back in stock
+ALTER TABLE refunds ADD COLUMN restock INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE refund_items (
+    refund_id TEXT NOT NULL,
+    order_item_id TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    amount BIGINT NOT NULL,
+    PRIMARY KEY (refund_id, order_item_id),
+    FOREIGN KEY (refund_id) REFERENCES refunds(id) ON DELETE CASCADE,
+    FOREIGN KEY (order_item_id) REFERENCES order_items(id)
+);
+
+CREATE INDEX idx_refund_items_order_item ON refund_items (order_item_id);
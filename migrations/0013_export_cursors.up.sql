@@ -0,0 +1,4 @@
+-- Keyset indexes for streaming exports, which page by (timestamp, id)
+CREATE INDEX idx_users_created_at_id ON users (created_at, id);
+CREATE INDEX idx_orders_placed_at_id ON orders (placed_at, id);
+CREATE INDEX idx_products_created_at_id ON products (created_at, id);
@@ -0,0 +1,15 @@
+-- Files received through `/upload`; the bytes live in the blob store under
+-- `storage_key`
+CREATE TABLE uploads (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    storage_key TEXT NOT NULL,
+    original_filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_uploads_owner_id ON uploads (owner_id);
@@ -0,0 +1,4 @@
+-- At most one intent per order may be awaiting the customer or gateway, so
+-- concurrent checkouts cannot both start one
+CREATE UNIQUE INDEX idx_payment_intents_open ON payment_intents (order_id)
+    WHERE status NOT IN ('succeeded', 'canceled');
@@ -1,4 +1,7 @@
+use crate::config::AuthConfig;
 use crate::error::{AppError, Result};
+use crate::models::UserRole;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
@@ -36,6 +39,14 @@ pub struct RefreshClaims {
     pub jti: String,
 }
 
+/// A token pair along with the claims it encodes, for recording the session.
+#[derive(Debug, Clone)]
+pub struct IssuedTokens {
+    pub tokens: TokenPair,
+    pub access_claims: Claims,
+    pub refresh_claims: RefreshClaims,
+}
+
 pub struct AuthService {
     jwt_secret: String,
     access_token_expiry: i64,
@@ -51,6 +62,14 @@ impl AuthService {
         }
     }
 
+    pub fn from_config(config: &AuthConfig) -> Self {
+        Self::new(
+            config.jwt_secret.clone(),
+            config.token_expiry_seconds,
+            config.refresh_token_expiry_seconds,
+        )
+    }
+
     pub fn hash_password(&self, password: &str) -> Result<String> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
@@ -75,6 +94,10 @@ impl AuthService {
         email: &str,
         role: &str,
     ) -> Result<TokenPair> {
+        Ok(self.issue_tokens(user_id, email, role)?.tokens)
+    }
+
+    pub fn issue_tokens(&self, user_id: Uuid, email: &str, role: &str) -> Result<IssuedTokens> {
         let now = Utc::now();
         let access_exp = now + Duration::seconds(self.access_token_expiry);
         let refresh_exp = now + Duration::seconds(self.refresh_token_expiry);
@@ -111,11 +134,15 @@ impl AuthService {
         )
         .map_err(|e| AppError::InternalError(format!("Token generation failed: {}", e)))?;
 
-        Ok(TokenPair {
-            access_token,
-            refresh_token,
-            token_type: "Bearer".to_string(),
-            expires_in: self.access_token_expiry,
+        Ok(IssuedTokens {
+            tokens: TokenPair {
+                access_token,
+                refresh_token,
+                token_type: "Bearer".to_string(),
+                expires_in: self.access_token_expiry,
+            },
+            access_claims,
+            refresh_claims,
         })
     }
 
@@ -147,6 +174,67 @@ impl AuthService {
     }
 }
 
+/// The authenticated caller, placed in request extensions by `auth_middleware`.
+/// Extracting it on a route without the middleware yields a 401.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub claims: Claims,
+    pub role: UserRole,
+}
+
+impl AuthUser {
+    pub fn from_claims(claims: Claims) -> Result<Self> {
+        let role = claims
+            .role
+            .parse::<UserRole>()
+            .map_err(AppError::AuthenticationError)?;
+        Ok(Self { claims, role })
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.claims.user_id
+    }
+
+    pub fn has_role(&self, required: UserRole) -> bool {
+        self.role.includes(&required)
+    }
+
+    pub fn require_role(&self, required: UserRole) -> Result<()> {
+        if self.has_role(required.clone()) {
+            Ok(())
+        } else {
+            Err(AppError::AuthorizationError(format!(
+                "Requires {} role",
+                required.as_str()
+            )))
+        }
+    }
+
+    /// Allows the owner of a resource, or anyone holding `override_role`.
+    pub fn require_owner_or(&self, owner_id: Uuid, override_role: UserRole) -> Result<()> {
+        if self.id() == owner_id || self.has_role(override_role) {
+            Ok(())
+        } else {
+            Err(AppError::AuthorizationError(
+                "Not allowed to access this resource".to_string(),
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or_else(|| AppError::AuthenticationError("Authentication required".to_string()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -180,3 +268,49 @@ pub struct AuthUserInfo {
     pub username: String,
     pub role: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_user(role: UserRole) -> AuthUser {
+        let service = AuthService::new("test-secret".to_string(), 60, 120);
+        let tokens = service
+            .generate_token_pair(Uuid::new_v4(), "a@example.com", role.as_str())
+            .unwrap();
+        let claims = service.validate_access_token(&tokens.access_token).unwrap().claims;
+        AuthUser::from_claims(claims).unwrap()
+    }
+
+    #[test]
+    fn test_role_hierarchy() {
+        let moderator = auth_user(UserRole::Moderator);
+        assert!(moderator.require_role(UserRole::User).is_ok());
+        assert!(moderator.require_role(UserRole::Moderator).is_ok());
+        assert!(matches!(
+            moderator.require_role(UserRole::Admin),
+            Err(AppError::AuthorizationError(_))
+        ));
+        assert!(!auth_user(UserRole::Guest).has_role(UserRole::User));
+    }
+
+    #[test]
+    fn test_owner_or_role() {
+        let user = auth_user(UserRole::User);
+        assert!(user.require_owner_or(user.id(), UserRole::Admin).is_ok());
+        assert!(user.require_owner_or(Uuid::new_v4(), UserRole::Admin).is_err());
+        assert!(auth_user(UserRole::Admin)
+            .require_owner_or(Uuid::new_v4(), UserRole::Admin)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_unknown_role_is_rejected() {
+        let mut claims = auth_user(UserRole::User).claims;
+        claims.role = "superuser".to_string();
+        assert!(matches!(
+            AuthUser::from_claims(claims),
+            Err(AppError::AuthenticationError(_))
+        ));
+    }
+}
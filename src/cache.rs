@@ -1,10 +1,36 @@
-use moka::future::Cache;
+use moka::{future::Cache, Expiry};
 use serde::{de::DeserializeOwned, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub struct CacheManager {
     string_cache: Cache<String, String>,
     json_cache: Cache<String, String>,
+    expiring_cache: Cache<String, Expiring>,
+}
+
+/// A JSON value kept for its own lifetime instead of the cache-wide TTL.
+#[derive(Clone)]
+struct Expiring {
+    json: String,
+    ttl: Duration,
+}
+
+struct PerEntryTtl;
+
+impl Expiry<String, Expiring> for PerEntryTtl {
+    fn expire_after_create(&self, _key: &String, value: &Expiring, _created_at: Instant) -> Option<Duration> {
+        Some(value.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &Expiring,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
 }
 
 impl CacheManager {
@@ -21,9 +47,15 @@ impl CacheManager {
             .time_to_idle(Duration::from_secs(60))
             .build();
 
+        let expiring_cache = Cache::builder()
+            .max_capacity(10_000)
+            .expire_after(PerEntryTtl)
+            .build();
+
         Self {
             string_cache,
             json_cache,
+            expiring_cache,
         }
     }
 
@@ -38,11 +70,42 @@ impl CacheManager {
     pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
         if let Some(json_str) = self.json_cache.get(key).await {
             serde_json::from_str(&json_str).ok()
+        } else if let Some(entry) = self.expiring_cache.get(key).await {
+            serde_json::from_str(&entry.json).ok()
         } else {
             None
         }
     }
 
+    /// Stores `value` for `ttl`, regardless of the cache-wide lifetime.
+    pub async fn set_json_for<T: Serialize>(
+        &self,
+        key: String,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), serde_json::Error> {
+        let json = serde_json::to_string(value)?;
+        self.expiring_cache.insert(key, Expiring { json, ttl }).await;
+        Ok(())
+    }
+
+    /// Like `set_json_for`, but only when `key` is absent. Returns whether
+    /// `value` was stored; concurrent callers see exactly one `true`.
+    pub async fn insert_json_for<T: Serialize>(
+        &self,
+        key: String,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<bool, serde_json::Error> {
+        let json = serde_json::to_string(value)?;
+        let entry = self
+            .expiring_cache
+            .entry(key)
+            .or_insert(Expiring { json, ttl })
+            .await;
+        Ok(entry.is_fresh())
+    }
+
     pub async fn set_json<T: Serialize>(&self, key: String, value: &T) -> Result<(), serde_json::Error> {
         let json_str = serde_json::to_string(value)?;
         self.json_cache.insert(key, json_str).await;
@@ -52,15 +115,19 @@ impl CacheManager {
     pub async fn delete(&self, key: &str) {
         self.string_cache.invalidate(key).await;
         self.json_cache.invalidate(key).await;
+        self.expiring_cache.invalidate(key).await;
     }
 
     pub async fn clear(&self) {
         self.string_cache.invalidate_all();
         self.json_cache.invalidate_all();
+        self.expiring_cache.invalidate_all();
     }
 
     pub fn entry_count(&self) -> u64 {
-        self.string_cache.entry_count() + self.json_cache.entry_count()
+        self.string_cache.entry_count()
+            + self.json_cache.entry_count()
+            + self.expiring_cache.entry_count()
     }
 }
 
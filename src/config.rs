@@ -1,7 +1,17 @@
+use config::{Config, Environment, File, FileFormat};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use typed_builder::TypedBuilder;
 
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+pub const ENV_PREFIX: &str = "APP";
+pub const ENV_SEPARATOR: &str = "__";
+const DEFAULT_JWT_SECRET: &str = "super-secret-jwt-key-change-in-production";
+
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct AppConfig {
     #[builder(default = DatabaseConfig::default())]
     pub database: DatabaseConfig,
@@ -13,6 +23,28 @@ pub struct AppConfig {
     pub cache: CacheConfig,
     #[builder(default = ExternalServices::default())]
     pub external: ExternalServices,
+    #[builder(default = RateLimitConfig::default())]
+    pub rate_limit: RateLimitConfig,
+    #[builder(default = TaxConfig::default())]
+    pub tax: TaxConfig,
+    #[builder(default = ShippingConfig::default())]
+    pub shipping: ShippingConfig,
+    #[builder(default = ExchangeConfig::default())]
+    pub exchange: ExchangeConfig,
+    #[builder(default = PaymentConfig::default())]
+    pub payments: PaymentConfig,
+    #[builder(default = IdempotencyConfig::default())]
+    pub idempotency: IdempotencyConfig,
+    #[builder(default = EmailConfig::default())]
+    pub email: EmailConfig,
+    #[builder(default = JobsConfig::default())]
+    pub jobs: JobsConfig,
+    #[builder(default = ExportConfig::default())]
+    pub exports: ExportConfig,
+    #[builder(default = ImportConfig::default())]
+    pub imports: ImportConfig,
+    #[builder(default = UploadConfig::default())]
+    pub uploads: UploadConfig,
 }
 
 impl Default for AppConfig {
@@ -21,7 +53,18 @@ impl Default for AppConfig {
     }
 }
 
+impl AppConfig {
+    pub fn loader() -> ConfigLoader {
+        ConfigLoader::new()
+    }
+
+    pub fn uses_default_jwt_secret(&self) -> bool {
+        self.auth.jwt_secret == DEFAULT_JWT_SECRET
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct DatabaseConfig {
     #[builder(default = "sqlite::memory:".to_string())]
     pub url: String,
@@ -31,6 +74,9 @@ pub struct DatabaseConfig {
     pub timeout_seconds: u64,
     #[builder(default = true)]
     pub enable_logging: bool,
+    /// Switch on-disk SQLite databases to write-ahead logging
+    #[builder(default = true)]
+    pub sqlite_wal: bool,
 }
 
 impl Default for DatabaseConfig {
@@ -39,7 +85,16 @@ impl Default for DatabaseConfig {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Deployment {
+    Development,
+    Test,
+    Production,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct ServerConfig {
     #[builder(default = "127.0.0.1".to_string())]
     pub host: String,
@@ -51,6 +106,9 @@ pub struct ServerConfig {
     pub enable_compression: bool,
     #[builder(default = true)]
     pub enable_cors: bool,
+    /// What kind of deployment this is; production refuses test doubles
+    #[builder(default = Deployment::Development)]
+    pub environment: Deployment,
 }
 
 impl Default for ServerConfig {
@@ -60,8 +118,9 @@ impl Default for ServerConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct AuthConfig {
-    #[builder(default = "super-secret-jwt-key-change-in-production".to_string())]
+    #[builder(default = DEFAULT_JWT_SECRET.to_string())]
     pub jwt_secret: String,
     #[builder(default = 3600)]
     pub token_expiry_seconds: i64,
@@ -78,6 +137,7 @@ impl Default for AuthConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct CacheConfig {
     #[builder(default = 10000)]
     pub max_capacity: u64,
@@ -94,6 +154,7 @@ impl Default for CacheConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
 pub struct ExternalServices {
     #[builder(default = "https://api.example.com".to_string())]
     pub api_base_url: String,
@@ -110,3 +171,735 @@ impl Default for ExternalServices {
         Self::builder().build()
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    #[builder(default = true)]
+    pub enabled: bool,
+    /// Key anonymous clients by the first `X-Forwarded-For` address; only
+    /// enable behind a proxy that sets it
+    #[builder(default = false)]
+    pub trust_forwarded_for: bool,
+    /// Unauthenticated requests, per client IP
+    #[builder(default = QuotaConfig::new(120, 60))]
+    pub anonymous: QuotaConfig,
+    /// Authenticated requests, per user id
+    #[builder(default = QuotaConfig::new(600, 60))]
+    pub authenticated: QuotaConfig,
+    /// Login and registration, per client IP
+    #[builder(default = QuotaConfig::new(5, 60))]
+    pub auth: QuotaConfig,
+    /// Authenticated routes, per client IP, counted before the token is
+    /// checked so missing or forged tokens are limited too
+    #[builder(default = QuotaConfig::new(1200, 60))]
+    pub per_ip: QuotaConfig,
+    /// Replaces the anonymous and authenticated quotas on one route, keyed
+    /// by its full route path, e.g. `/api/v1/search` or `/api/v1/posts/:id`.
+    /// Each route keeps its own buckets, per user id or client IP.
+    #[builder(default)]
+    pub routes: HashMap<String, QuotaConfig>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// `requests` may be spent at once, then refill evenly over `period_seconds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    pub requests: u32,
+    pub period_seconds: u64,
+}
+
+impl QuotaConfig {
+    pub const fn new(requests: u32, period_seconds: u64) -> Self {
+        Self {
+            requests,
+            period_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct TaxConfig {
+    /// Catalog prices already contain tax; it is extracted rather than added
+    #[builder(default = false)]
+    pub prices_include_tax: bool,
+    #[builder(default = true)]
+    pub exempt_digital_goods: bool,
+    /// Applied where no jurisdiction matches the shipping address
+    #[builder(default = Decimal::new(10, 2))]
+    pub default_rate: Decimal,
+    #[builder(default)]
+    pub jurisdictions: Vec<TaxJurisdiction>,
+}
+
+impl Default for TaxConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// A rate for a country, optionally narrowed to a state and a postal code
+/// prefix. The most specific match wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxJurisdiction {
+    pub country: String,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub postal_prefix: Option<String>,
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct ShippingConfig {
+    /// Currency of every rate and `free_over` threshold; quotes convert
+    /// them into the order's currency
+    #[builder(default = "USD".to_string())]
+    pub currency: String,
+    /// Cubic centimetres per kilogram of volumetric weight
+    #[builder(default = Decimal::from(5000))]
+    pub volumetric_divisor: Decimal,
+    /// Countries not listed in any zone fall into `shipping::DEFAULT_ZONE`
+    #[builder(default)]
+    pub zones: Vec<ShippingZone>,
+    #[builder(default = vec![CarrierConfig::flat_rate()])]
+    pub carriers: Vec<CarrierConfig>,
+}
+
+impl Default for ShippingConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShippingZone {
+    pub code: String,
+    /// ISO 3166-1 alpha-2 codes
+    pub countries: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CarrierConfig {
+    pub code: String,
+    pub name: String,
+    pub methods: Vec<ShippingMethodConfig>,
+}
+
+impl CarrierConfig {
+    /// One method charging 9.99 to anywhere, whatever the weight.
+    fn flat_rate() -> Self {
+        Self {
+            code: "standard".to_string(),
+            name: "Standard Post".to_string(),
+            methods: vec![ShippingMethodConfig {
+                code: "ground".to_string(),
+                name: "Ground".to_string(),
+                min_days: 3,
+                max_days: 7,
+                rates: vec![ZoneRate {
+                    zone: "world".to_string(),
+                    tiers: vec![WeightTier {
+                        max_weight: None,
+                        price: Decimal::new(999, 2),
+                    }],
+                    free_over: None,
+                }],
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShippingMethodConfig {
+    pub code: String,
+    pub name: String,
+    pub min_days: u32,
+    pub max_days: u32,
+    /// Zones without a rate are not served by this method
+    pub rates: Vec<ZoneRate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneRate {
+    pub zone: String,
+    /// The lightest tier the billable weight fits in applies
+    pub tiers: Vec<WeightTier>,
+    /// Order value from which shipping is free
+    #[serde(default)]
+    pub free_over: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightTier {
+    /// Kilograms; `None` for no upper limit
+    #[serde(default)]
+    pub max_weight: Option<Decimal>,
+    pub price: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateSource {
+    /// The `rates` table below
+    Static,
+    /// `endpoint` on `external.api_base_url`
+    Http,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct ExchangeConfig {
+    #[builder(default = RateSource::Static)]
+    pub source: RateSource,
+    /// Queried with `?base=USD&target=EUR`, answering a `CurrencyExchangeRate`
+    #[builder(default = "/exchange-rates".to_string())]
+    pub endpoint: String,
+    /// Each pair also serves its inverse
+    #[builder(default)]
+    pub rates: Vec<ExchangeRateConfig>,
+}
+
+impl Default for ExchangeConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Units of `target` one unit of `base` buys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRateConfig {
+    pub base: String,
+    pub target: String,
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentGatewayKind {
+    /// Approves the test cards without moving money; refused in production
+    Mock,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct PaymentConfig {
+    #[builder(default = PaymentGatewayKind::Mock)]
+    pub gateway: PaymentGatewayKind,
+    /// Shared with the gateway to sign webhooks; webhooks are refused while
+    /// it is empty
+    #[builder(default = "".to_string())]
+    pub webhook_secret: String,
+    /// How far a webhook's signed timestamp may be from our clock
+    #[builder(default = 300)]
+    pub webhook_tolerance_seconds: u64,
+}
+
+impl Default for PaymentConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdempotencyBackend {
+    /// In-process; lost on restart and not shared between instances
+    Cache,
+    /// The `idempotency_keys` table
+    Database,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    #[builder(default = true)]
+    pub enabled: bool,
+    #[builder(default = IdempotencyBackend::Cache)]
+    pub backend: IdempotencyBackend,
+    /// How long a key replays its first response
+    #[builder(default = 86_400)]
+    pub ttl_seconds: u64,
+    /// Larger request bodies are rejected when they carry a key
+    #[builder(default = 1024 * 1024)]
+    pub max_body_bytes: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransportKind {
+    /// Only logs each message
+    Log,
+    Smtp,
+    /// Writes `.eml` files under `file.directory`
+    File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct EmailConfig {
+    #[builder(default = "noreply@example.com".to_string())]
+    pub from_address: String,
+    #[builder(default = "Compile Benchmark".to_string())]
+    pub from_name: String,
+    /// Prefix for links in emails
+    #[builder(default = "https://example.com".to_string())]
+    pub base_url: String,
+    /// Files here replace or add to the built-in templates by relative
+    /// name, e.g. `email/welcome.html` or `email/welcome.fr.html`
+    #[builder(default)]
+    pub templates_dir: Option<String>,
+    #[builder(default = EmailTransportKind::Log)]
+    pub transport: EmailTransportKind,
+    #[builder(default = SmtpConfig::default())]
+    pub smtp: SmtpConfig,
+    #[builder(default = FileSinkConfig::default())]
+    pub file: FileSinkConfig,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text; only for relays on a trusted network
+    None,
+    /// Upgrades a plain connection, usually on port 587
+    StartTls,
+    /// TLS from the first byte, usually on port 465
+    Tls,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct SmtpConfig {
+    #[builder(default = "localhost".to_string())]
+    pub host: String,
+    #[builder(default = 587)]
+    pub port: u16,
+    #[builder(default = SmtpTls::StartTls)]
+    pub tls: SmtpTls,
+    /// Authenticates only when both are set
+    #[builder(default)]
+    pub username: Option<String>,
+    #[builder(default)]
+    pub password: Option<String>,
+    /// Connections kept open to the relay
+    #[builder(default = 4)]
+    pub pool_size: u32,
+    #[builder(default = 30)]
+    pub timeout_seconds: u64,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct FileSinkConfig {
+    #[builder(default = "mail".to_string())]
+    pub directory: String,
+    /// Deliver into `new/` of a maildir instead of a flat directory
+    #[builder(default = false)]
+    pub maildir: bool,
+}
+
+impl Default for FileSinkConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// Run workers in this process; jobs are still enqueued when off, for
+    /// another instance sharing the database to run
+    #[builder(default = true)]
+    pub enabled: bool,
+    #[builder(default = 2)]
+    pub workers: usize,
+    /// Idle wait between polls for due jobs
+    #[builder(default = 1000)]
+    pub poll_interval_ms: u64,
+    /// How long a claimed job is hidden from other workers; a job running
+    /// longer is abandoned and retried
+    #[builder(default = 300)]
+    pub visibility_timeout_seconds: u64,
+    /// First retry delay, doubled on each further attempt
+    #[builder(default = 10)]
+    pub backoff_base_seconds: u64,
+    #[builder(default = 3600)]
+    pub backoff_max_seconds: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Where finished export files are kept until their link expires
+    #[builder(default = "exports".to_string())]
+    pub directory: String,
+    /// Lifetime of a finished export and its signed download link
+    #[builder(default = 3600)]
+    pub url_ttl_seconds: u64,
+    /// Exports stop here rather than growing without bound
+    #[builder(default = 100_000)]
+    pub max_rows: usize,
+    /// Rows read per query, and per response chunk, by streaming exports
+    #[builder(default = 500)]
+    pub stream_batch_size: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct ImportConfig {
+    /// Uploads wait here for their job; reports stay until their link expires
+    #[builder(default = "imports".to_string())]
+    pub directory: String,
+    /// Largest accepted upload
+    #[builder(default = 10 * 1024 * 1024)]
+    pub max_file_bytes: usize,
+    /// Longer sheets are rejected rather than partly imported
+    #[builder(default = 50_000)]
+    pub max_rows: usize,
+    /// Lifetime of a finished report and its signed download link
+    #[builder(default = 86_400)]
+    pub url_ttl_seconds: u64,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Files under `directory`
+    Local,
+    /// Any S3-compatible object store, as set in `s3`
+    S3,
+}
+
+/// Where uploaded files are kept and which are accepted. Their size is
+/// capped by `server.max_body_size_mb`.
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct UploadConfig {
+    #[builder(default = StorageBackend::Local)]
+    pub backend: StorageBackend,
+    #[builder(default = "uploads".to_string())]
+    pub directory: String,
+    #[builder(default = S3Config::default())]
+    pub s3: S3Config,
+    /// Checked against the type sniffed from the file's content, not the
+    /// one the client declares
+    #[builder(default = vec![
+        "image/png".to_string(),
+        "image/jpeg".to_string(),
+        "image/gif".to_string(),
+        "image/webp".to_string(),
+        "application/pdf".to_string(),
+    ])]
+    pub allowed_content_types: Vec<String>,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(default)]
+pub struct S3Config {
+    /// Scheme and host, e.g. `https://s3.us-east-1.amazonaws.com` or a local
+    /// stand-in such as `http://localhost:9000`
+    #[builder(default = "https://s3.amazonaws.com".to_string())]
+    pub endpoint: String,
+    #[builder(default)]
+    pub bucket: String,
+    #[builder(default = "us-east-1".to_string())]
+    pub region: String,
+    #[builder(default)]
+    pub access_key_id: String,
+    #[builder(default)]
+    pub secret_access_key: String,
+    /// `endpoint/bucket/key` rather than `bucket.endpoint/key`; most
+    /// self-hosted stores need this
+    #[builder(default = true)]
+    pub path_style: bool,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Builds an `AppConfig` from, in increasing order of precedence: the built-in
+/// defaults, a TOML/YAML/JSON file, `APP__SECTION__KEY` environment variables
+/// and explicit overrides (CLI flags).
+#[derive(Debug, Default)]
+pub struct ConfigLoader {
+    file: Option<PathBuf>,
+    file_required: bool,
+    env_source: Option<HashMap<String, String>>,
+    overrides: Vec<(String, config::Value)>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl Into<PathBuf>, required: bool) -> Self {
+        self.file = Some(path.into());
+        self.file_required = required;
+        self
+    }
+
+    /// Replaces the process environment as the source of `APP__*` variables.
+    pub fn with_env_source(mut self, vars: HashMap<String, String>) -> Self {
+        self.env_source = Some(vars);
+        self
+    }
+
+    pub fn with_override<V: Into<config::Value>>(mut self, key: &str, value: V) -> Self {
+        self.overrides.push((key.to_string(), value.into()));
+        self
+    }
+
+    pub fn with_override_option<V: Into<config::Value>>(self, key: &str, value: Option<V>) -> Self {
+        match value {
+            Some(value) => self.with_override(key, value),
+            None => self,
+        }
+    }
+
+    pub fn load(self) -> Result<AppConfig, ConfigLoadError> {
+        let defaults = Config::try_from(&AppConfig::default())?;
+        let mut builder = Config::builder().add_source(defaults);
+
+        if let Some(path) = &self.file {
+            if path.exists() {
+                let format = file_format(path)?;
+                builder = builder.add_source(File::from(path.as_path()).format(format));
+            } else if self.file_required {
+                return Err(ConfigLoadError::FileNotFound(path.clone()));
+            } else {
+                tracing::debug!(path = %path.display(), "Config file not found, using defaults");
+            }
+        }
+
+        builder = builder.add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .separator(ENV_SEPARATOR)
+                .try_parsing(true)
+                .source(self.env_source),
+        );
+
+        for (key, value) in self.overrides {
+            builder = builder.set_override(key, value)?;
+        }
+
+        Ok(builder.build()?.try_deserialize()?)
+    }
+}
+
+fn file_format(path: &Path) -> Result<FileFormat, ConfigLoadError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    match extension.as_deref() {
+        Some("toml") => Ok(FileFormat::Toml),
+        Some("yaml") | Some("yml") => Ok(FileFormat::Yaml),
+        Some("json") => Ok(FileFormat::Json),
+        _ => Err(ConfigLoadError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigLoadError {
+    #[error("Config file not found: {}", .0.display())]
+    FileNotFound(PathBuf),
+    #[error("Unsupported config file format: {} (expected .toml, .yaml, .yml or .json)", .0.display())]
+    UnsupportedFormat(PathBuf),
+    #[error("Invalid configuration: {0}")]
+    Invalid(#[from] config::ConfigError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("config-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_defaults_without_file() {
+        let config = ConfigLoader::new()
+            .with_file("does-not-exist.toml", false)
+            .with_env_source(HashMap::new())
+            .load()
+            .unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert!(config.uses_default_jwt_secret());
+    }
+
+    #[test]
+    fn test_missing_required_file() {
+        let result = ConfigLoader::new()
+            .with_file("does-not-exist.toml", true)
+            .load();
+        assert!(matches!(result, Err(ConfigLoadError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_file_formats() {
+        let toml = write_temp("app.toml", "[server]\nport = 9000\n");
+        let yaml = write_temp("app.yaml", "server:\n  port: 9001\n");
+        let json = write_temp("app.json", r#"{"server": {"port": 9002}}"#);
+
+        for (path, port) in [(toml, 9000), (yaml, 9001), (json, 9002)] {
+            let config = ConfigLoader::new()
+                .with_file(&path, true)
+                .with_env_source(HashMap::new())
+                .load()
+                .unwrap();
+            assert_eq!(config.server.port, port);
+            assert_eq!(config.server.host, "127.0.0.1");
+        }
+    }
+
+    #[test]
+    fn test_layering_precedence() {
+        let path = write_temp(
+            "app.toml",
+            "[server]\nport = 9000\nhost = \"0.0.0.0\"\n[auth]\njwt_secret = \"from-file\"\n",
+        );
+        let env = HashMap::from([
+            ("APP__SERVER__PORT".to_string(), "9100".to_string()),
+            ("APP__DATABASE__MAX_CONNECTIONS".to_string(), "42".to_string()),
+        ]);
+
+        let config = ConfigLoader::new()
+            .with_file(&path, true)
+            .with_env_source(env)
+            .with_override("server.host", "10.0.0.1")
+            .load()
+            .unwrap();
+
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.server.host, "10.0.0.1");
+        assert_eq!(config.database.max_connections, 42);
+        assert_eq!(config.auth.jwt_secret, "from-file");
+    }
+
+    #[test]
+    fn test_error_names_key_and_file() {
+        let path = write_temp("app.toml", "[server]\nport = \"not-a-port\"\n");
+        let err = ConfigLoader::new()
+            .with_file(&path, true)
+            .with_env_source(HashMap::new())
+            .load()
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("server.port"), "{}", err);
+        assert!(err.contains("app.toml"), "{}", err);
+    }
+
+    #[test]
+    fn test_tax_jurisdictions_from_file() {
+        let path = write_temp(
+            "app.toml",
+            "[tax]\ndefault_rate = \"0\"\n\n[[tax.jurisdictions]]\ncountry = \"US\"\nstate = \"NY\"\n\
+             rate = \"0.04\"\n\n[[tax.jurisdictions]]\ncountry = \"DE\"\nrate = 0.19\n",
+        );
+        let config = ConfigLoader::new()
+            .with_file(&path, true)
+            .with_env_source(HashMap::new())
+            .load()
+            .unwrap();
+
+        assert_eq!(config.tax.default_rate, Decimal::ZERO);
+        assert_eq!(config.tax.jurisdictions.len(), 2);
+        assert_eq!(config.tax.jurisdictions[0].state.as_deref(), Some("NY"));
+        assert_eq!(config.tax.jurisdictions[0].rate, Decimal::new(4, 2));
+        assert_eq!(config.tax.jurisdictions[1].postal_prefix, None);
+        assert_eq!(config.tax.jurisdictions[1].rate, Decimal::new(19, 2));
+    }
+
+    #[test]
+    fn test_shipping_carriers_replace_default() {
+        let path = write_temp(
+            "app.yaml",
+            "shipping:\n  zones:\n    - code: eu\n      countries: [DE, FR]\n  carriers:\n    \
+             - code: dhl\n      name: DHL\n      methods:\n        - code: parcel\n          \
+             name: Parcel\n          min_days: 2\n          max_days: 4\n          rates:\n            \
+             - zone: eu\n              free_over: \"50\"\n              tiers:\n                \
+             - max_weight: \"2\"\n                  price: \"4.90\"\n",
+        );
+        let config = ConfigLoader::new()
+            .with_file(&path, true)
+            .with_env_source(HashMap::new())
+            .load()
+            .unwrap();
+
+        assert_eq!(config.shipping.volumetric_divisor, Decimal::from(5000));
+        assert_eq!(config.shipping.zones[0].countries, vec!["DE", "FR"]);
+        assert_eq!(config.shipping.carriers.len(), 1);
+        let rate = &config.shipping.carriers[0].methods[0].rates[0];
+        assert_eq!(rate.free_over, Some(Decimal::from(50)));
+        assert_eq!(rate.tiers[0].max_weight, Some(Decimal::from(2)));
+        assert_eq!(rate.tiers[0].price, Decimal::new(490, 2));
+    }
+
+    #[test]
+    fn test_unsupported_extension() {
+        let path = write_temp("app.ini", "");
+        let result = ConfigLoader::new().with_file(&path, true).load();
+        assert!(matches!(result, Err(ConfigLoadError::UnsupportedFormat(_))));
+    }
+}
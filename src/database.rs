@@ -1,182 +1,246 @@
-use anyhow::Result;
-use sqlx::{Pool, Sqlite, sqlite::SqlitePoolOptions};
+pub mod migrations;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use sqlx::any::{AnyConnectOptions, AnyPoolOptions, AnyTypeInfo, AnyValueRef};
+use sqlx::decode::Decode;
+use sqlx::error::BoxDynError;
+use sqlx::{Any, AnyPool, ConnectOptions, Type, TypeInfo, ValueRef};
+use std::str::FromStr;
+use std::sync::Once;
 use std::time::Duration;
 
+use self::migrations::Migrator;
+use crate::config::DatabaseConfig;
+
+/// Database driver, chosen from the URL scheme of `DatabaseConfig::url`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Self> {
+        if url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite)
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Ok(Backend::Postgres)
+        } else {
+            bail!("Unsupported database URL scheme: {}", url)
+        }
+    }
+}
+
+/// Connection pool shared by all services. Queries go through sqlx's `Any`
+/// driver so the same SQL runs on SQLite and Postgres; columns therefore
+/// stick to TEXT, INTEGER and REAL, and placeholders use the `$N` form.
+/// Money is stored as BIGINT minor units next to a currency column.
 pub struct Database {
-    pub pool: Pool<Sqlite>,
+    pub pool: AnyPool,
+    backend: Backend,
+}
+
+pub fn install_drivers() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(sqlx::any::install_default_drivers);
 }
 
 impl Database {
-    pub async fn new() -> Result<Self> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(10)
-            .acquire_timeout(Duration::from_secs(30))
-            .connect("sqlite::memory:")
-            .await?;
-
-        let db = Self { pool };
+    /// Connects and brings the schema up to date. Refuses to start if the
+    /// database was migrated by a newer binary.
+    pub async fn new(config: &DatabaseConfig) -> Result<Self> {
+        let db = Self::connect(config).await?;
         db.run_migrations().await?;
         Ok(db)
     }
 
-    async fn run_migrations(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                email TEXT UNIQUE NOT NULL,
-                username TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                first_name TEXT,
-                last_name TEXT,
-                avatar_url TEXT,
-                bio TEXT,
-                role TEXT NOT NULL DEFAULT 'user',
-                status TEXT NOT NULL DEFAULT 'active',
-                email_verified INTEGER NOT NULL DEFAULT 0,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                last_login_at TEXT
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS posts (
-                id TEXT PRIMARY KEY,
-                author_id TEXT NOT NULL,
-                title TEXT NOT NULL,
-                slug TEXT UNIQUE NOT NULL,
-                content TEXT NOT NULL,
-                excerpt TEXT,
-                featured_image_url TEXT,
-                status TEXT NOT NULL DEFAULT 'draft',
-                visibility TEXT NOT NULL DEFAULT 'public',
-                tags TEXT,
-                categories TEXT,
-                view_count INTEGER NOT NULL DEFAULT 0,
-                like_count INTEGER NOT NULL DEFAULT 0,
-                comment_count INTEGER NOT NULL DEFAULT 0,
-                published_at TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (author_id) REFERENCES users(id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS products (
-                id TEXT PRIMARY KEY,
-                sku TEXT UNIQUE NOT NULL,
-                name TEXT NOT NULL,
-                slug TEXT UNIQUE NOT NULL,
-                description TEXT NOT NULL,
-                short_description TEXT,
-                price REAL NOT NULL,
-                sale_price REAL,
-                cost_price REAL,
-                currency TEXT NOT NULL DEFAULT 'USD',
-                quantity INTEGER NOT NULL DEFAULT 0,
-                low_stock_threshold INTEGER NOT NULL DEFAULT 10,
-                weight REAL,
-                dimensions TEXT,
-                images TEXT,
-                thumbnail_url TEXT,
-                category_id TEXT,
-                brand_id TEXT,
-                status TEXT NOT NULL DEFAULT 'active',
-                is_featured INTEGER NOT NULL DEFAULT 0,
-                is_digital INTEGER NOT NULL DEFAULT 0,
-                meta_title TEXT,
-                meta_description TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS orders (
-                id TEXT PRIMARY KEY,
-                order_number TEXT UNIQUE NOT NULL,
-                customer_id TEXT NOT NULL,
-                status TEXT NOT NULL DEFAULT 'pending',
-                payment_status TEXT NOT NULL DEFAULT 'pending',
-                fulfillment_status TEXT NOT NULL DEFAULT 'unfulfilled',
-                subtotal REAL NOT NULL,
-                tax_amount REAL NOT NULL DEFAULT 0,
-                shipping_amount REAL NOT NULL DEFAULT 0,
-                discount_amount REAL NOT NULL DEFAULT 0,
-                total REAL NOT NULL,
-                currency TEXT NOT NULL DEFAULT 'USD',
-                billing_address TEXT NOT NULL,
-                shipping_address TEXT NOT NULL,
-                shipping_method TEXT,
-                tracking_number TEXT,
-                notes TEXT,
-                metadata TEXT,
-                placed_at TEXT NOT NULL,
-                paid_at TEXT,
-                shipped_at TEXT,
-                delivered_at TEXT,
-                cancelled_at TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (customer_id) REFERENCES users(id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS order_items (
-                id TEXT PRIMARY KEY,
-                order_id TEXT NOT NULL,
-                product_id TEXT NOT NULL,
-                variant_id TEXT,
-                sku TEXT NOT NULL,
-                name TEXT NOT NULL,
-                quantity INTEGER NOT NULL,
-                unit_price REAL NOT NULL,
-                total_price REAL NOT NULL,
-                tax_amount REAL NOT NULL DEFAULT 0,
-                discount_amount REAL NOT NULL DEFAULT 0,
-                metadata TEXT,
-                FOREIGN KEY (order_id) REFERENCES orders(id),
-                FOREIGN KEY (product_id) REFERENCES products(id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS sessions (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL,
-                token_hash TEXT NOT NULL,
-                expires_at TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Connects without touching the schema (used by the `migrate` subcommand).
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
+        install_drivers();
+
+        let backend = Backend::from_url(&config.url)?;
+        let in_memory = backend == Backend::Sqlite && config.url.contains(":memory:");
+        let url = match backend {
+            // `Any` re-parses the URL per connection, and every `:memory:` parse
+            // yields a new database; a named shared-cache URI keeps one per pool
+            Backend::Sqlite if in_memory => format!(
+                "sqlite:file:memdb-{}?mode=memory&cache=shared",
+                uuid::Uuid::new_v4().simple()
+            ),
+            Backend::Sqlite => sqlite_create_if_missing(&config.url),
+            Backend::Postgres => config.url.clone(),
+        };
+
+        let mut options = AnyConnectOptions::from_str(&url)?;
+        if !config.enable_logging {
+            options = options.disable_statement_logging();
+        }
+
+        let mut pool_options = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(config.timeout_seconds));
+        if in_memory {
+            // An in-memory database disappears with its last connection
+            pool_options = pool_options
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None);
+        }
+
+        let pool = pool_options.connect_with(options).await?;
+
+        if backend == Backend::Sqlite && !in_memory && config.sqlite_wal {
+            sqlx::query("PRAGMA journal_mode = WAL").execute(&pool).await?;
+        }
 
+        tracing::info!(backend = ?backend, "Connected to database");
+
+        Ok(Self { pool, backend })
+    }
+
+    /// A fresh, migrated in-memory SQLite database.
+    pub async fn in_memory() -> Result<Self> {
+        Self::new(&DatabaseConfig::default()).await
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn migrator(&self) -> Migrator<'_> {
+        Migrator::new(&self.pool)
+    }
+
+    async fn run_migrations(&self) -> Result<()> {
+        let applied = self.migrator().up(None).await?;
+        if !applied.is_empty() {
+            tracing::info!(count = applied.len(), "Database migrations applied");
+        }
         Ok(())
     }
 }
+
+/// Nullable column for `FromRow` rows, used as `#[sqlx(try_from = "Nullable<T>")]`.
+///
+/// sqlx 0.7's `Any` values never report themselves as NULL, so a plain
+/// `Option<T>` fails to decode a NULL column; this checks the value kind instead.
+pub struct Nullable<T>(pub Option<T>);
+
+impl<T: Type<Any>> Type<Any> for Nullable<T> {
+    fn type_info() -> AnyTypeInfo {
+        T::type_info()
+    }
+
+    fn compatible(ty: &AnyTypeInfo) -> bool {
+        is_null_type(ty) || T::compatible(ty)
+    }
+}
+
+impl<'r, T: Decode<'r, Any>> Decode<'r, Any> for Nullable<T> {
+    fn decode(value: AnyValueRef<'r>) -> std::result::Result<Self, BoxDynError> {
+        if is_null_type(&value.type_info()) {
+            Ok(Nullable(None))
+        } else {
+            T::decode(value).map(|v| Nullable(Some(v)))
+        }
+    }
+}
+
+fn is_null_type(ty: &AnyTypeInfo) -> bool {
+    ty.name() == "NULL"
+}
+
+impl<T> From<Nullable<T>> for Option<T> {
+    fn from(value: Nullable<T>) -> Self {
+        value.0
+    }
+}
+
+/// Reads an id stored as TEXT.
+pub fn parse_uuid(value: &str) -> crate::error::Result<uuid::Uuid> {
+    uuid::Uuid::parse_str(value).map_err(|e| {
+        crate::error::AppError::InternalError(format!("Invalid UUID '{}': {}", value, e))
+    })
+}
+
+/// Reads an RFC 3339 timestamp stored as TEXT.
+pub fn parse_timestamp(value: &str) -> crate::error::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| {
+            crate::error::AppError::InternalError(format!("Invalid timestamp '{}': {}", value, e))
+        })
+}
+
+/// Reads a decimal stored as TEXT.
+pub fn parse_decimal(value: &str) -> crate::error::Result<rust_decimal::Decimal> {
+    value.parse().map_err(|e| {
+        crate::error::AppError::InternalError(format!("Invalid decimal '{}': {}", value, e))
+    })
+}
+
+/// A unique constraint failure becomes a conflict reporting `message`;
+/// anything else stays a database error.
+pub fn map_unique_violation(error: sqlx::Error, message: &str) -> crate::error::AppError {
+    match &error {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+            crate::error::AppError::Conflict(message.to_string())
+        }
+        _ => crate::error::AppError::DatabaseError(error),
+    }
+}
+
+/// On-disk SQLite databases are created on first use unless a `mode` is given.
+fn sqlite_create_if_missing(url: &str) -> String {
+    if url.contains("mode=") {
+        url.to_string()
+    } else if url.contains('?') {
+        format!("{}&mode=rwc", url)
+    } else {
+        format!("{}?mode=rwc", url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_url() {
+        assert_eq!(Backend::from_url("sqlite::memory:").unwrap(), Backend::Sqlite);
+        assert_eq!(Backend::from_url("sqlite://data/app.db").unwrap(), Backend::Sqlite);
+        assert_eq!(
+            Backend::from_url("postgres://app@localhost/app").unwrap(),
+            Backend::Postgres
+        );
+        assert!(Backend::from_url("mysql://localhost/app").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_database_persists_across_pools() {
+        let path = std::env::temp_dir().join(format!("db-test-{}.db", uuid::Uuid::new_v4()));
+        let config = DatabaseConfig::builder()
+            .url(format!("sqlite://{}", path.display()))
+            .build();
+
+        let db = Database::new(&config).await.unwrap();
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+        sqlx::query("INSERT INTO users (id, email, username, password_hash, created_at, updated_at) VALUES ('1', 'a@b.c', 'a', 'x', 'now', 'now')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.pool.close().await;
+
+        let reopened = Database::new(&config).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&reopened.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,370 @@
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::AnyPool;
+
+/// A schema change shipped with the binary. Files live in `migrations/` as
+/// `NNNN_name.up.sql` and, when reversible, `NNNN_name.down.sql`.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: Option<&'static str>,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.up.as_bytes()))
+    }
+}
+
+macro_rules! migration {
+    ($version:expr, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $file,
+            up: include_str!(concat!("../../migrations/", $file, ".up.sql")),
+            down: Some(include_str!(concat!("../../migrations/", $file, ".down.sql"))),
+        }
+    };
+}
+
+/// All migrations known to this binary, in ascending version order.
+pub static MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_session_rotation"),
+    migration!(3, "0003_order_events"),
+    migration!(4, "0004_coupons"),
+    migration!(5, "0005_order_tax"),
+    migration!(6, "0006_money_minor_units"),
+    migration!(7, "0007_order_exchange_rate"),
+    migration!(8, "0008_payments"),
+    migration!(9, "0009_webhook_events"),
+    migration!(10, "0010_refund_items"),
+    migration!(11, "0011_idempotency_keys"),
+    migration!(12, "0012_jobs"),
+    migration!(13, "0013_export_cursors"),
+    migration!(14, "0014_uploads"),
+    migration!(15, "0015_open_payment_intent"),
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<String>,
+    pub checksum_matches: bool,
+}
+
+pub struct Migrator<'a> {
+    pool: &'a AnyPool,
+    migrations: &'a [Migration],
+}
+
+impl<'a> Migrator<'a> {
+    pub fn new(pool: &'a AnyPool) -> Self {
+        Self {
+            pool,
+            migrations: MIGRATIONS,
+        }
+    }
+
+    pub fn with_migrations(mut self, migrations: &'a [Migration]) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    pub async fn ensure_table(&self) -> Result<(), MigrationError> {
+        sqlx::raw_sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrationError> {
+        self.ensure_table().await?;
+        let rows = sqlx::query_as(
+            "SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version",
+        )
+        .fetch_all(self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn current_version(&self) -> Result<i64, MigrationError> {
+        Ok(self.applied().await?.last().map(|m| m.version).unwrap_or(0))
+    }
+
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>, MigrationError> {
+        let applied = self.applied().await?;
+
+        let mut statuses: Vec<MigrationStatus> = self
+            .migrations
+            .iter()
+            .map(|m| {
+                let record = applied.iter().find(|a| a.version == m.version);
+                MigrationStatus {
+                    version: m.version,
+                    name: m.name.to_string(),
+                    applied_at: record.map(|a| a.applied_at.clone()),
+                    checksum_matches: record.is_none_or(|a| a.checksum == m.checksum()),
+                }
+            })
+            .collect();
+
+        // Versions recorded by a newer binary
+        for a in &applied {
+            if !self.migrations.iter().any(|m| m.version == a.version) {
+                statuses.push(MigrationStatus {
+                    version: a.version,
+                    name: a.name.clone(),
+                    applied_at: Some(a.applied_at.clone()),
+                    checksum_matches: false,
+                });
+            }
+        }
+        statuses.sort_by_key(|s| s.version);
+
+        Ok(statuses)
+    }
+
+    /// Fails if the database was migrated by a newer binary or an applied
+    /// migration was edited after the fact.
+    pub async fn verify(&self) -> Result<(), MigrationError> {
+        let applied = self.applied().await?;
+        let latest = self.migrations.last().map(|m| m.version).unwrap_or(0);
+
+        if let Some(newest) = applied.last() {
+            if newest.version > latest {
+                return Err(MigrationError::SchemaAhead {
+                    database: newest.version,
+                    binary: latest,
+                });
+            }
+        }
+
+        for record in &applied {
+            match self.migrations.iter().find(|m| m.version == record.version) {
+                Some(m) if m.checksum() != record.checksum => {
+                    return Err(MigrationError::ChecksumMismatch {
+                        version: m.version,
+                        name: m.name.to_string(),
+                    });
+                }
+                Some(_) => {}
+                None => return Err(MigrationError::UnknownVersion(record.version)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies pending migrations up to and including `target` (or all of them).
+    pub async fn up(&self, target: Option<i64>) -> Result<Vec<i64>, MigrationError> {
+        self.verify().await?;
+        let current = self.current_version().await?;
+        let target = target.unwrap_or(i64::MAX);
+
+        let mut applied = Vec::new();
+        for migration in self
+            .migrations
+            .iter()
+            .filter(|m| m.version > current && m.version <= target)
+        {
+            let mut tx = self.pool.begin().await?;
+            sqlx::raw_sql(migration.up).execute(&mut *tx).await?;
+            sqlx::query(
+                "INSERT INTO schema_migrations (version, name, checksum, applied_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(migration.checksum())
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            tracing::info!(version = migration.version, name = migration.name, "Applied migration");
+            applied.push(migration.version);
+        }
+
+        Ok(applied)
+    }
+
+    /// Reverts the `steps` most recently applied migrations.
+    pub async fn down(&self, steps: usize) -> Result<Vec<i64>, MigrationError> {
+        self.verify().await?;
+        let applied = self.applied().await?;
+
+        let mut reverted = Vec::new();
+        for record in applied.iter().rev().take(steps) {
+            let migration = self
+                .migrations
+                .iter()
+                .find(|m| m.version == record.version)
+                .ok_or(MigrationError::UnknownVersion(record.version))?;
+            let down = migration
+                .down
+                .ok_or(MigrationError::Irreversible(migration.version))?;
+
+            let mut tx = self.pool.begin().await?;
+            sqlx::raw_sql(down).execute(&mut *tx).await?;
+            sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            tracing::info!(version = migration.version, name = migration.name, "Reverted migration");
+            reverted.push(migration.version);
+        }
+
+        Ok(reverted)
+    }
+
+    /// Reverts and re-applies the most recent migration.
+    pub async fn redo(&self) -> Result<Option<i64>, MigrationError> {
+        let reverted = self.down(1).await?;
+        match reverted.first() {
+            Some(&version) => {
+                self.up(Some(version)).await?;
+                Ok(Some(version))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Database schema version {database} is newer than this binary supports ({binary})")]
+    SchemaAhead { database: i64, binary: i64 },
+    #[error("Migration {version} ({name}) has changed since it was applied")]
+    ChecksumMismatch { version: i64, name: String },
+    #[error("Database has migration {0} which this binary does not know about")]
+    UnknownVersion(i64),
+    #[error("Migration {0} has no down script")]
+    Irreversible(i64),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::any::AnyPoolOptions;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "create_widgets",
+            up: "CREATE TABLE widgets (id TEXT PRIMARY KEY);",
+            down: Some("DROP TABLE widgets;"),
+        },
+        Migration {
+            version: 2,
+            name: "add_widget_name",
+            up: "ALTER TABLE widgets ADD COLUMN name TEXT;",
+            down: None,
+        },
+    ];
+
+    async fn pool() -> AnyPool {
+        crate::database::install_drivers();
+        AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_up_down_and_status() {
+        let pool = pool().await;
+        let migrator = Migrator::new(&pool).with_migrations(TEST_MIGRATIONS);
+
+        assert_eq!(migrator.up(Some(1)).await.unwrap(), vec![1]);
+        assert_eq!(migrator.up(None).await.unwrap(), vec![2]);
+        assert!(migrator.up(None).await.unwrap().is_empty());
+        assert_eq!(migrator.current_version().await.unwrap(), 2);
+
+        assert!(matches!(
+            migrator.down(1).await,
+            Err(MigrationError::Irreversible(2))
+        ));
+
+        let status = migrator.status().await.unwrap();
+        assert!(status.iter().all(|s| s.applied_at.is_some() && s.checksum_matches));
+    }
+
+    #[tokio::test]
+    async fn test_redo() {
+        let pool = pool().await;
+        let migrator = Migrator::new(&pool).with_migrations(&TEST_MIGRATIONS[..1]);
+
+        migrator.up(None).await.unwrap();
+        assert_eq!(migrator.redo().await.unwrap(), Some(1));
+        assert_eq!(migrator.current_version().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_schema_ahead_of_binary() {
+        let pool = pool().await;
+        Migrator::new(&pool)
+            .with_migrations(TEST_MIGRATIONS)
+            .up(None)
+            .await
+            .unwrap();
+
+        let older = Migrator::new(&pool).with_migrations(&TEST_MIGRATIONS[..1]);
+        assert!(matches!(
+            older.verify().await,
+            Err(MigrationError::SchemaAhead {
+                database: 2,
+                binary: 1
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_detects_edited_migration() {
+        let pool = pool().await;
+        Migrator::new(&pool)
+            .with_migrations(&TEST_MIGRATIONS[..1])
+            .up(None)
+            .await
+            .unwrap();
+
+        let edited = [Migration {
+            up: "CREATE TABLE widgets (id TEXT PRIMARY KEY, extra TEXT);",
+            ..TEST_MIGRATIONS[0]
+        }];
+        let migrator = Migrator::new(&pool).with_migrations(&edited);
+        assert!(matches!(
+            migrator.verify().await,
+            Err(MigrationError::ChecksumMismatch { version: 1, .. })
+        ));
+    }
+}
@@ -41,10 +41,60 @@ pub enum AppError {
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
     #[error("Service unavailable: {0}")]
     ServiceUnavailable(String),
 }
 
+impl From<crate::money::MoneyError> for AppError {
+    fn from(error: crate::money::MoneyError) -> Self {
+        AppError::BadRequest(error.to_string())
+    }
+}
+
+impl From<crate::exchange::ExchangeError> for AppError {
+    fn from(error: crate::exchange::ExchangeError) -> Self {
+        match error {
+            crate::exchange::ExchangeError::UnknownPair(..) => AppError::BadRequest(error.to_string()),
+            _ => AppError::ServiceUnavailable(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::storage::StorageError> for AppError {
+    fn from(error: crate::storage::StorageError) -> Self {
+        match error {
+            crate::storage::StorageError::NotFound(_) => AppError::NotFound(error.to_string()),
+            _ => AppError::ServiceUnavailable(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::services::payment_service::PaymentError> for AppError {
+    fn from(error: crate::services::payment_service::PaymentError) -> Self {
+        use crate::services::payment_service::PaymentError;
+        match error {
+            PaymentError::NotFound(_) => AppError::NotFound(error.to_string()),
+            PaymentError::PaymentFailed(_) | PaymentError::InvalidPaymentMethod(_) => {
+                AppError::BadRequest(error.to_string())
+            }
+            PaymentError::InvalidState(_) | PaymentError::RefundFailed(_) => {
+                AppError::Conflict(error.to_string())
+            }
+            PaymentError::ApiError(_) => AppError::ServiceUnavailable(error.to_string()),
+            PaymentError::Misconfigured(_) => AppError::InternalError(error.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
@@ -92,6 +142,17 @@ impl IntoResponse for AppError {
             ),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "bad_request", msg.clone()),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg.clone()),
+            AppError::UnprocessableEntity(msg) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "unprocessable_entity", msg.clone())
+            }
+            AppError::PayloadTooLarge(msg) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", msg.clone())
+            }
+            AppError::UnsupportedMediaType(msg) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                msg.clone(),
+            ),
             AppError::ServiceUnavailable(msg) => {
                 (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable", msg.clone())
             }
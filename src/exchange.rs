@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::cache::{cache_key, CacheManager};
+use crate::config::{ExchangeConfig, ExchangeRateConfig, ExternalServices, RateSource};
+use crate::money::Money;
+use crate::services::external_api_service::{
+    CurrencyExchangeRate, ExternalApiError, ExternalApiService,
+};
+
+#[derive(Debug, Error)]
+pub enum ExchangeError {
+    #[error("No exchange rate from {0} to {1}")]
+    UnknownPair(String, String),
+    #[error("Exchange rate provider returned an unusable rate: {0}")]
+    InvalidRate(String),
+    #[error("Exchange rate provider failed: {0}")]
+    Provider(#[from] ExternalApiError),
+}
+
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+    /// Looks up a pair of distinct, uppercase currency codes.
+    async fn fetch(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError>;
+
+    /// Units of `target` one unit of `base` buys; 1 for the same currency.
+    async fn rate(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError> {
+        let (base, target) = (base.to_ascii_uppercase(), target.to_ascii_uppercase());
+        if base == target {
+            return Ok(Decimal::ONE);
+        }
+        self.fetch(&base, &target).await
+    }
+
+    async fn convert(&self, money: &Money, target: &str) -> Result<Money, ExchangeError> {
+        let rate = self.rate(&money.currency, target).await?;
+        Ok(money.exchange(rate, target))
+    }
+}
+
+/// Builds the configured provider, cached in `cache`.
+pub fn from_config(
+    config: &ExchangeConfig,
+    external: &ExternalServices,
+    cache: Arc<CacheManager>,
+) -> Arc<dyn RateProvider> {
+    let provider: Arc<dyn RateProvider> = match config.source {
+        RateSource::Static => Arc::new(StaticRates::new(&config.rates)),
+        RateSource::Http => Arc::new(HttpRates::new(
+            ExternalApiService::new(external.api_base_url.clone(), external.api_key.clone()),
+            config.endpoint.clone(),
+        )),
+    };
+    Arc::new(CachedRates::new(provider, cache))
+}
+
+/// A fixed table; each configured pair also answers its inverse.
+#[derive(Debug, Default)]
+pub struct StaticRates {
+    rates: HashMap<(String, String), Decimal>,
+}
+
+impl StaticRates {
+    pub fn new(rates: &[ExchangeRateConfig]) -> Self {
+        let mut table = HashMap::new();
+        for pair in rates.iter().filter(|pair| pair.rate > Decimal::ZERO) {
+            let base = pair.base.to_ascii_uppercase();
+            let target = pair.target.to_ascii_uppercase();
+            table
+                .entry((target.clone(), base.clone()))
+                .or_insert(Decimal::ONE / pair.rate);
+            table.insert((base, target), pair.rate);
+        }
+        Self { rates: table }
+    }
+}
+
+#[async_trait]
+impl RateProvider for StaticRates {
+    async fn fetch(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError> {
+        self.rates
+            .get(&(base.to_string(), target.to_string()))
+            .copied()
+            .ok_or_else(|| ExchangeError::UnknownPair(base.to_string(), target.to_string()))
+    }
+}
+
+/// Rates from a remote API through `ExternalApiService`.
+pub struct HttpRates {
+    api: ExternalApiService,
+    endpoint: String,
+}
+
+impl HttpRates {
+    pub fn new(api: ExternalApiService, endpoint: String) -> Self {
+        Self { api, endpoint }
+    }
+}
+
+#[async_trait]
+impl RateProvider for HttpRates {
+    async fn fetch(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError> {
+        let quote: CurrencyExchangeRate = self
+            .api
+            .get(&format!("{}?base={}&target={}", self.endpoint, base, target))
+            .await?;
+        if !quote.base.eq_ignore_ascii_case(base) || !quote.target.eq_ignore_ascii_case(target) {
+            return Err(ExchangeError::InvalidRate(format!(
+                "asked for {}/{}, got {}/{}",
+                base, target, quote.base, quote.target
+            )));
+        }
+        Decimal::try_from(quote.rate)
+            .ok()
+            .filter(|rate| *rate > Decimal::ZERO)
+            .ok_or_else(|| ExchangeError::InvalidRate(quote.rate.to_string()))
+    }
+}
+
+/// Remembers another provider's answers in `CacheManager` for its TTL.
+pub struct CachedRates {
+    inner: Arc<dyn RateProvider>,
+    cache: Arc<CacheManager>,
+}
+
+impl CachedRates {
+    pub fn new(inner: Arc<dyn RateProvider>, cache: Arc<CacheManager>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+#[async_trait]
+impl RateProvider for CachedRates {
+    async fn fetch(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError> {
+        let key = cache_key("exchange_rate", &[base, target]);
+        if let Some(rate) = self.cache.get_string(&key).await.and_then(|r| r.parse().ok()) {
+            return Ok(rate);
+        }
+
+        let rate = self.inner.fetch(base, target).await?;
+        self.cache.set_string(key, rate.to_string()).await;
+        Ok(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn usd_eur() -> StaticRates {
+        StaticRates::new(&[ExchangeRateConfig {
+            base: "usd".to_string(),
+            target: "EUR".to_string(),
+            rate: dec("0.8"),
+        }])
+    }
+
+    #[tokio::test]
+    async fn test_static_rates_and_conversion() {
+        let rates = usd_eur();
+        assert_eq!(rates.rate("USD", "eur").await.unwrap(), dec("0.8"));
+        assert_eq!(rates.rate("EUR", "USD").await.unwrap(), dec("1.25"));
+        assert_eq!(rates.rate("JPY", "jpy").await.unwrap(), Decimal::ONE);
+        assert!(matches!(
+            rates.rate("USD", "GBP").await,
+            Err(ExchangeError::UnknownPair(_, _))
+        ));
+
+        let price = Money::new(dec("19.99"), "USD");
+        assert_eq!(
+            rates.convert(&price, "EUR").await.unwrap(),
+            Money::new(dec("15.99"), "EUR")
+        );
+    }
+
+    struct Counting {
+        inner: StaticRates,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl RateProvider for Counting {
+        async fn fetch(&self, base: &str, target: &str) -> Result<Decimal, ExchangeError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.fetch(base, target).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_rates_hit_provider_once() {
+        let counting = Arc::new(Counting {
+            inner: usd_eur(),
+            calls: AtomicUsize::new(0),
+        });
+        let cached = CachedRates::new(counting.clone(), Arc::new(CacheManager::new()));
+
+        for _ in 0..3 {
+            assert_eq!(cached.rate("USD", "EUR").await.unwrap(), dec("0.8"));
+        }
+        assert!(cached.rate("USD", "GBP").await.is_err());
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+    }
+}
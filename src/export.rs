@@ -0,0 +1,254 @@
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use rust_xlsxwriter::{Format, Workbook};
+use serde_json::Value;
+
+use crate::models::ExportFormat;
+
+pub mod stream;
+
+/// Rows ready to write: one value per header, in header order.
+#[derive(Debug, Clone, Default)]
+pub struct ExportTable {
+    pub title: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("XLSX error: {0}")]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+    #[error("PDF error: {0}")]
+    Pdf(String),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            ExportFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// Writes `table` as a complete file. JSON is always an array of objects
+/// keyed by header, so `include_headers` only affects the other formats.
+pub fn render(
+    table: &ExportTable,
+    format: &ExportFormat,
+    include_headers: bool,
+) -> Result<Vec<u8>, ExportError> {
+    match format {
+        ExportFormat::Csv => write_csv(table, include_headers),
+        ExportFormat::Json => write_json(table),
+        ExportFormat::Xlsx => write_xlsx(table, include_headers),
+        ExportFormat::Pdf => write_pdf(table, include_headers),
+    }
+}
+
+/// A value as one cell of text: strings unquoted, nested values as JSON.
+pub fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn write_csv(table: &ExportTable, include_headers: bool) -> Result<Vec<u8>, ExportError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if include_headers {
+        writer.write_record(&table.headers)?;
+    }
+    for row in &table.rows {
+        writer.write_record(row.iter().map(cell_text))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| ExportError::Csv(e.into_error().into()))
+}
+
+fn write_json(table: &ExportTable) -> Result<Vec<u8>, ExportError> {
+    let records: Vec<serde_json::Map<String, Value>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            table
+                .headers
+                .iter()
+                .cloned()
+                .zip(row.iter().cloned())
+                .collect()
+        })
+        .collect();
+    Ok(serde_json::to_vec_pretty(&records)?)
+}
+
+fn write_xlsx(table: &ExportTable, include_headers: bool) -> Result<Vec<u8>, ExportError> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    // Sheet names are limited to 31 characters
+    sheet.set_name(table.title.chars().take(31).collect::<String>())?;
+
+    let mut first_row = 0;
+    if include_headers {
+        let bold = Format::new().set_bold();
+        for (col, header) in table.headers.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, header, &bold)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+        first_row = 1;
+    }
+
+    for (index, row) in table.rows.iter().enumerate() {
+        let row_num = first_row + index as u32;
+        for (col, value) in row.iter().enumerate() {
+            let col = col as u16;
+            match value {
+                Value::Null => {}
+                Value::Bool(b) => {
+                    sheet.write_boolean(row_num, col, *b)?;
+                }
+                Value::Number(n) => match n.as_f64() {
+                    Some(number) => {
+                        sheet.write_number(row_num, col, number)?;
+                    }
+                    None => {
+                        sheet.write_string(row_num, col, n.to_string())?;
+                    }
+                },
+                other => {
+                    sheet.write_string(row_num, col, cell_text(other))?;
+                }
+            }
+        }
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+const PAGE_WIDTH_MM: f32 = 297.0;
+const PAGE_HEIGHT_MM: f32 = 210.0;
+const MARGIN_MM: f32 = 10.0;
+const LINE_HEIGHT_MM: f32 = 5.0;
+const FONT_SIZE: f32 = 8.0;
+/// Rough Helvetica advance at `FONT_SIZE`, used to clip cells to their column
+const CHAR_WIDTH_MM: f32 = 1.6;
+
+/// A plain landscape table: equal-width columns, cells clipped to fit and
+/// the header row repeated on every page.
+fn write_pdf(table: &ExportTable, include_headers: bool) -> Result<Vec<u8>, ExportError> {
+    let (document, page, layer) = PdfDocument::new(
+        &table.title,
+        Mm(PAGE_WIDTH_MM),
+        Mm(PAGE_HEIGHT_MM),
+        "Layer 1",
+    );
+    let font = document
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(|e| ExportError::Pdf(e.to_string()))?;
+    let bold = document
+        .add_builtin_font(BuiltinFont::HelveticaBold)
+        .map_err(|e| ExportError::Pdf(e.to_string()))?;
+
+    let columns = table.headers.len().max(1);
+    let column_width = (PAGE_WIDTH_MM - 2.0 * MARGIN_MM) / columns as f32;
+    let max_chars = ((column_width / CHAR_WIDTH_MM) as usize).max(1);
+    let clip = |text: String| -> String {
+        if text.chars().count() <= max_chars {
+            text
+        } else {
+            let kept: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+            format!("{}…", kept)
+        }
+    };
+
+    let mut layer = document.get_page(page).get_layer(layer);
+    let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+    layer.use_text(&table.title, FONT_SIZE + 4.0, Mm(MARGIN_MM), Mm(y), &bold);
+    y -= 2.0 * LINE_HEIGHT_MM;
+
+    let write_headers = |layer: &printpdf::PdfLayerReference, y: f32| {
+        for (col, header) in table.headers.iter().enumerate() {
+            let x = MARGIN_MM + col as f32 * column_width;
+            layer.use_text(clip(header.clone()), FONT_SIZE, Mm(x), Mm(y), &bold);
+        }
+    };
+    if include_headers {
+        write_headers(&layer, y);
+        y -= LINE_HEIGHT_MM;
+    }
+
+    for row in &table.rows {
+        if y < MARGIN_MM {
+            let (page, next) = document.add_page(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Layer 1");
+            layer = document.get_page(page).get_layer(next);
+            y = PAGE_HEIGHT_MM - MARGIN_MM;
+            if include_headers {
+                write_headers(&layer, y);
+                y -= LINE_HEIGHT_MM;
+            }
+        }
+        for (col, value) in row.iter().enumerate() {
+            let x = MARGIN_MM + col as f32 * column_width;
+            layer.use_text(clip(cell_text(value)), FONT_SIZE, Mm(x), Mm(y), &font);
+        }
+        y -= LINE_HEIGHT_MM;
+    }
+
+    document
+        .save_to_bytes()
+        .map_err(|e| ExportError::Pdf(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_writers_quote_and_type_cells() {
+        let table = ExportTable {
+            title: "users".to_string(),
+            headers: vec!["name".to_string(), "age".to_string(), "tags".to_string()],
+            rows: vec![vec![json!("Doe, \"Jo\""), json!(42), json!(["a", "b"])]],
+        };
+
+        let csv = String::from_utf8(render(&table, &ExportFormat::Csv, true).unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "name,age,tags\n\"Doe, \"\"Jo\"\"\",42,\"[\"\"a\"\",\"\"b\"\"]\"\n"
+        );
+        let headless = render(&table, &ExportFormat::Csv, false).unwrap();
+        assert!(!String::from_utf8(headless).unwrap().starts_with("name"));
+
+        let json: Value =
+            serde_json::from_slice(&render(&table, &ExportFormat::Json, true).unwrap()).unwrap();
+        assert_eq!(json[0]["age"], json!(42));
+
+        // An XLSX file is a zip archive
+        assert!(render(&table, &ExportFormat::Xlsx, true)
+            .unwrap()
+            .starts_with(b"PK"));
+        assert!(render(&table, &ExportFormat::Pdf, true)
+            .unwrap()
+            .starts_with(b"%PDF"));
+    }
+}
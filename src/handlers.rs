@@ -3,8 +3,14 @@ pub mod users;
 pub mod posts;
 pub mod products;
 pub mod orders;
+pub mod payments;
+pub mod webhooks;
+pub mod jobs;
+pub mod coupons;
+pub mod shipping;
 pub mod auth;
 pub mod analytics;
 pub mod search;
 pub mod upload;
 pub mod export;
+pub mod imports;
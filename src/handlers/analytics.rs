@@ -1,15 +1,21 @@
 use axum::{extract::State, Json};
 
 use crate::{
+    auth::AuthUser,
     error::Result,
     models::{
         AnalyticsOverview, AnalyticsResponse, ChartData, CustomerSegment, Dataset,
-        DeviceAnalytics, GeographicData, TopCategory, TopProduct, TrafficSource,
+        DeviceAnalytics, GeographicData, TopCategory, TopProduct, TrafficSource, UserRole,
     },
     AppState,
 };
 
-pub async fn get_analytics(State(_state): State<AppState>) -> Result<Json<AnalyticsResponse>> {
+pub async fn get_analytics(
+    State(_state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<AnalyticsResponse>> {
+    auth.require_role(UserRole::Admin)?;
+
     // Generate sample analytics data
     let overview = AnalyticsOverview::builder()
         .total_users(15420)
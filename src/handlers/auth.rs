@@ -2,11 +2,15 @@ use axum::{extract::State, Json};
 
 use crate::{
     auth::{
-        AuthResponse, AuthService, AuthUserInfo, LoginRequest, RefreshTokenRequest,
+        AuthResponse, AuthService, AuthUser, AuthUserInfo, LoginRequest, RefreshTokenRequest,
         RegisterRequest, TokenPair,
     },
     error::{AppError, Result},
-    services::user_service::UserService,
+    services::{
+        email_service::TemplatedEmail, job_service::JobService, session_service::SessionService,
+        user_service::UserService,
+    },
+    templates::WelcomeEmailData,
     AppState,
 };
 
@@ -22,7 +26,7 @@ pub async fn login(
     );
 
     let user = user_service
-        .get_user_by_email(&request.email)
+        .get_user_for_login(&request.email)
         .await?
         .ok_or_else(|| AppError::AuthenticationError("Invalid credentials".to_string()))?;
 
@@ -33,12 +37,11 @@ pub async fn login(
         ));
     }
 
-    // Generate tokens
-    let tokens = auth_service.generate_token_pair(
-        user.id,
-        &user.email,
-        &format!("{:?}", user.role).to_lowercase(),
-    )?;
+    user_service.record_login(user.id).await?;
+
+    let tokens = SessionService::new(state.db.clone(), state.cache.clone())
+        .start(&auth_service, &user)
+        .await?;
 
     Ok(Json(AuthResponse {
         user: AuthUserInfo {
@@ -90,12 +93,25 @@ pub async fn register(
         )
         .await?;
 
-    // Generate tokens
-    let tokens = auth_service.generate_token_pair(
-        user.id,
-        &user.email,
-        &format!("{:?}", user.role).to_lowercase(),
-    )?;
+    let tokens = SessionService::new(state.db.clone(), state.cache.clone())
+        .start(&auth_service, &user)
+        .await?;
+
+    // The account exists either way; a lost welcome email is not worth failing over
+    let welcome = WelcomeEmailData {
+        username: user.username.clone(),
+        verification_link: None,
+    };
+    let queued = match TemplatedEmail::new(&user.email, "welcome", None, &welcome) {
+        Ok(email) => JobService::new(state.db.clone(), state.cache.clone())
+            .enqueue(&email)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = queued {
+        tracing::warn!(user_id = %user.id, error = %e, "Failed to queue welcome email");
+    }
 
     Ok(Json(AuthResponse {
         user: AuthUserInfo {
@@ -108,32 +124,35 @@ pub async fn register(
     }))
 }
 
+/// Rotates the refresh token; the presented one is single-use.
 pub async fn refresh_token(
     State(state): State<AppState>,
     Json(request): Json<RefreshTokenRequest>,
 ) -> Result<Json<TokenPair>> {
-    let auth_service = AuthService::new(
-        state.config.auth.jwt_secret.clone(),
-        state.config.auth.token_expiry_seconds,
-        state.config.auth.refresh_token_expiry_seconds,
-    );
-    let user_service = UserService::new(state.db.clone(), state.cache.clone());
-
-    // Validate refresh token
-    let token_data = auth_service.validate_refresh_token(&request.refresh_token)?;
+    let auth_service = AuthService::from_config(&state.config.auth);
+    let tokens = SessionService::new(state.db.clone(), state.cache.clone())
+        .rotate(&auth_service, &request.refresh_token)
+        .await?;
 
-    // Get user
-    let user = user_service
-        .get_user_by_id(token_data.claims.user_id)
-        .await?
-        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    Ok(Json(tokens))
+}
 
-    // Generate new token pair
-    let tokens = auth_service.generate_token_pair(
-        user.id,
-        &user.email,
-        &format!("{:?}", user.role).to_lowercase(),
-    )?;
+pub async fn logout(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>> {
+    SessionService::new(state.db.clone(), state.cache.clone())
+        .logout(&auth.claims.jti)
+        .await?;
+    Ok(Json(serde_json::json!({ "logged_out": true })))
+}
 
-    Ok(Json(tokens))
+pub async fn logout_all(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<serde_json::Value>> {
+    SessionService::new(state.db.clone(), state.cache.clone())
+        .logout_all(auth.id())
+        .await?;
+    Ok(Json(serde_json::json!({ "logged_out": true })))
 }
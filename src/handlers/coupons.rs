@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    error::{AppError, Result},
+    models::{
+        Coupon, CouponListResponse, CreateCouponRequest, PaginationParams, UpdateCouponRequest,
+        UserRole,
+    },
+    services::coupon_service::CouponService,
+    AppState,
+};
+
+pub async fn list_coupons(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Query(pagination): Query<PaginationParams>,
+) -> Result<Json<CouponListResponse>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = CouponService::new(state.db.clone(), state.cache.clone());
+    let (coupons, total) = service.list_coupons(&pagination).await?;
+
+    Ok(Json(CouponListResponse {
+        coupons,
+        total,
+        page: pagination.page,
+        per_page: pagination.per_page,
+    }))
+}
+
+pub async fn get_coupon(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Coupon>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = CouponService::new(state.db.clone(), state.cache.clone());
+    match service.get_coupon_by_id(id).await? {
+        Some(c) => Ok(Json(c)),
+        None => Err(AppError::NotFound(format!("Coupon {} not found", id))),
+    }
+}
+
+pub async fn create_coupon(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(request): Json<CreateCouponRequest>,
+) -> Result<Json<Coupon>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = CouponService::new(state.db.clone(), state.cache.clone());
+    Ok(Json(service.create_coupon(request).await?))
+}
+
+pub async fn update_coupon(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateCouponRequest>,
+) -> Result<Json<Coupon>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = CouponService::new(state.db.clone(), state.cache.clone());
+    Ok(Json(service.update_coupon(id, request).await?))
+}
+
+pub async fn delete_coupon(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = CouponService::new(state.db.clone(), state.cache.clone());
+    service.delete_coupon(id).await?;
+    Ok(Json(serde_json::json!({ "deleted": true, "id": id })))
+}
@@ -1,54 +1,104 @@
-use axum::{extract::State, Json};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
 
 use crate::{
+    auth::AuthUser,
     error::Result,
-    models::{ExportFormat, ExportResponse, ExportStatus},
+    export::stream::{self, StreamFormat},
+    models::{ExportFormat, ExportRequest, ExportResource, ExportResponse, UserRole},
+    services::export_service::{DownloadParams, ExportService},
     AppState,
 };
 
-pub async fn export_data(State(_state): State<AppState>) -> Result<Json<ExportResponse>> {
-    let job_id = uuid::Uuid::new_v4().to_string();
+fn export_service(state: &AppState) -> ExportService {
+    ExportService::new(
+        state.db.clone(),
+        state.cache.clone(),
+        &state.config.exports,
+        &state.config.auth.jwt_secret,
+    )
+}
 
-    let response = ExportResponse {
-        job_id: job_id.clone(),
-        status: ExportStatus::Pending,
-        download_url: None,
-        expires_at: None,
-    };
+/// Queues the export; poll `GET /export/:id` for its status and link.
+pub async fn export_data(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(request): Json<ExportRequest>,
+) -> Result<Json<ExportResponse>> {
+    auth.require_role(UserRole::Admin)?;
+    Ok(Json(export_service(&state).start(request).await?))
+}
 
-    // In production, this would queue an async export job
-    tracing::info!(job_id = %job_id, "Export job created");
+pub async fn export_status(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ExportResponse>> {
+    auth.require_role(UserRole::Admin)?;
+    Ok(Json(export_service(&state).status(id).await?))
+}
 
-    Ok(Json(response))
+/// Unauthenticated: the signed, expiring link is the credential.
+pub async fn download_export(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DownloadParams>,
+) -> Result<Response> {
+    let file = export_service(&state).download(id, &params).await?;
+    let disposition = format!("attachment; filename=\"{}\"", file.filename);
+    Ok((
+        [
+            (header::CONTENT_TYPE, file.content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        file.bytes,
+    )
+        .into_response())
 }
 
-pub fn generate_csv_export<T: serde::Serialize>(
-    data: &[T],
-    fields: &[String],
-) -> Result<String> {
-    let mut csv = String::new();
-
-    // Header row
-    csv.push_str(&fields.join(","));
-    csv.push('\n');
-
-    // Data rows (simplified - would use proper CSV library)
-    for item in data {
-        if let Ok(json) = serde_json::to_value(item) {
-            let row: Vec<String> = fields
-                .iter()
-                .map(|f| {
-                    json.get(f)
-                        .map(|v| v.to_string().trim_matches('"').to_string())
-                        .unwrap_or_default()
-                })
-                .collect();
-            csv.push_str(&row.join(","));
-            csv.push('\n');
-        }
-    }
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    pub format: Option<StreamFormat>,
+    pub include_headers: Option<bool>,
+}
+
+/// Streams a whole table as CSV (default) or NDJSON while it is read.
+pub async fn stream_export(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(resource): Path<ExportResource>,
+    Query(params): Query<StreamParams>,
+) -> Result<Response> {
+    auth.require_role(UserRole::Admin)?;
 
-    Ok(csv)
+    let format = params.format.unwrap_or(StreamFormat::Csv);
+    let rows = stream::rows(
+        state.db.clone(),
+        resource,
+        format,
+        params.include_headers.unwrap_or(true),
+        state.config.exports.stream_batch_size,
+    );
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        serde_json::to_value(resource)?.as_str().unwrap_or("export"),
+        format.extension()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(rows),
+    )
+        .into_response())
 }
 
 pub fn determine_export_format(format_str: &str) -> ExportFormat {
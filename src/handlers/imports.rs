@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    error::{AppError, Result},
+    models::{ImportRequest, ImportResource, ImportResponse, UserRole},
+    services::{export_service::DownloadParams, import_service::ImportService},
+    AppState,
+};
+
+fn import_service(state: &AppState) -> ImportService {
+    ImportService::new(
+        state.db.clone(),
+        state.cache.clone(),
+        &state.config.imports,
+        &state.config.auth,
+    )
+}
+
+/// Takes a multipart form: `file` (.csv or .xlsx), `resource`, and optionally
+/// `mapping` (a JSON object of column header to field) and `dry_run`.
+/// Poll `GET /imports/:id` for the summary and report link.
+pub async fn start_import(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    mut multipart: Multipart,
+) -> Result<Json<ImportResponse>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let invalid = |e: axum::extract::multipart::MultipartError| AppError::BadRequest(e.to_string());
+    let mut resource: Option<ImportResource> = None;
+    let mut mapping = None;
+    let mut dry_run = None;
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+        match field.name().unwrap_or_default() {
+            "file" => {
+                let filename = field.file_name().unwrap_or_default().to_string();
+                file = Some((filename, field.bytes().await.map_err(invalid)?));
+            }
+            "resource" => {
+                let text = field.text().await.map_err(invalid)?;
+                resource = Some(
+                    serde_json::from_value(serde_json::Value::String(text.trim().to_string()))
+                        .map_err(|_| {
+                            AppError::BadRequest(
+                                "resource must be 'products' or 'users'".to_string(),
+                            )
+                        })?,
+                );
+            }
+            "mapping" => {
+                let text = field.text().await.map_err(invalid)?;
+                mapping = Some(serde_json::from_str(&text).map_err(|e| {
+                    AppError::BadRequest(format!("mapping must be a JSON object: {}", e))
+                })?);
+            }
+            "dry_run" => {
+                let text = field.text().await.map_err(invalid)?;
+                dry_run = Some(text.trim().parse().map_err(|_| {
+                    AppError::BadRequest("dry_run must be true or false".to_string())
+                })?);
+            }
+            _ => {}
+        }
+    }
+
+    let resource =
+        resource.ok_or_else(|| AppError::BadRequest("resource is required".to_string()))?;
+    let (filename, bytes) =
+        file.ok_or_else(|| AppError::BadRequest("file is required".to_string()))?;
+    let request = ImportRequest {
+        resource,
+        mapping,
+        dry_run,
+    };
+    Ok(Json(
+        import_service(&state)
+            .start(request, &filename, &bytes)
+            .await?,
+    ))
+}
+
+pub async fn import_status(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ImportResponse>> {
+    auth.require_role(UserRole::Admin)?;
+    Ok(Json(import_service(&state).status(id).await?))
+}
+
+/// Unauthenticated: the signed, expiring link is the credential.
+pub async fn download_report(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DownloadParams>,
+) -> Result<Response> {
+    let file = import_service(&state).report(id, &params).await?;
+    let disposition = format!("attachment; filename=\"{}\"", file.filename);
+    Ok((
+        [
+            (header::CONTENT_TYPE, file.content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        file.bytes,
+    )
+        .into_response())
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthUser,
+    error::{AppError, Result},
+    models::{PaginationParams, UserRole},
+    services::job_service::{Job, JobFilter, JobListResponse, JobService},
+    AppState,
+};
+
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Query(pagination): Query<PaginationParams>,
+    Query(filter): Query<JobFilter>,
+) -> Result<Json<JobListResponse>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = JobService::new(state.db.clone(), state.cache.clone());
+    let (jobs, total) = service.list_jobs(&filter, &pagination).await?;
+
+    Ok(Json(JobListResponse {
+        jobs,
+        total,
+        page: pagination.page,
+        per_page: pagination.per_page,
+    }))
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = JobService::new(state.db.clone(), state.cache.clone());
+    match service.get_job(id).await? {
+        Some(job) => Ok(Json(job)),
+        None => Err(AppError::NotFound(format!("Job {} not found", id))),
+    }
+}
+
+/// Re-queues a dead or canceled job with its attempts reset.
+pub async fn retry_job(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = JobService::new(state.db.clone(), state.cache.clone());
+    Ok(Json(service.retry_job(id).await?))
+}
+
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = JobService::new(state.db.clone(), state.cache.clone());
+    Ok(Json(service.cancel_job(id).await?))
+}
@@ -5,18 +5,30 @@ use axum::{
 use uuid::Uuid;
 
 use crate::{
+    auth::AuthUser,
     error::{AppError, Result},
-    models::{CreateOrderRequest, OrderListResponse, OrderResponse, PaginationParams},
+    models::{
+        CreateOrderRequest, OrderEvent, OrderListResponse, OrderResponse, PaginationParams,
+        UpdateOrderStatusRequest, UserRole,
+    },
     services::order_service::OrderService,
     AppState,
 };
 
+/// Admins see every order; everyone else sees only their own.
 pub async fn list_orders(
     State(state): State<AppState>,
+    auth: AuthUser,
     Query(pagination): Query<PaginationParams>,
 ) -> Result<Json<OrderListResponse>> {
     let service = OrderService::new(state.db.clone(), state.cache.clone());
-    let (orders, total) = service.list_orders(&pagination).await?;
+    let (orders, total) = if auth.has_role(UserRole::Admin) {
+        service.list_orders(&pagination).await?
+    } else {
+        service
+            .list_orders_by_customer(auth.id(), &pagination)
+            .await?
+    };
 
     let response = OrderListResponse {
         orders,
@@ -30,22 +42,75 @@ pub async fn list_orders(
 
 pub async fn get_order(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<OrderResponse>> {
     let service = OrderService::new(state.db.clone(), state.cache.clone());
-    let order = service.get_order_by_id(id).await?;
+    let order = service
+        .get_order_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", id)))?;
 
-    match order {
-        Some(o) => Ok(Json(o)),
-        None => Err(AppError::NotFound(format!("Order {} not found", id))),
-    }
+    auth.require_owner_or(order.customer_id, UserRole::Admin)?;
+    Ok(Json(order))
 }
 
 pub async fn create_order(
     State(state): State<AppState>,
+    auth: AuthUser,
     Json(request): Json<CreateOrderRequest>,
 ) -> Result<Json<OrderResponse>> {
-    let service = OrderService::new(state.db.clone(), state.cache.clone());
+    auth.require_role(UserRole::User)?;
+    auth.require_owner_or(request.customer_id, UserRole::Admin)?;
+    let service = OrderService::new(state.db.clone(), state.cache.clone())
+        .with_tax_calculator(state.tax.clone())
+        .with_shipping_rates(state.shipping.clone())
+        .with_exchange_rates(state.exchange.clone());
     let order = service.create_order(request).await?;
     Ok(Json(order))
 }
+
+/// Drives the order, payment and fulfillment state machines; illegal moves are 409s.
+pub async fn update_order_status(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateOrderStatusRequest>,
+) -> Result<Json<OrderResponse>> {
+    auth.require_role(UserRole::Admin)?;
+
+    let service = OrderService::new(state.db.clone(), state.cache.clone());
+    let order = service.transition(id, request, Some(auth.id())).await?;
+    Ok(Json(order))
+}
+
+pub async fn cancel_order(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<OrderResponse>> {
+    let service = OrderService::new(state.db.clone(), state.cache.clone());
+    let order = service
+        .get_order_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", id)))?;
+    auth.require_owner_or(order.customer_id, UserRole::Admin)?;
+
+    let order = service.cancel_order(id, Some(auth.id())).await?;
+    Ok(Json(order))
+}
+
+pub async fn list_order_events(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<OrderEvent>>> {
+    let service = OrderService::new(state.db.clone(), state.cache.clone());
+    let order = service
+        .get_order_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", id)))?;
+    auth.require_owner_or(order.customer_id, UserRole::Admin)?;
+
+    Ok(Json(service.order_events(id).await?))
+}
@@ -0,0 +1,134 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    auth::AuthUser,
+    error::{AppError, Result},
+    models::UserRole,
+    services::{
+        order_service::OrderService,
+        payment_service::{
+            CreateRefundRequest, PaymentIntent, PaymentMethodRequest, PaymentService, Refund,
+        },
+    },
+    AppState,
+};
+
+fn payment_service(state: &AppState) -> PaymentService {
+    PaymentService::new(state.db.clone(), state.cache.clone()).with_gateway(state.payments.clone())
+}
+
+/// Only the order's customer, or an admin, may see or act on its payments.
+async fn require_order_access(state: &AppState, auth: &AuthUser, order_id: Uuid) -> Result<()> {
+    let order = OrderService::new(state.db.clone(), state.cache.clone())
+        .get_order_by_id(order_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", order_id)))?;
+    auth.require_owner_or(order.customer_id, UserRole::Admin)
+}
+
+async fn accessible_intent(
+    state: &AppState,
+    auth: &AuthUser,
+    service: &PaymentService,
+    id: Uuid,
+) -> Result<PaymentIntent> {
+    let intent = service
+        .get_payment_intent(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Payment intent {} not found", id)))?;
+    require_order_access(state, auth, intent.order_id).await?;
+    Ok(intent)
+}
+
+pub async fn create_payment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<PaymentIntent>> {
+    require_order_access(&state, &auth, order_id).await?;
+    let intent = payment_service(&state).create_payment_intent(order_id).await?;
+    Ok(Json(intent))
+}
+
+pub async fn list_order_payments(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<PaymentIntent>>> {
+    require_order_access(&state, &auth, order_id).await?;
+    let intents = payment_service(&state)
+        .payment_intents_for_order(order_id)
+        .await?;
+    Ok(Json(intents))
+}
+
+pub async fn get_payment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentIntent>> {
+    let service = payment_service(&state);
+    Ok(Json(accessible_intent(&state, &auth, &service, id).await?))
+}
+
+pub async fn confirm_payment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<PaymentMethodRequest>,
+) -> Result<Json<PaymentIntent>> {
+    let service = payment_service(&state);
+    accessible_intent(&state, &auth, &service, id).await?;
+    Ok(Json(service.confirm_payment(id, request).await?))
+}
+
+/// Called once the customer finished the `next_action_url` step.
+pub async fn authenticate_payment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentIntent>> {
+    let service = payment_service(&state);
+    accessible_intent(&state, &auth, &service, id).await?;
+    Ok(Json(service.complete_action(id).await?))
+}
+
+pub async fn cancel_payment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentIntent>> {
+    let service = payment_service(&state);
+    accessible_intent(&state, &auth, &service, id).await?;
+    Ok(Json(service.cancel_payment_intent(id).await?))
+}
+
+pub async fn list_refunds(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Refund>>> {
+    let service = payment_service(&state);
+    accessible_intent(&state, &auth, &service, id).await?;
+    Ok(Json(service.refunds(id).await?))
+}
+
+pub async fn create_refund(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateRefundRequest>,
+) -> Result<Json<Refund>> {
+    auth.require_role(UserRole::Admin)?;
+    request
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let refund = payment_service(&state).create_refund(id, request).await?;
+    Ok(Json(refund))
+}
@@ -6,10 +6,11 @@ use chrono::Utc;
 use uuid::Uuid;
 
 use crate::{
+    auth::AuthUser,
     error::{AppError, Result},
     models::{
         CreatePostRequest, PaginationParams, Post, PostListResponse, PostResponse, PostStatus,
-        PostVisibility, UpdatePostRequest,
+        PostVisibility, UpdatePostRequest, UserRole,
     },
     services::post_service::PostService,
     AppState,
@@ -47,8 +48,10 @@ pub async fn get_post(
 
 pub async fn create_post(
     State(state): State<AppState>,
+    auth: AuthUser,
     Json(request): Json<CreatePostRequest>,
 ) -> Result<Json<PostResponse>> {
+    auth.require_role(UserRole::User)?;
     let service = PostService::new(state.db.clone(), state.cache.clone());
 
     let now = Utc::now();
@@ -56,7 +59,7 @@ pub async fn create_post(
     
     let post = Post::builder()
         .id(Uuid::new_v4())
-        .author_id(Uuid::new_v4()) // Would get from auth context
+        .author_id(auth.id())
         .title(request.title)
         .slug(slug)
         .content(request.content)
@@ -80,15 +83,17 @@ pub async fn create_post(
 
 pub async fn update_post(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
     Json(request): Json<UpdatePostRequest>,
 ) -> Result<Json<PostResponse>> {
     let service = PostService::new(state.db.clone(), state.cache.clone());
 
-    let existing = service.get_post_by_id(id).await?;
-    if existing.is_none() {
-        return Err(AppError::NotFound(format!("Post {} not found", id)));
-    }
+    let existing = service
+        .get_post_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Post {} not found", id)))?;
+    auth.require_owner_or(existing.author_id, UserRole::Moderator)?;
 
     let updated = service.update_post(id, request).await?;
     Ok(Json(PostResponse::from(updated)))
@@ -96,14 +101,16 @@ pub async fn update_post(
 
 pub async fn delete_post(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>> {
     let service = PostService::new(state.db.clone(), state.cache.clone());
 
-    let existing = service.get_post_by_id(id).await?;
-    if existing.is_none() {
-        return Err(AppError::NotFound(format!("Post {} not found", id)));
-    }
+    let existing = service
+        .get_post_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Post {} not found", id)))?;
+    auth.require_owner_or(existing.author_id, UserRole::Moderator)?;
 
     service.delete_post(id).await?;
     Ok(Json(serde_json::json!({ "deleted": true, "id": id })))
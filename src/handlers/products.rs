@@ -6,7 +6,8 @@ use uuid::Uuid;
 
 use crate::{
     error::{AppError, Result},
-    models::{PaginationParams, ProductListResponse, ProductResponse},
+    exchange::RateProvider,
+    models::{CurrencyParams, PaginationParams, ProductListResponse, ProductResponse},
     services::product_service::ProductService,
     AppState,
 };
@@ -14,12 +15,25 @@ use crate::{
 pub async fn list_products(
     State(state): State<AppState>,
     Query(pagination): Query<PaginationParams>,
+    Query(display): Query<CurrencyParams>,
 ) -> Result<Json<ProductListResponse>> {
     let service = ProductService::new(state.db.clone(), state.cache.clone());
     let (products, total) = service.list_products(&pagination).await?;
 
+    let mut responses = Vec::with_capacity(products.len());
+    for product in products {
+        responses.push(
+            in_currency(
+                state.exchange.as_ref(),
+                ProductResponse::from(product),
+                display.currency.as_deref(),
+            )
+            .await?,
+        );
+    }
+
     let response = ProductListResponse {
-        products: products.into_iter().map(ProductResponse::from).collect(),
+        products: responses,
         total,
         page: pagination.page,
         per_page: pagination.per_page,
@@ -31,12 +45,41 @@ pub async fn list_products(
 pub async fn get_product(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
+    Query(display): Query<CurrencyParams>,
 ) -> Result<Json<ProductResponse>> {
     let service = ProductService::new(state.db.clone(), state.cache.clone());
     let product = service.get_product_by_id(id).await?;
 
     match product {
-        Some(p) => Ok(Json(ProductResponse::from(p))),
+        Some(p) => Ok(Json(
+            in_currency(
+                state.exchange.as_ref(),
+                ProductResponse::from(p),
+                display.currency.as_deref(),
+            )
+            .await?,
+        )),
         None => Err(AppError::NotFound(format!("Product {} not found", id))),
     }
 }
+
+/// Re-prices a product for display; catalog prices are left untouched.
+async fn in_currency(
+    rates: &dyn RateProvider,
+    mut product: ProductResponse,
+    currency: Option<&str>,
+) -> Result<ProductResponse> {
+    let Some(currency) = currency.map(str::to_ascii_uppercase) else {
+        return Ok(product);
+    };
+    if currency.len() != 3 {
+        return Err(AppError::BadRequest(format!("Unknown currency '{}'", currency)));
+    }
+
+    product.price = rates.convert(&product.price, &currency).await?;
+    if let Some(sale_price) = &product.sale_price {
+        product.sale_price = Some(rates.convert(sale_price, &currency).await?);
+    }
+    product.currency = currency;
+    Ok(product)
+}
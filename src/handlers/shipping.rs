@@ -0,0 +1,19 @@
+use axum::{extract::State, Json};
+
+use crate::{
+    error::Result,
+    models::{ShippingQuoteRequest, ShippingQuoteResponse},
+    services::shipping_service::ShippingService,
+    AppState,
+};
+
+/// Shipping options for a cart; open to anonymous shoppers.
+pub async fn quote(
+    State(state): State<AppState>,
+    Json(request): Json<ShippingQuoteRequest>,
+) -> Result<Json<ShippingQuoteResponse>> {
+    let service = ShippingService::new(state.db.clone(), state.cache.clone())
+        .with_rates(state.shipping.clone())
+        .with_exchange_rates(state.exchange.clone());
+    Ok(Json(service.quote(request).await?))
+}
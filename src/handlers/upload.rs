@@ -1,14 +1,23 @@
-use axum::{extract::State, Json};
-use serde::{Deserialize, Serialize};
+use axum::{
+    body::Body,
+    extract::{multipart::MultipartError, Multipart, Path, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::TryStreamExt;
+use serde::Serialize;
+use uuid::Uuid;
 
-use crate::{error::Result, models::FileUploadResponse, AppState};
-
-#[derive(Debug, Deserialize)]
-pub struct UploadRequest {
-    pub filename: String,
-    pub content_type: String,
-    pub size: u64,
-}
+use crate::{
+    auth::AuthUser,
+    error::{AppError, Result},
+    models::{FileUploadResponse, UserRole},
+    services::upload_service::UploadService,
+    storage::{parse_range, RangeRequest},
+    utils::sanitize_filename,
+    AppState,
+};
 
 #[derive(Debug, Serialize)]
 pub struct PresignedUploadResponse {
@@ -17,35 +26,141 @@ pub struct PresignedUploadResponse {
     pub expires_in: i64,
 }
 
+fn upload_service(state: &AppState) -> UploadService {
+    UploadService::new(
+        state.db.clone(),
+        state.blobs.clone(),
+        &state.config.uploads,
+        state.config.server.max_body_size_mb as u64 * 1024 * 1024,
+    )
+}
+
+fn multipart_error(error: MultipartError) -> AppError {
+    if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge(error.body_text())
+    } else {
+        AppError::BadRequest(error.body_text())
+    }
+}
+
+/// Takes a multipart form with the file in its `file` field, which is
+/// streamed to storage rather than buffered.
 pub async fn upload_file(
-    State(_state): State<AppState>,
-    Json(request): Json<UploadRequest>,
+    State(state): State<AppState>,
+    auth: AuthUser,
+    mut multipart: Multipart,
 ) -> Result<Json<FileUploadResponse>> {
-    let file_id = uuid::Uuid::new_v4().to_string();
-    let filename = format!("{}_{}", file_id, sanitize_filename(&request.filename));
-
-    let response = FileUploadResponse {
-        id: file_id.clone(),
-        filename: filename.clone(),
-        original_filename: request.filename,
-        content_type: request.content_type,
-        size: request.size,
-        url: format!("/uploads/{}", filename),
-        thumbnail_url: None,
+    auth.require_role(UserRole::User)?;
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = field.file_name().unwrap_or("upload").to_string();
+        let upload = upload_service(&state)
+            .store(auth.id(), &filename, field.map_err(multipart_error))
+            .await?;
+
+        return Ok(Json(FileUploadResponse {
+            id: upload.id.to_string(),
+            filename: upload.storage_key,
+            original_filename: upload.original_filename,
+            content_type: upload.content_type,
+            size: upload.size,
+            sha256: upload.sha256,
+            url: format!("/api/v1/uploads/{}", upload.id),
+            thumbnail_url: None,
+        }));
+    }
+
+    Err(AppError::BadRequest("file is required".to_string()))
+}
+
+/// Serves an uploaded file to its owner or an admin, honouring a single
+/// `Range` (and `If-Range` against the checksum ETag) so media can be seeked
+/// and downloads resumed.
+pub async fn download_file(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let uploads = upload_service(&state);
+    let upload = uploads.get(id).await?;
+    auth.require_owner_or(upload.owner_id, UserRole::Admin)?;
+    let etag = format!("\"{}\"", upload.sha256);
+
+    // A stale validator means the client's partial copy is of another
+    // version, so it gets the whole file
+    let if_range_matches = headers
+        .get(header::IF_RANGE)
+        .is_none_or(|v| v.to_str().is_ok_and(|v| v == etag));
+    let range = headers
+        .get(header::RANGE)
+        .filter(|_| if_range_matches)
+        .and_then(|v| v.to_str().ok());
+
+    let mut response_headers = HeaderMap::new();
+    set(
+        &mut response_headers,
+        header::ACCEPT_RANGES,
+        "bytes".to_string(),
+    );
+    set(&mut response_headers, header::ETAG, etag);
+
+    let (status, range) = match parse_range(range, upload.size) {
+        RangeRequest::Full => (StatusCode::OK, None),
+        RangeRequest::Partial(range) => {
+            set(
+                &mut response_headers,
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end, upload.size),
+            );
+            (StatusCode::PARTIAL_CONTENT, Some(range))
+        }
+        RangeRequest::Unsatisfiable => {
+            set(
+                &mut response_headers,
+                header::CONTENT_RANGE,
+                format!("bytes */{}", upload.size),
+            );
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
     };
+    let length = range.map_or(upload.size, |r| r.len());
+
+    set(
+        &mut response_headers,
+        header::CONTENT_TYPE,
+        upload.content_type.clone(),
+    );
+    set(
+        &mut response_headers,
+        header::CONTENT_LENGTH,
+        length.to_string(),
+    );
+    set(
+        &mut response_headers,
+        header::CONTENT_DISPOSITION,
+        format!(
+            "inline; filename=\"{}\"",
+            sanitize_filename(&upload.original_filename)
+        ),
+    );
+    set(
+        &mut response_headers,
+        header::X_CONTENT_TYPE_OPTIONS,
+        "nosniff".to_string(),
+    );
 
-    Ok(Json(response))
+    let body = Body::from_stream(uploads.open(&upload, range).await?);
+    Ok((status, response_headers, body).into_response())
 }
 
-fn sanitize_filename(filename: &str) -> String {
-    filename
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect()
+/// Every value set here is built from checked or sanitized parts, so one
+/// that still fails to parse is left out rather than failing the request.
+fn set(headers: &mut HeaderMap, name: HeaderName, value: String) {
+    if let Ok(value) = value.parse() {
+        headers.insert(name, value);
+    }
 }
@@ -6,6 +6,7 @@ use chrono::Utc;
 use uuid::Uuid;
 
 use crate::{
+    auth::{AuthService, AuthUser},
     error::{AppError, Result},
     models::{
         CreateUserRequest, PaginationParams, UpdateUserRequest, User, UserListResponse,
@@ -17,8 +18,10 @@ use crate::{
 
 pub async fn list_users(
     State(state): State<AppState>,
+    auth: AuthUser,
     Query(pagination): Query<PaginationParams>,
 ) -> Result<Json<UserListResponse>> {
+    auth.require_role(UserRole::Moderator)?;
     let service = UserService::new(state.db.clone(), state.cache.clone());
     let (users, total) = service.list_users(&pagination).await?;
 
@@ -34,8 +37,10 @@ pub async fn list_users(
 
 pub async fn get_user(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<UserResponse>> {
+    auth.require_owner_or(id, UserRole::Moderator)?;
     let service = UserService::new(state.db.clone(), state.cache.clone());
     let user = service.get_user_by_id(id).await?;
 
@@ -47,8 +52,10 @@ pub async fn get_user(
 
 pub async fn create_user(
     State(state): State<AppState>,
+    auth: AuthUser,
     Json(request): Json<CreateUserRequest>,
 ) -> Result<Json<UserResponse>> {
+    auth.require_role(UserRole::Admin)?;
     let service = UserService::new(state.db.clone(), state.cache.clone());
     
     // Check if email already exists
@@ -61,12 +68,19 @@ pub async fn create_user(
         return Err(AppError::Conflict("Username already taken".to_string()));
     }
 
+    let auth_service = AuthService::new(
+        state.config.auth.jwt_secret.clone(),
+        state.config.auth.token_expiry_seconds,
+        state.config.auth.refresh_token_expiry_seconds,
+    );
+    let password_hash = auth_service.hash_password(&request.password)?;
+
     let now = Utc::now();
     let user = User::builder()
         .id(Uuid::new_v4())
         .email(request.email)
         .username(request.username)
-        .password_hash("hashed".to_string()) // Would properly hash in production
+        .password_hash(password_hash)
         .first_name(request.first_name)
         .last_name(request.last_name)
         .avatar_url(None)
@@ -85,9 +99,11 @@ pub async fn create_user(
 
 pub async fn update_user(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
     Json(request): Json<UpdateUserRequest>,
 ) -> Result<Json<UserResponse>> {
+    auth.require_owner_or(id, UserRole::Admin)?;
     let service = UserService::new(state.db.clone(), state.cache.clone());
     
     let existing = service.get_user_by_id(id).await?;
@@ -101,8 +117,10 @@ pub async fn update_user(
 
 pub async fn delete_user(
     State(state): State<AppState>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>> {
+    auth.require_role(UserRole::Admin)?;
     let service = UserService::new(state.db.clone(), state.cache.clone());
     
     let existing = service.get_user_by_id(id).await?;
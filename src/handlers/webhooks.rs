@@ -0,0 +1,35 @@
+use axum::{body::Bytes, extract::State, http::HeaderMap, Json};
+
+use crate::{
+    auth::AuthUser,
+    error::Result,
+    models::UserRole,
+    services::webhook_service::{ReplaySummary, WebhookReceipt, WebhookService, SIGNATURE_HEADER},
+    AppState,
+};
+
+fn webhook_service(state: &AppState) -> WebhookService {
+    WebhookService::new(state.db.clone(), state.cache.clone(), &state.config.payments)
+        .with_gateway(state.payments.clone())
+}
+
+/// Takes the raw body, since the signature covers the exact bytes sent.
+pub async fn payments(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<WebhookReceipt>> {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let receipt = webhook_service(&state).receive(signature, &body).await?;
+    Ok(Json(receipt))
+}
+
+pub async fn replay_payments(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<ReplaySummary>> {
+    auth.require_role(UserRole::Admin)?;
+    Ok(Json(webhook_service(&state).replay_unhandled().await?))
+}
@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use axum::http::HeaderName;
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    cache::CacheManager,
+    config::{IdempotencyBackend, IdempotencyConfig},
+    database::{Database, Nullable},
+    error::Result,
+};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses served from a stored result
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// A response kept for replay; `body` is base64.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// What a key holds: the request it was first used for and, once that
+/// request finished, its response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub fingerprint: String,
+    pub response: Option<StoredResponse>,
+}
+
+/// Where keys and their responses live for the configured window.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims `key` for the request with `fingerprint`. Returns `None` when
+    /// the caller now owns the key, else the record already holding it.
+    async fn claim(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>>;
+
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<()>;
+
+    /// Frees a claim whose request failed, so a retry runs again.
+    async fn release(&self, key: &str) -> Result<()>;
+
+    /// Drops expired keys; stores that expire on their own have nothing to do.
+    async fn purge_expired(&self) -> Result<u64> {
+        Ok(0)
+    }
+}
+
+pub fn from_config(
+    config: &IdempotencyConfig,
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+) -> Arc<dyn IdempotencyStore> {
+    let ttl = Duration::from_secs(config.ttl_seconds.max(1));
+    match config.backend {
+        IdempotencyBackend::Cache => Arc::new(CacheStore::new(cache, ttl)),
+        IdempotencyBackend::Database => Arc::new(DatabaseStore::new(db, ttl)),
+    }
+}
+
+/// Keeps keys in the shared `CacheManager`.
+pub struct CacheStore {
+    cache: Arc<CacheManager>,
+    ttl: Duration,
+}
+
+impl CacheStore {
+    pub fn new(cache: Arc<CacheManager>, ttl: Duration) -> Self {
+        Self { cache, ttl }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for CacheStore {
+    async fn claim(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>> {
+        let pending = IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        };
+        if self
+            .cache
+            .insert_json_for(key.to_string(), &pending, self.ttl)
+            .await?
+        {
+            return Ok(None);
+        }
+        // Gone again only if it expired in between, which frees the key
+        Ok(self.cache.get_json(key).await)
+    }
+
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<()> {
+        let Some(mut record) = self.cache.get_json::<IdempotencyRecord>(key).await else {
+            return Ok(());
+        };
+        record.response = Some(response.clone());
+        self.cache
+            .set_json_for(key.to_string(), &record, self.ttl)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<()> {
+        self.cache.delete(key).await;
+        Ok(())
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct KeyRow {
+    fingerprint: String,
+    #[sqlx(try_from = "Nullable<i64>")]
+    status: Option<i64>,
+    #[sqlx(try_from = "Nullable<String>")]
+    content_type: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    body: Option<String>,
+}
+
+/// Keeps keys in the `idempotency_keys` table, surviving restarts and
+/// shared by every instance.
+pub struct DatabaseStore {
+    db: Arc<Database>,
+    ttl: Duration,
+}
+
+impl DatabaseStore {
+    pub fn new(db: Arc<Database>, ttl: Duration) -> Self {
+        Self { db, ttl }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for DatabaseStore {
+    async fn claim(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>> {
+        let now = Utc::now();
+        let expires_at = now + ChronoDuration::from_std(self.ttl).unwrap_or(ChronoDuration::days(1));
+
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND expires_at <= $2",
+        )
+        .bind(key)
+        .bind(now.to_rfc3339())
+        .execute(&self.db.pool)
+        .await?;
+        // Not a unique-violation catch: sqlx's SQLite worker steps a failed
+        // statement again, which can insert once the key is freed
+        let inserted = sqlx::query(
+            "INSERT INTO idempotency_keys (idempotency_key, fingerprint, created_at, expires_at) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT (idempotency_key) DO NOTHING",
+        )
+        .bind(key)
+        .bind(fingerprint)
+        .bind(now.to_rfc3339())
+        .bind(expires_at.to_rfc3339())
+        .execute(&self.db.pool)
+        .await?;
+        if inserted.rows_affected() == 1 {
+            return Ok(None);
+        }
+
+        let row: Option<KeyRow> = sqlx::query_as(
+            "SELECT fingerprint, status, content_type, body FROM idempotency_keys \
+             WHERE idempotency_key = $1",
+        )
+        .bind(key)
+        .fetch_optional(&self.db.pool)
+        .await?;
+        Ok(row.map(|row| IdempotencyRecord {
+            fingerprint: row.fingerprint,
+            response: row.status.map(|status| StoredResponse {
+                status: status as u16,
+                content_type: row.content_type,
+                body: row.body.unwrap_or_default(),
+            }),
+        }))
+    }
+
+    async fn complete(&self, key: &str, response: &StoredResponse) -> Result<()> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status = $1, content_type = $2, body = $3 \
+             WHERE idempotency_key = $4",
+        )
+        .bind(i64::from(response.status))
+        .bind(&response.content_type)
+        .bind(&response.body)
+        .bind(key)
+        .execute(&self.db.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE idempotency_key = $1")
+            .bind(key)
+            .execute(&self.db.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= $1")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.db.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn claim_flow(store: &dyn IdempotencyStore) {
+        assert_eq!(store.claim("k1", "abc").await.unwrap(), None);
+
+        let in_flight = store.claim("k1", "abc").await.unwrap().unwrap();
+        assert_eq!(in_flight.response, None);
+
+        let response = StoredResponse {
+            status: 201,
+            content_type: Some("application/json".to_string()),
+            body: "e30=".to_string(),
+        };
+        store.complete("k1", &response).await.unwrap();
+        let done = store.claim("k1", "other").await.unwrap().unwrap();
+        assert_eq!(done.fingerprint, "abc");
+        assert_eq!(done.response, Some(response));
+
+        store.release("k1").await.unwrap();
+        assert_eq!(store.claim("k1", "other").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stores_claim_complete_and_release() {
+        let ttl = Duration::from_secs(60);
+        claim_flow(&CacheStore::new(Arc::new(CacheManager::new()), ttl)).await;
+
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        claim_flow(&DatabaseStore::new(db, ttl)).await;
+    }
+}
@@ -0,0 +1,227 @@
+use calamine::{open_workbook_from_rs, Data, Reader, Xlsx};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ImportFormat {
+    /// The format of an uploaded file, from its extension.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let (_, extension) = filename.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Some(ImportFormat::Csv),
+            "xlsx" => Some(ImportFormat::Xlsx),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// An uploaded sheet: the first row as headers and every later non-blank
+/// row as trimmed text, one cell per header.
+#[derive(Debug, Clone, Default)]
+pub struct ImportTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<SheetRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetRow {
+    /// Where the row starts in the file, counting the header as line 1, so
+    /// reports still point at the right line once blank rows are dropped
+    pub line: usize,
+    pub cells: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("XLSX error: {0}")]
+    Xlsx(#[from] calamine::XlsxError),
+    #[error("{0}")]
+    Empty(String),
+}
+
+/// What happened to one row; in a dry run, what would have happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowAction {
+    Created,
+    Updated,
+    Unchanged,
+    Failed,
+}
+
+impl RowAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowAction::Created => "created",
+            RowAction::Updated => "updated",
+            RowAction::Unchanged => "unchanged",
+            RowAction::Failed => "failed",
+        }
+    }
+}
+
+/// One line of the validation report. `row` is the line in the uploaded
+/// sheet, counting the header as line 1.
+#[derive(Debug, Clone)]
+pub struct ReportRow {
+    pub row: usize,
+    pub key: String,
+    pub action: RowAction,
+    pub changes: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+pub fn parse(bytes: &[u8], format: ImportFormat) -> Result<ImportTable, ImportError> {
+    let mut table = match format {
+        ImportFormat::Csv => read_csv(bytes)?,
+        ImportFormat::Xlsx => read_xlsx(bytes)?,
+    };
+    if table.headers.iter().all(|h| h.is_empty()) {
+        return Err(ImportError::Empty("The file has no header row".to_string()));
+    }
+
+    let width = table.headers.len();
+    for row in &mut table.rows {
+        row.cells.resize(width, String::new());
+    }
+    table
+        .rows
+        .retain(|row| row.cells.iter().any(|cell| !cell.is_empty()));
+    Ok(table)
+}
+
+pub fn write_report(rows: &[ReportRow]) -> Result<Vec<u8>, ImportError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["row", "key", "action", "changes", "errors"])?;
+    for row in rows {
+        writer.write_record([
+            row.row.to_string(),
+            row.key.clone(),
+            row.action.as_str().to_string(),
+            row.changes.join(", "),
+            row.errors.join("; "),
+        ])?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| ImportError::Csv(e.into_error().into()))
+}
+
+fn read_csv(bytes: &[u8]) -> Result<ImportTable, ImportError> {
+    // Spreadsheet programs often save CSV with a byte order mark
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(bytes);
+
+    let headers = reader.headers()?.iter().map(str::to_string).collect();
+    let rows = reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            Ok(SheetRow {
+                line: record.position().map_or(0, |p| p.line() as usize),
+                cells: record.iter().map(str::to_string).collect(),
+            })
+        })
+        .collect::<Result<_, csv::Error>>()?;
+    Ok(ImportTable { headers, rows })
+}
+
+/// Reads the first worksheet. Whole numbers come through without a decimal
+/// point, so numeric SKUs and quantities read as typed.
+fn read_xlsx(bytes: &[u8]) -> Result<ImportTable, ImportError> {
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(bytes))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| ImportError::Empty("The workbook has no worksheets".to_string()))??;
+
+    // The range starts at the first used row, which need not be the first
+    let first_line = range.start().map_or(1, |(row, _)| row as usize + 1);
+    let mut rows = range.rows().enumerate().map(|(index, row)| SheetRow {
+        line: first_line + index,
+        cells: row
+            .iter()
+            .map(|cell| cell_text(cell).trim().to_string())
+            .collect(),
+    });
+    let headers = rows.next().map(|row| row.cells).unwrap_or_default();
+    Ok(ImportTable {
+        headers,
+        rows: rows.collect(),
+    })
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_xlsxwriter::Workbook;
+
+    #[test]
+    fn test_reads_csv_and_xlsx_alike() {
+        let csv = "\u{feff}sku, name ,quantity\nA-1,\"Mug, blue\",5\n,,\nB-2,Plate\n";
+        let table = parse(csv.as_bytes(), ImportFormat::Csv).unwrap();
+        assert_eq!(table.headers, ["sku", "name", "quantity"]);
+        let cells: Vec<_> = table.rows.iter().map(|row| row.cells.clone()).collect();
+        assert_eq!(
+            cells,
+            [vec!["A-1", "Mug, blue", "5"], vec!["B-2", "Plate", ""]]
+        );
+        // The blank line 3 is dropped without renumbering what follows
+        let lines: Vec<_> = table.rows.iter().map(|row| row.line).collect();
+        assert_eq!(lines, [2, 4]);
+
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.write_string(0, 0, "sku").unwrap();
+        sheet.write_string(0, 1, "quantity").unwrap();
+        sheet.write_number(1, 0, 1001.0).unwrap();
+        sheet.write_number(1, 1, 2.5).unwrap();
+        sheet.write_string(3, 0, "1002").unwrap();
+        let bytes = workbook.save_to_buffer().unwrap();
+        let table = parse(&bytes, ImportFormat::Xlsx).unwrap();
+        assert_eq!(table.headers, ["sku", "quantity"]);
+        assert_eq!(
+            table.rows,
+            [
+                SheetRow {
+                    line: 2,
+                    cells: vec!["1001".to_string(), "2.5".to_string()],
+                },
+                SheetRow {
+                    line: 4,
+                    cells: vec!["1002".to_string(), String::new()],
+                },
+            ]
+        );
+
+        assert_eq!(
+            ImportFormat::from_filename("Stock.XLSX"),
+            Some(ImportFormat::Xlsx)
+        );
+        assert_eq!(ImportFormat::from_filename("stock.xls"), None);
+    }
+}
@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{config::JobsConfig, error::Result, services::job_service::JobService};
+
+/// A unit of deferred work, stored as JSON under `KIND`.
+pub trait JobPayload: Serialize + DeserializeOwned + Send + Sync + 'static {
+    const KIND: &'static str;
+    const MAX_ATTEMPTS: u32 = 5;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    /// Worth another attempt after the backoff
+    #[error("{0}")]
+    Retryable(String),
+    /// Retrying cannot help; the job goes straight to dead
+    #[error("{0}")]
+    Permanent(String),
+}
+
+#[async_trait]
+pub trait JobHandler<P: JobPayload>: Send + Sync + 'static {
+    async fn handle(&self, payload: P) -> std::result::Result<(), JobError>;
+}
+
+#[async_trait]
+trait ErasedHandler: Send + Sync {
+    async fn run(&self, payload: serde_json::Value) -> std::result::Result<(), JobError>;
+}
+
+struct Typed<P, H> {
+    handler: Arc<H>,
+    _payload: PhantomData<fn() -> P>,
+}
+
+#[async_trait]
+impl<P: JobPayload, H: JobHandler<P>> ErasedHandler for Typed<P, H> {
+    async fn run(&self, payload: serde_json::Value) -> std::result::Result<(), JobError> {
+        let payload: P = serde_json::from_value(payload)
+            .map_err(|e| JobError::Permanent(format!("Invalid {} payload: {}", P::KIND, e)))?;
+        self.handler.handle(payload).await
+    }
+}
+
+/// Maps each job kind to the handler that runs it.
+#[derive(Default)]
+pub struct JobRegistry {
+    handlers: HashMap<&'static str, Arc<dyn ErasedHandler>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<P: JobPayload, H: JobHandler<P>>(mut self, handler: Arc<H>) -> Self {
+        self.handlers.insert(
+            P::KIND,
+            Arc::new(Typed::<P, H> {
+                handler,
+                _payload: PhantomData,
+            }),
+        );
+        self
+    }
+
+    pub fn kinds(&self) -> Vec<&'static str> {
+        self.handlers.keys().copied().collect()
+    }
+}
+
+/// Pulls due jobs and runs them, settling each as succeeded, retried or dead.
+pub struct JobRunner {
+    jobs: Arc<JobService>,
+    registry: Arc<JobRegistry>,
+    config: JobsConfig,
+}
+
+impl JobRunner {
+    pub fn new(jobs: JobService, registry: JobRegistry, config: JobsConfig) -> Self {
+        Self {
+            jobs: Arc::new(jobs),
+            registry: Arc::new(registry),
+            config,
+        }
+    }
+
+    /// Runs a single due job. Returns `false` when none was waiting.
+    pub async fn run_once(&self) -> Result<bool> {
+        let visibility = Duration::from_secs(self.config.visibility_timeout_seconds.max(1));
+        let Some(lease) = self.jobs.claim(&self.registry.kinds(), visibility).await? else {
+            return Ok(false);
+        };
+        let job = &lease.job;
+
+        // A job that keeps outliving its lease never reports back a failure
+        if job.attempts > job.max_attempts {
+            self.jobs
+                .fail(&lease, "Exceeded max attempts without completing", None)
+                .await?;
+            return Ok(true);
+        }
+
+        let Some(handler) = self.registry.handlers.get(job.kind.as_str()) else {
+            return Ok(true);
+        };
+        let outcome = match tokio::time::timeout(visibility, handler.run(job.payload.clone())).await
+        {
+            Ok(outcome) => outcome,
+            Err(_) => Err(JobError::Retryable("Timed out".to_string())),
+        };
+
+        match outcome {
+            Ok(()) => {
+                tracing::debug!(job_id = %job.id, kind = %job.kind, "Job succeeded");
+                self.jobs.complete(&lease).await?;
+            }
+            Err(JobError::Retryable(error)) if job.attempts < job.max_attempts => {
+                let delay = self.backoff(job.attempts as u32);
+                tracing::warn!(job_id = %job.id, kind = %job.kind, attempt = job.attempts, error = %error, "Job failed, retrying");
+                let retry_at = Utc::now()
+                    + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::hours(1));
+                self.jobs.fail(&lease, &error, Some(retry_at)).await?;
+            }
+            Err(e) => {
+                tracing::error!(job_id = %job.id, kind = %job.kind, error = %e, "Job dead");
+                self.jobs.fail(&lease, &e.to_string(), None).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Delay before the attempt after `attempt`: doubles from the base, capped.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        let seconds = self
+            .config
+            .backoff_base_seconds
+            .saturating_mul(factor)
+            .min(self.config.backoff_max_seconds);
+        Duration::from_secs(seconds)
+    }
+
+    /// Starts the configured number of workers, each polling until aborted.
+    pub fn spawn(self) -> Vec<JoinHandle<()>> {
+        let runner = Arc::new(self);
+        let poll = Duration::from_millis(runner.config.poll_interval_ms.max(10));
+        (0..runner.config.workers.max(1))
+            .map(|_| {
+                let runner = runner.clone();
+                tokio::spawn(async move {
+                    loop {
+                        match runner.run_once().await {
+                            Ok(true) => continue,
+                            Ok(false) => {}
+                            Err(e) => tracing::error!(error = %e, "Job worker error"),
+                        }
+                        tokio::time::sleep(poll).await;
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::CacheManager, database::Database, services::job_service::JobStatus};
+    use serde::Deserialize;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Serialize, Deserialize)]
+    struct Flaky {
+        fail_times: u32,
+    }
+
+    impl JobPayload for Flaky {
+        const KIND: &'static str = "test.flaky";
+        const MAX_ATTEMPTS: u32 = 2;
+    }
+
+    #[derive(Default)]
+    struct FlakyHandler {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl JobHandler<Flaky> for FlakyHandler {
+        async fn handle(&self, payload: Flaky) -> std::result::Result<(), JobError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if call <= payload.fail_times {
+                return Err(JobError::Retryable(format!("failure {}", call)));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runner_retries_with_backoff_then_dead_letters() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let handler = Arc::new(FlakyHandler::default());
+        let config = JobsConfig::builder().backoff_base_seconds(0).build();
+        let runner = JobRunner::new(
+            JobService::new(db.clone(), cache.clone()),
+            JobRegistry::new().register::<Flaky, _>(handler.clone()),
+            config,
+        );
+        let jobs = JobService::new(db, cache);
+
+        let job = jobs.enqueue(&Flaky { fail_times: 5 }).await.unwrap();
+        assert!(runner.run_once().await.unwrap());
+        let retried = jobs.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(retried.status, JobStatus::Queued);
+        assert_eq!(retried.last_error.as_deref(), Some("failure 1"));
+
+        assert!(runner.run_once().await.unwrap());
+        let dead = jobs.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(dead.status, JobStatus::Dead);
+        assert_eq!(dead.attempts, 2);
+        assert!(!runner.run_once().await.unwrap());
+
+        // An admin retry gives it fresh attempts, and it now succeeds
+        jobs.retry_job(job.id).await.unwrap();
+        handler.calls.store(5, Ordering::SeqCst);
+        assert!(runner.run_once().await.unwrap());
+        let done = jobs.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert!(jobs.cancel_job(job.id).await.is_err());
+
+        let delayed = JobRunner::new(
+            JobService::new(
+                Arc::new(Database::in_memory().await.unwrap()),
+                Arc::new(CacheManager::new()),
+            ),
+            JobRegistry::new(),
+            JobsConfig::builder()
+                .backoff_base_seconds(10)
+                .backoff_max_seconds(60)
+                .build(),
+        );
+        assert_eq!(delayed.backoff(1), Duration::from_secs(10));
+        assert_eq!(delayed.backoff(3), Duration::from_secs(40));
+        assert_eq!(delayed.backoff(40), Duration::from_secs(60));
+    }
+}
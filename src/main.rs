@@ -8,20 +8,33 @@ mod cache;
 mod config;
 mod database;
 mod error;
+mod exchange;
+mod export;
 mod handlers;
+mod idempotency;
+mod import;
+mod jobs;
 mod middleware;
+mod money;
+mod payment_gateway;
 mod models;
+mod rate_limit;
 mod services;
+mod shipping;
+mod storage;
+mod tax;
 mod templates;
 mod utils;
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, DEFAULT_CONFIG_FILE};
 use axum::{
-    routing::{delete, get, post, put},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
@@ -31,17 +44,57 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value = "8080")]
-    port: u16,
+    /// Overrides `server.port`
+    #[arg(short, long)]
+    port: Option<u16>,
 
-    #[arg(short, long, default_value = "127.0.0.1")]
-    bind: String,
+    /// Overrides `server.host`
+    #[arg(short, long)]
+    bind: Option<String>,
 
-    #[arg(short, long, default_value = "config.toml")]
-    config: String,
+    /// TOML, YAML or JSON config file [default: config.toml, if present]
+    #[arg(short, long, env = "APP_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Overrides `database.url`
+    #[arg(long)]
+    database_url: Option<String>,
 
     #[arg(long, default_value = "info")]
     log_level: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Manage database schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Stop after applying this version
+        #[arg(long)]
+        target: Option<i64>,
+    },
+    /// Revert the most recently applied migrations
+    Down {
+        #[arg(long, default_value = "1")]
+        steps: usize,
+    },
+    /// List migrations and whether they are applied
+    Status,
+    /// Revert and re-apply the most recent migration
+    Redo,
 }
 
 #[derive(Clone)]
@@ -50,6 +103,13 @@ pub struct AppState {
     pub db: Arc<database::Database>,
     pub cache: Arc<cache::CacheManager>,
     pub http_client: reqwest::Client,
+    pub rate_limiters: Arc<rate_limit::RateLimiters>,
+    pub tax: Arc<dyn tax::TaxCalculator>,
+    pub shipping: Arc<shipping::ShippingRates>,
+    pub exchange: Arc<dyn exchange::RateProvider>,
+    pub payments: Arc<dyn payment_gateway::PaymentGateway>,
+    pub idempotency: Arc<dyn idempotency::IdempotencyStore>,
+    pub blobs: Arc<dyn storage::BlobStore>,
 }
 
 #[tokio::main]
@@ -63,56 +123,338 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Starting compile-benchmark application");
 
-    let config = Arc::new(AppConfig::default());
-    let db = Arc::new(database::Database::new().await?);
+    let config_file = args
+        .config
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+    let config = AppConfig::loader()
+        .with_file(&config_file, args.config.is_some())
+        .with_override_option("server.port", args.port)
+        .with_override_option("server.host", args.bind.clone())
+        .with_override_option("database.url", args.database_url.clone())
+        .load()?;
+
+    if config.uses_default_jwt_secret() {
+        tracing::warn!("Using the built-in JWT secret; set auth.jwt_secret before deploying");
+    }
+
+    let config = Arc::new(config);
+
+    if let Some(Command::Migrate { action }) = args.command {
+        let db = database::Database::connect(&config.database).await?;
+        return run_migrate(&db, action).await;
+    }
+
+    let db = Arc::new(database::Database::new(&config.database).await?);
     let cache = Arc::new(cache::CacheManager::new());
     let http_client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .build()?;
 
+    let rate_limiters = Arc::new(rate_limit::RateLimiters::new(&config.rate_limit));
+    tokio::spawn({
+        let rate_limiters = rate_limiters.clone();
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                rate_limiters.retain_recent();
+            }
+        }
+    });
+
+    tokio::spawn({
+        let sessions = services::session_service::SessionService::new(db.clone(), cache.clone());
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = sessions.purge_expired().await {
+                    tracing::warn!(error = %e, "Failed to purge expired sessions");
+                }
+            }
+        }
+    });
+
+    let tax = Arc::new(tax::RateTable::new(&config.tax));
+    let shipping = Arc::new(shipping::ShippingRates::new(&config.shipping));
+    let exchange = exchange::from_config(&config.exchange, &config.external, cache.clone());
+    let payments = payment_gateway::from_config(&config.payments, config.server.environment)?;
+    let templates = Arc::new(templates::TemplateEngine::from_directory(
+        config.email.templates_dir.as_deref(),
+    )?);
+    let blobs = storage::from_config(&config.uploads, http_client.clone());
+    let idempotency = idempotency::from_config(&config.idempotency, db.clone(), cache.clone());
+    tokio::spawn({
+        let idempotency = idempotency.clone();
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = idempotency.purge_expired().await {
+                    tracing::warn!(error = %e, "Failed to purge expired idempotency keys");
+                }
+            }
+        }
+    });
+
+    tokio::spawn({
+        let exports = services::export_service::ExportService::new(
+            db.clone(),
+            cache.clone(),
+            &config.exports,
+            &config.auth.jwt_secret,
+        );
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = exports.purge_expired().await {
+                    tracing::warn!(error = %e, "Failed to purge expired exports");
+                }
+            }
+        }
+    });
+
+    tokio::spawn({
+        let imports = services::import_service::ImportService::new(
+            db.clone(),
+            cache.clone(),
+            &config.imports,
+            &config.auth,
+        );
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = imports.purge_expired().await {
+                    tracing::warn!(error = %e, "Failed to purge expired imports");
+                }
+            }
+        }
+    });
+
+    if config.jobs.enabled {
+        let email = Arc::new(
+            services::email_service::EmailService::from_config(&config.email)?
+                .with_templates(templates),
+        );
+        let registry = jobs::JobRegistry::new()
+            .register::<services::email_service::EmailMessage, _>(email.clone())
+            .register::<services::email_service::TemplatedEmail, _>(email)
+            .register::<services::notification_service::PushDelivery, _>(Arc::new(
+                services::notification_service::NotificationService::new(),
+            ))
+            .register::<services::export_service::ExportJob, _>(Arc::new(
+                services::export_service::ExportService::new(
+                    db.clone(),
+                    cache.clone(),
+                    &config.exports,
+                    &config.auth.jwt_secret,
+                ),
+            ))
+            .register::<services::import_service::ImportJob, _>(Arc::new(
+                services::import_service::ImportService::new(
+                    db.clone(),
+                    cache.clone(),
+                    &config.imports,
+                    &config.auth,
+                ),
+            ));
+        let runner = jobs::JobRunner::new(
+            services::job_service::JobService::new(db.clone(), cache.clone()),
+            registry,
+            config.jobs.clone(),
+        );
+        runner.spawn();
+    } else {
+        tracing::warn!(
+            "Job workers are disabled; emails, exports and imports stay queued until a process with jobs.enabled runs them"
+        );
+    }
+
+    let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port).parse()?;
+
     let state = AppState {
         config,
         db,
         cache,
         http_client,
+        rate_limiters,
+        tax,
+        shipping,
+        exchange,
+        payments,
+        idempotency,
+        blobs,
     };
 
     let app = create_router(state);
 
-    let addr: SocketAddr = format!("{}:{}", args.bind, args.port).parse()?;
     tracing::info!("Listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn run_migrate(db: &database::Database, action: MigrateAction) -> anyhow::Result<()> {
+    let migrator = db.migrator();
+
+    match action {
+        MigrateAction::Up { target } => {
+            let applied = migrator.up(target).await?;
+            println!("Applied {} migration(s): {:?}", applied.len(), applied);
+        }
+        MigrateAction::Down { steps } => {
+            let reverted = migrator.down(steps).await?;
+            println!("Reverted {} migration(s): {:?}", reverted.len(), reverted);
+        }
+        MigrateAction::Redo => match migrator.redo().await? {
+            Some(version) => println!("Redid migration {}", version),
+            None => println!("No migrations to redo"),
+        },
+        MigrateAction::Status => {
+            for status in migrator.status().await? {
+                let state = match (&status.applied_at, status.checksum_matches) {
+                    (Some(_), false) => "modified",
+                    (Some(_), true) => "applied",
+                    (None, _) => "pending",
+                };
+                println!(
+                    "{:>6}  {:<9} {:<40} {}",
+                    status.version,
+                    state,
+                    status.name,
+                    status.applied_at.as_deref().unwrap_or("-")
+                );
+            }
+        }
+    }
 
     Ok(())
 }
 
 fn create_router(state: AppState) -> Router {
-    let api_routes = Router::new()
+    let credential_routes = Router::new()
+        .route("/auth/login", post(handlers::auth::login))
+        .route("/auth/register", post(handlers::auth::register))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::idempotency_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::auth_rate_limit_middleware,
+        ));
+
+    let public_routes = Router::new()
         .route("/health", get(handlers::health::health_check))
+        .route("/posts", get(handlers::posts::list_posts))
+        .route("/posts/:id", get(handlers::posts::get_post))
+        .route("/auth/refresh", post(handlers::auth::refresh_token))
+        .route("/products", get(handlers::products::list_products))
+        .route("/products/:id", get(handlers::products::get_product))
+        .route("/search", get(handlers::search::search))
+        .route("/shipping/quote", post(handlers::shipping::quote))
+        .route("/export/:id/download", get(handlers::export::download_export))
+        .route("/imports/:id/report", get(handlers::imports::download_report))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::idempotency_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::rate_limit_middleware,
+        ));
+
+    // Authenticated by their signature rather than a token, and not rate
+    // limited so gateway retries always get through
+    let webhook_routes =
+        Router::new().route("/webhooks/payments", post(handlers::webhooks::payments));
+
+    // Role and ownership checks live in the handlers, via `AuthUser`
+    let protected_routes = Router::new()
         .route("/users", get(handlers::users::list_users))
         .route("/users", post(handlers::users::create_user))
         .route("/users/:id", get(handlers::users::get_user))
         .route("/users/:id", put(handlers::users::update_user))
         .route("/users/:id", delete(handlers::users::delete_user))
-        .route("/posts", get(handlers::posts::list_posts))
         .route("/posts", post(handlers::posts::create_post))
-        .route("/posts/:id", get(handlers::posts::get_post))
         .route("/posts/:id", put(handlers::posts::update_post))
         .route("/posts/:id", delete(handlers::posts::delete_post))
-        .route("/auth/login", post(handlers::auth::login))
-        .route("/auth/register", post(handlers::auth::register))
-        .route("/auth/refresh", post(handlers::auth::refresh_token))
-        .route("/products", get(handlers::products::list_products))
-        .route("/products/:id", get(handlers::products::get_product))
         .route("/orders", get(handlers::orders::list_orders))
         .route("/orders", post(handlers::orders::create_order))
         .route("/orders/:id", get(handlers::orders::get_order))
+        .route("/orders/:id/status", patch(handlers::orders::update_order_status))
+        .route("/orders/:id/cancel", post(handlers::orders::cancel_order))
+        .route("/orders/:id/events", get(handlers::orders::list_order_events))
+        .route("/orders/:id/payments", get(handlers::payments::list_order_payments))
+        .route("/orders/:id/payments", post(handlers::payments::create_payment))
+        .route("/payments/:id", get(handlers::payments::get_payment))
+        .route("/payments/:id/confirm", post(handlers::payments::confirm_payment))
+        .route("/payments/:id/authenticate", post(handlers::payments::authenticate_payment))
+        .route("/payments/:id/cancel", post(handlers::payments::cancel_payment))
+        .route("/payments/:id/refunds", get(handlers::payments::list_refunds))
+        .route("/payments/:id/refunds", post(handlers::payments::create_refund))
+        .route("/webhooks/payments/replay", post(handlers::webhooks::replay_payments))
+        .route("/jobs", get(handlers::jobs::list_jobs))
+        .route("/jobs/:id", get(handlers::jobs::get_job))
+        .route("/jobs/:id/retry", post(handlers::jobs::retry_job))
+        .route("/jobs/:id/cancel", post(handlers::jobs::cancel_job))
+        .route("/coupons", get(handlers::coupons::list_coupons))
+        .route("/coupons", post(handlers::coupons::create_coupon))
+        .route("/coupons/:id", get(handlers::coupons::get_coupon))
+        .route("/coupons/:id", put(handlers::coupons::update_coupon))
+        .route("/coupons/:id", delete(handlers::coupons::delete_coupon))
         .route("/analytics", get(handlers::analytics::get_analytics))
-        .route("/search", get(handlers::search::search))
-        .route("/upload", post(handlers::upload::upload_file))
-        .route("/export", get(handlers::export::export_data));
+        .route(
+            "/upload",
+            // Headroom for the multipart framing around the file
+            post(handlers::upload::upload_file).layer(DefaultBodyLimit::max(
+                state.config.server.max_body_size_mb * 1024 * 1024 + 64 * 1024,
+            )),
+        )
+        .route("/uploads/:id", get(handlers::upload::download_file))
+        .route("/export", post(handlers::export::export_data))
+        .route("/export/:id", get(handlers::export::export_status))
+        .route("/export/stream/:resource", get(handlers::export::stream_export))
+        .route(
+            "/imports",
+            // Headroom for the multipart framing around the file
+            post(handlers::imports::start_import).layer(DefaultBodyLimit::max(
+                state.config.imports.max_file_bytes + 64 * 1024,
+            )),
+        )
+        .route("/imports/:id", get(handlers::imports::import_status))
+        .route("/auth/logout", post(handlers::auth::logout))
+        .route("/auth/logout-all", post(handlers::auth::logout_all))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::idempotency_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::rate_limit_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::auth_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::ip_rate_limit_middleware,
+        ));
+
+    let api_routes = credential_routes
+        .merge(public_routes)
+        .merge(webhook_routes)
+        .merge(protected_routes);
 
     Router::new()
         .nest("/api/v1", api_routes)
@@ -121,3 +463,342 @@ fn create_router(state: AppState) -> Router {
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthService;
+    use crate::models::UserRole;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    async fn test_router() -> (Router, AuthService) {
+        test_router_with(AppConfig::default()).await
+    }
+
+    async fn test_router_with(config: AppConfig) -> (Router, AuthService) {
+        let auth = AuthService::from_config(&config.auth);
+        let db = Arc::new(database::Database::in_memory().await.unwrap());
+        let cache = Arc::new(cache::CacheManager::new());
+        let state = AppState {
+            rate_limiters: Arc::new(rate_limit::RateLimiters::new(&config.rate_limit)),
+            tax: Arc::new(tax::RateTable::new(&config.tax)),
+            shipping: Arc::new(shipping::ShippingRates::new(&config.shipping)),
+            exchange: Arc::new(exchange::StaticRates::new(&config.exchange.rates)),
+            payments: Arc::new(payment_gateway::MockGateway::new()),
+            idempotency: idempotency::from_config(&config.idempotency, db.clone(), cache.clone()),
+            blobs: storage::from_config(&config.uploads, reqwest::Client::new()),
+            config: Arc::new(config),
+            db,
+            cache,
+            http_client: reqwest::Client::new(),
+        };
+        (create_router(state), auth)
+    }
+
+    async fn get(router: &Router, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn token(auth: &AuthService, role: UserRole) -> String {
+        auth.generate_token_pair(Uuid::new_v4(), "a@example.com", role.as_str())
+            .unwrap()
+            .access_token
+    }
+
+    #[tokio::test]
+    async fn test_protected_routes_require_token() {
+        let (router, _) = test_router().await;
+
+        assert_eq!(get(&router, "/api/v1/users?page=1&per_page=20", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&router, "/api/v1/orders?page=1&per_page=20", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            get(&router, "/api/v1/analytics", Some("not-a-jwt")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(get(&router, "/api/v1/posts?page=1&per_page=20", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_role_guards() {
+        let (router, auth) = test_router().await;
+        let user = token(&auth, UserRole::User);
+        let admin = token(&auth, UserRole::Admin);
+
+        assert_eq!(get(&router, "/api/v1/analytics", Some(&user)).await, StatusCode::FORBIDDEN);
+        assert_eq!(get(&router, "/api/v1/analytics", Some(&admin)).await, StatusCode::OK);
+        assert_eq!(get(&router, "/api/v1/users?page=1&per_page=20", Some(&user)).await, StatusCode::FORBIDDEN);
+        assert_eq!(get(&router, "/api/v1/orders?page=1&per_page=20", Some(&user)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_login_is_rate_limited() {
+        let config = AppConfig::builder()
+            .rate_limit(
+                config::RateLimitConfig::builder()
+                    .auth(config::QuotaConfig::new(2, 60))
+                    .build(),
+            )
+            .build();
+        let (router, _) = test_router_with(config).await;
+
+        let login = || {
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/auth/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"email":"a@example.com","password":"wrong"}"#))
+                .unwrap()
+        };
+
+        for remaining in ["1", "0"] {
+            let response = router.clone().oneshot(login()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()["ratelimit-remaining"], remaining);
+        }
+
+        let response = router.clone().oneshot(login()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["ratelimit-limit"], "2");
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+        // Other routes draw from a separate quota
+        assert_eq!(get(&router, "/api/v1/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_bad_tokens_are_rate_limited_per_ip() {
+        let config = AppConfig::builder()
+            .rate_limit(
+                config::RateLimitConfig::builder()
+                    .per_ip(config::QuotaConfig::new(2, 60))
+                    .build(),
+            )
+            .build();
+        let (router, _) = test_router_with(config).await;
+
+        let orders = "/api/v1/orders?page=1&per_page=20";
+        for _ in 0..2 {
+            assert_eq!(get(&router, orders, Some("guess")).await, StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(get(&router, orders, Some("guess")).await, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_route_quota_overrides_global_quota() {
+        let config = AppConfig::builder()
+            .rate_limit(
+                config::RateLimitConfig::builder()
+                    .routes(std::collections::HashMap::from([(
+                        "/api/v1/posts".to_string(),
+                        config::QuotaConfig::new(1, 60),
+                    )]))
+                    .build(),
+            )
+            .build();
+        let (router, _) = test_router_with(config).await;
+
+        let posts = "/api/v1/posts?page=1&per_page=20";
+        assert_eq!(get(&router, posts, None).await, StatusCode::OK);
+        assert_eq!(get(&router, posts, None).await, StatusCode::TOO_MANY_REQUESTS);
+        // Routes without an override keep the global quota
+        assert_eq!(get(&router, "/api/v1/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_access_token() {
+        let (router, _) = test_router().await;
+
+        let register = Request::builder()
+            .method("POST")
+            .uri("/api/v1/auth/register")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"email":"kim@example.com","username":"kim","password":"hunter22"}"#,
+            ))
+            .unwrap();
+        let response = router.clone().oneshot(register).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let auth: crate::auth::AuthResponse = serde_json::from_slice(&body).unwrap();
+        let token = auth.tokens.access_token;
+
+        let orders = "/api/v1/orders?page=1&per_page=20";
+        assert_eq!(get(&router, orders, Some(&token)).await, StatusCode::OK);
+
+        let logout = Request::builder()
+            .method("POST")
+            .uri("/api/v1/auth/logout")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(logout).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(get(&router, orders, Some(&token)).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_replays_registration() {
+        let config = AppConfig::builder()
+            .idempotency(
+                config::IdempotencyConfig::builder()
+                    .backend(config::IdempotencyBackend::Database)
+                    .build(),
+            )
+            .build();
+        let (router, _) = test_router_with(config).await;
+
+        let register = |body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("idempotency-key", "signup-1")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let body = r#"{"email":"lee@example.com","username":"lee","password":"hunter22"}"#;
+
+        let first = router.clone().oneshot(register(body)).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let first = axum::body::to_bytes(first.into_body(), usize::MAX).await.unwrap();
+
+        // Registering twice would otherwise conflict on the email
+        let retry = router.clone().oneshot(register(body)).await.unwrap();
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
+        let retry = axum::body::to_bytes(retry.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(retry, first);
+
+        let other = r#"{"email":"max@example.com","username":"max","password":"hunter22"}"#;
+        let reused = router.clone().oneshot(register(other)).await.unwrap();
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_welcome_email_is_queued_on_register_only() {
+        let (router, auth) = test_router().await;
+        let admin = token(&auth, UserRole::Admin);
+        let welcome_jobs = || async {
+            let request = Request::builder()
+                .uri("/api/v1/jobs?page=1&per_page=20&kind=email.template")
+                .header(header::AUTHORIZATION, format!("Bearer {}", admin))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<services::job_service::JobListResponse>(&body)
+                .unwrap()
+                .total
+        };
+        let post = |uri: &'static str, body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let register = post(
+            "/api/v1/auth/register",
+            r#"{"email":"kim@example.com","username":"kim","password":"hunter22"}"#,
+        );
+        assert_eq!(router.clone().oneshot(register).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(welcome_jobs().await, 1);
+
+        let login = post(
+            "/api/v1/auth/login",
+            r#"{"email":"kim@example.com","password":"hunter22"}"#,
+        );
+        assert_eq!(router.clone().oneshot(login).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(welcome_jobs().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_upload_then_download_a_range() {
+        let directory = std::env::temp_dir().join(format!("uploads-{}", Uuid::new_v4()));
+        let config = AppConfig::builder()
+            .uploads(
+                config::UploadConfig::builder()
+                    .directory(directory.to_string_lossy().into_owned())
+                    .build(),
+            )
+            // Uploads are streamed past the idempotency layer, whatever its limit
+            .idempotency(config::IdempotencyConfig::builder().max_body_bytes(16).build())
+            .build();
+        let (router, issuer) = test_router_with(config).await;
+
+        let register = Request::builder()
+            .method("POST")
+            .uri("/api/v1/auth/register")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"email":"ana@example.com","username":"ana","password":"hunter22"}"#,
+            ))
+            .unwrap();
+        let response = router.clone().oneshot(register).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let auth: crate::auth::AuthResponse = serde_json::from_slice(&body).unwrap();
+
+        let pdf = b"%PDF-1.7 a tiny document";
+        let mut form = b"--boundary\r\nContent-Disposition: form-data; name=\"file\"; \
+            filename=\"notes.pdf\"\r\nContent-Type: text/plain\r\n\r\n"
+            .to_vec();
+        form.extend_from_slice(pdf);
+        form.extend_from_slice(b"\r\n--boundary--\r\n");
+        let upload = Request::builder()
+            .method("POST")
+            .uri("/api/v1/upload")
+            .header(header::AUTHORIZATION, format!("Bearer {}", auth.tokens.access_token))
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+            .header("idempotency-key", "upload-1")
+            .body(Body::from(form))
+            .unwrap();
+        let response = router.clone().oneshot(upload).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let uploaded: models::FileUploadResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(uploaded.content_type, "application/pdf");
+        assert_eq!(uploaded.size, pdf.len() as u64);
+
+        let download = |range: &str| {
+            Request::builder()
+                .uri(&uploaded.url)
+                .header(header::AUTHORIZATION, format!("Bearer {}", auth.tokens.access_token))
+                .header(header::RANGE, range)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = router.clone().oneshot(download("bytes=9-12")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 9-12/24");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"a ti");
+
+        let response = router.clone().oneshot(download("bytes=24-")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */24");
+
+        // Only the owner and admins may read it
+        assert_eq!(get(&router, &uploaded.url, None).await, StatusCode::UNAUTHORIZED);
+        let stranger = token(&issuer, UserRole::User);
+        assert_eq!(get(&router, &uploaded.url, Some(&stranger)).await, StatusCode::FORBIDDEN);
+        let admin = token(&issuer, UserRole::Admin);
+        assert_eq!(get(&router, &uploaded.url, Some(&admin)).await, StatusCode::OK);
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
@@ -1,15 +1,26 @@
 use axum::{
     body::Body,
-    extract::State,
-    http::{header, Request, StatusCode},
+    extract::{ConnectInfo, MatchedPath, State},
+    http::{header, HeaderValue, Method, Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::Instant;
 use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::{auth::AuthService, AppState};
+use crate::{
+    auth::{AuthService, AuthUser},
+    cache::cache_key,
+    error::AppError,
+    idempotency::{IdempotencyRecord, StoredResponse, IDEMPOTENCY_KEY, IDEMPOTENT_REPLAYED},
+    rate_limit::{ClientKey, RateLimitInfo},
+    services::session_service::SessionService,
+    utils::{decode_base64, encode_base64},
+    AppState,
+};
 
 pub async fn request_id_middleware(
     mut request: Request<Body>,
@@ -67,40 +78,252 @@ pub async fn logging_middleware(
     response
 }
 
+/// Rejects requests without a valid, unrevoked bearer token and hands the
+/// caller to handlers as an `AuthUser` extension.
 pub async fn auth_middleware(
     State(state): State<AppState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     let auth_header = request
         .headers()
         .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok());
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| AppError::AuthenticationError("Missing authorization header".to_string()))?;
 
-    if let Some(auth_header) = auth_header {
-        let auth_service = AuthService::new(
-            state.config.auth.jwt_secret.clone(),
-            state.config.auth.token_expiry_seconds,
-            state.config.auth.refresh_token_expiry_seconds,
-        );
+    let token = AuthService::extract_token_from_header(auth_header)?;
+    let claims = AuthService::from_config(&state.config.auth)
+        .validate_access_token(token)?
+        .claims;
 
-        if let Ok(token) = AuthService::extract_token_from_header(auth_header) {
-            if auth_service.validate_access_token(token).is_ok() {
-                return Ok(next.run(request).await);
-            }
-        }
+    if SessionService::new(state.db.clone(), state.cache.clone())
+        .is_access_token_revoked(&claims.jti)
+        .await?
+    {
+        return Err(AppError::AuthenticationError("Token has been revoked".to_string()));
     }
 
-    Err(StatusCode::UNAUTHORIZED)
+    request.extensions_mut().insert(AuthUser::from_claims(claims)?);
+    Ok(next.run(request).await)
 }
 
+/// Applies the per-user quota to authenticated requests (layer it inside
+/// `auth_middleware`) and the per-IP quota to everything else.
 pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
     request: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
-    // Simple rate limiting placeholder
-    // In production, use governor or similar
-    Ok(next.run(request).await)
+) -> Response {
+    let limiters = &state.rate_limiters;
+    if !limiters.enabled {
+        return next.run(request).await;
+    }
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| limiters.routes.get(path.as_str()));
+    let user = request.extensions().get::<AuthUser>().map(AuthUser::id);
+    let info = match (route, user) {
+        (Some(limiter), Some(id)) => limiter.check(&ClientKey::User(id)),
+        (Some(limiter), None) => limiter.check(&ClientKey::Ip(client_ip(
+            &request,
+            limiters.trust_forwarded_for,
+        ))),
+        (None, Some(id)) => limiters.authenticated.check(&id),
+        (None, None) => limiters
+            .anonymous
+            .check(&client_ip(&request, limiters.trust_forwarded_for)),
+    };
+    enforce_rate_limit(info, request, next).await
+}
+
+/// Tight per-IP quota for login and registration.
+pub async fn auth_rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let limiters = &state.rate_limiters;
+    if !limiters.enabled {
+        return next.run(request).await;
+    }
+
+    let info = limiters
+        .auth
+        .check(&client_ip(&request, limiters.trust_forwarded_for));
+    enforce_rate_limit(info, request, next).await
+}
+
+/// Per-IP quota for authenticated routes. Layer it outside `auth_middleware`
+/// so requests it would reject still count; the per-user quota inside
+/// reports the headers of requests that get through.
+pub async fn ip_rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let limiters = &state.rate_limiters;
+    if !limiters.enabled {
+        return next.run(request).await;
+    }
+
+    let info = limiters
+        .per_ip
+        .check(&client_ip(&request, limiters.trust_forwarded_for));
+    if info.allowed() {
+        return next.run(request).await;
+    }
+    let mut response = AppError::RateLimitExceeded.into_response();
+    info.apply_headers(response.headers_mut());
+    response
+}
+
+fn is_multipart(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().starts_with("multipart/"))
+}
+
+async fn enforce_rate_limit(info: RateLimitInfo, request: Request<Body>, next: Next) -> Response {
+    let mut response = if info.allowed() {
+        next.run(request).await
+    } else {
+        AppError::RateLimitExceeded.into_response()
+    };
+    info.apply_headers(response.headers_mut());
+    response
+}
+
+/// Runs a POST carrying an `Idempotency-Key` at most once per key and
+/// caller, replaying the stored response to retries. Layer it inside
+/// `auth_middleware` so keys are scoped to the user. Multipart uploads are
+/// streamed by their handlers, so they pass through without being buffered.
+pub async fn idempotency_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let config = &state.config.idempotency;
+    if !config.enabled || request.method() != Method::POST || is_multipart(&request) {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= 255)
+        .ok_or_else(|| {
+            AppError::BadRequest("Idempotency-Key must be 1 to 255 visible characters".to_string())
+        })?
+        .to_string();
+    let scope = request
+        .extensions()
+        .get::<AuthUser>()
+        .map(|user| user.id().to_string())
+        .unwrap_or_else(|| "anonymous".to_string());
+
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, config.max_body_bytes)
+        .await
+        .map_err(|_| AppError::BadRequest("Request body is too large".to_string()))?;
+    let fingerprint = format!(
+        "{:x}",
+        Sha256::new()
+            .chain_update(parts.method.as_str())
+            .chain_update(b" ")
+            .chain_update(parts.uri.path())
+            .chain_update(b"\n")
+            .chain_update(&body)
+            .finalize()
+    );
+
+    let store = &state.idempotency;
+    let store_key = cache_key("idempotency", &[&scope, &key]);
+    match store.claim(&store_key, &fingerprint).await? {
+        None => {}
+        Some(record) if record.fingerprint != fingerprint => {
+            return Err(AppError::UnprocessableEntity(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        Some(IdempotencyRecord {
+            response: Some(stored),
+            ..
+        }) => return replay(stored),
+        Some(_) => {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still in progress".to_string(),
+            ));
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    // Server errors are not final; let the retry run again
+    if response.status().is_server_error() {
+        store.release(&store_key).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            store.release(&store_key).await?;
+            return Err(AppError::InternalError(format!("Failed to read response: {}", e)));
+        }
+    };
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: encode_base64(&body),
+    };
+    if let Err(e) = store.complete(&store_key, &stored).await {
+        warn!(error = %e, "Failed to store idempotent response");
+        store.release(&store_key).await?;
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn replay(stored: StoredResponse) -> Result<Response, AppError> {
+    let body = decode_base64(&stored.body)
+        .map_err(|e| AppError::InternalError(format!("Stored response is corrupt: {}", e)))?;
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(stored.status)
+        .map_err(|e| AppError::InternalError(format!("Stored response is corrupt: {}", e)))?;
+    if let Some(content_type) = stored.content_type.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+fn client_ip(request: &Request<Body>, trust_forwarded_for: bool) -> IpAddr {
+    let forwarded = trust_forwarded_for
+        .then(|| request.headers().get("x-forwarded-for"))
+        .flatten()
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+
+    forwarded
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        })
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
 }
 
 pub async fn cors_middleware(
@@ -119,7 +342,7 @@ pub async fn cors_middleware(
     );
     response.headers_mut().insert(
         header::ACCESS_CONTROL_ALLOW_HEADERS,
-        "Content-Type, Authorization".parse().unwrap(),
+        "Content-Type, Authorization, Idempotency-Key".parse().unwrap(),
     );
     
     response
@@ -2,6 +2,8 @@ pub mod user;
 pub mod post;
 pub mod product;
 pub mod order;
+pub mod coupon;
+pub mod shipping;
 pub mod analytics;
 pub mod common;
 
@@ -9,5 +11,7 @@ pub use user::*;
 pub use post::*;
 pub use product::*;
 pub use order::*;
+pub use coupon::*;
+pub use shipping::*;
 pub use analytics::*;
 pub use common::*;
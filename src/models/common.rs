@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use typed_builder::TypedBuilder;
 
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
@@ -19,6 +20,12 @@ impl Default for PaginationParams {
     }
 }
 
+/// `?currency=` on reads that show prices, converted at the current rate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurrencyParams {
+    pub currency: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
@@ -135,18 +142,29 @@ pub struct FileUploadResponse {
     pub original_filename: String,
     pub content_type: String,
     pub size: u64,
+    /// Lowercase hex SHA-256 of the contents
+    pub sha256: String,
     pub url: String,
     pub thumbnail_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportRequest {
+    pub resource: ExportResource,
     pub format: ExportFormat,
     pub filters: Option<Vec<FilterParam>>,
     pub fields: Option<Vec<String>>,
     pub include_headers: Option<bool>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportResource {
+    Users,
+    Orders,
+    Products,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ExportFormat {
@@ -162,9 +180,10 @@ pub struct ExportResponse {
     pub status: ExportStatus,
     pub download_url: Option<String>,
     pub expires_at: Option<String>,
+    pub error: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum ExportStatus {
     Pending,
@@ -172,3 +191,51 @@ pub enum ExportStatus {
     Completed,
     Failed,
 }
+
+/// The form fields sent alongside an uploaded import file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRequest {
+    pub resource: ImportResource,
+    /// Column header to field name; unmapped headers are matched to fields
+    /// by name
+    pub mapping: Option<HashMap<String, String>>,
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportResource {
+    Products,
+    Users,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub job_id: String,
+    pub status: ImportStatus,
+    pub dry_run: bool,
+    pub summary: Option<ImportSummary>,
+    /// Per-row CSV report, once the import has run
+    pub report_url: Option<String>,
+    pub expires_at: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+/// Row counts by outcome; in a dry run, what would have happened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub total_rows: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
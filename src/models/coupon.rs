@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Coupon {
+    pub id: Uuid,
+    pub code: String,
+    pub kind: CouponKind,
+    /// Percent off for `Percentage`, amount off for `FixedAmount`, unused otherwise
+    pub value: Decimal,
+    /// Currency of a `FixedAmount` value
+    pub currency: Option<String>,
+    pub min_order_value: Option<Decimal>,
+    pub max_uses: Option<i64>,
+    pub max_uses_per_user: Option<i64>,
+    pub used_count: i64,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// When both scopes are empty the coupon applies to every item
+    pub product_ids: Vec<Uuid>,
+    pub category_ids: Vec<Uuid>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Coupon {
+    pub fn applies_to(&self, product_id: Uuid, category_id: Option<Uuid>) -> bool {
+        (self.product_ids.is_empty() && self.category_ids.is_empty())
+            || self.product_ids.contains(&product_id)
+            || category_id.is_some_and(|c| self.category_ids.contains(&c))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CouponKind {
+    Percentage,
+    FixedAmount,
+    FreeShipping,
+}
+
+impl CouponKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CouponKind::Percentage => "percentage",
+            CouponKind::FixedAmount => "fixed_amount",
+            CouponKind::FreeShipping => "free_shipping",
+        }
+    }
+}
+
+impl std::str::FromStr for CouponKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "percentage" => Ok(CouponKind::Percentage),
+            "fixed_amount" => Ok(CouponKind::FixedAmount),
+            "free_shipping" => Ok(CouponKind::FreeShipping),
+            _ => Err(format!("Unknown coupon kind: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct CreateCouponRequest {
+    #[validate(length(min = 1, max = 64))]
+    pub code: String,
+    pub kind: CouponKind,
+    #[validate(custom = "crate::money::validate_non_negative")]
+    #[serde(default)]
+    pub value: Decimal,
+    #[validate(length(equal = 3))]
+    pub currency: Option<String>,
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub min_order_value: Option<Decimal>,
+    #[validate(range(min = 1))]
+    pub max_uses: Option<i64>,
+    #[validate(range(min = 1))]
+    pub max_uses_per_user: Option<i64>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub product_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub category_ids: Vec<Uuid>,
+    pub is_active: Option<bool>,
+}
+
+/// Absent fields are left unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+pub struct UpdateCouponRequest {
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub value: Option<Decimal>,
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub min_order_value: Option<Decimal>,
+    #[validate(range(min = 1))]
+    pub max_uses: Option<i64>,
+    #[validate(range(min = 1))]
+    pub max_uses_per_user: Option<i64>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub product_ids: Option<Vec<Uuid>>,
+    pub category_ids: Option<Vec<Uuid>>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponListResponse {
+    pub coupons: Vec<Coupon>,
+    pub total: i64,
+    pub page: i32,
+    pub per_page: i32,
+}
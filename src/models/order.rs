@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use typed_builder::TypedBuilder;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::money::Money;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, TypedBuilder)]
 pub struct Order {
     pub id: Uuid,
@@ -13,11 +16,11 @@ pub struct Order {
     pub status: OrderStatus,
     pub payment_status: PaymentStatus,
     pub fulfillment_status: FulfillmentStatus,
-    pub subtotal: f64,
-    pub tax_amount: f64,
-    pub shipping_amount: f64,
-    pub discount_amount: f64,
-    pub total: f64,
+    pub subtotal: Money,
+    pub tax_amount: Money,
+    pub shipping_amount: Money,
+    pub discount_amount: Money,
+    pub total: Money,
     pub currency: String,
     pub billing_address: Address,
     pub shipping_address: Address,
@@ -62,6 +65,53 @@ pub enum OrderStatus {
     OnHold,
 }
 
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::Processing => "processing",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::OnHold => "on_hold",
+        }
+    }
+
+    /// Whether an order may move from `self` to `next`.
+    pub fn can_transition_to(&self, next: &OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Confirmed | Cancelled | OnHold)
+                | (Confirmed, Processing | Cancelled | OnHold)
+                | (Processing, Shipped | Cancelled | OnHold)
+                | (OnHold, Pending | Confirmed | Processing | Cancelled)
+                | (Shipped, Delivered)
+                | (Delivered | Cancelled, Refunded)
+        )
+    }
+}
+
+impl std::str::FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(OrderStatus::Pending),
+            "confirmed" => Ok(OrderStatus::Confirmed),
+            "processing" => Ok(OrderStatus::Processing),
+            "shipped" => Ok(OrderStatus::Shipped),
+            "delivered" => Ok(OrderStatus::Delivered),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            "refunded" => Ok(OrderStatus::Refunded),
+            "on_hold" => Ok(OrderStatus::OnHold),
+            _ => Err(format!("Unknown order status: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "payment_status", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
@@ -76,6 +126,59 @@ pub enum PaymentStatus {
     Voided,
 }
 
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Authorized => "authorized",
+            PaymentStatus::Paid => "paid",
+            PaymentStatus::PartiallyPaid => "partially_paid",
+            PaymentStatus::Refunded => "refunded",
+            PaymentStatus::PartiallyRefunded => "partially_refunded",
+            PaymentStatus::Failed => "failed",
+            PaymentStatus::Voided => "voided",
+        }
+    }
+
+    /// Money was captured and not all of it has been refunded.
+    pub fn holds_funds(&self) -> bool {
+        matches!(
+            self,
+            PaymentStatus::Paid | PaymentStatus::PartiallyPaid | PaymentStatus::PartiallyRefunded
+        )
+    }
+
+    pub fn can_transition_to(&self, next: &PaymentStatus) -> bool {
+        use PaymentStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Authorized | Paid | PartiallyPaid | Failed | Voided)
+                | (Authorized, Paid | PartiallyPaid | Failed | Voided)
+                | (PartiallyPaid, Paid | PartiallyRefunded | Refunded)
+                | (Paid | PartiallyRefunded, PartiallyRefunded | Refunded)
+                | (Failed, Pending)
+        )
+    }
+}
+
+impl std::str::FromStr for PaymentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(PaymentStatus::Pending),
+            "authorized" => Ok(PaymentStatus::Authorized),
+            "paid" => Ok(PaymentStatus::Paid),
+            "partially_paid" => Ok(PaymentStatus::PartiallyPaid),
+            "refunded" => Ok(PaymentStatus::Refunded),
+            "partially_refunded" => Ok(PaymentStatus::PartiallyRefunded),
+            "failed" => Ok(PaymentStatus::Failed),
+            "voided" => Ok(PaymentStatus::Voided),
+            _ => Err(format!("Unknown payment status: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "fulfillment_status", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
@@ -88,6 +191,47 @@ pub enum FulfillmentStatus {
     Returned,
 }
 
+impl FulfillmentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FulfillmentStatus::Unfulfilled => "unfulfilled",
+            FulfillmentStatus::PartiallyFulfilled => "partially_fulfilled",
+            FulfillmentStatus::Fulfilled => "fulfilled",
+            FulfillmentStatus::Shipped => "shipped",
+            FulfillmentStatus::Delivered => "delivered",
+            FulfillmentStatus::Returned => "returned",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: &FulfillmentStatus) -> bool {
+        use FulfillmentStatus::*;
+        matches!(
+            (self, next),
+            (Unfulfilled, PartiallyFulfilled | Fulfilled)
+                | (PartiallyFulfilled, Fulfilled)
+                | (Fulfilled, Shipped)
+                | (Shipped, Delivered | Returned)
+                | (Delivered, Returned)
+        )
+    }
+}
+
+impl std::str::FromStr for FulfillmentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unfulfilled" => Ok(FulfillmentStatus::Unfulfilled),
+            "partially_fulfilled" => Ok(FulfillmentStatus::PartiallyFulfilled),
+            "fulfilled" => Ok(FulfillmentStatus::Fulfilled),
+            "shipped" => Ok(FulfillmentStatus::Shipped),
+            "delivered" => Ok(FulfillmentStatus::Delivered),
+            "returned" => Ok(FulfillmentStatus::Returned),
+            _ => Err(format!("Unknown fulfillment status: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct OrderItem {
     pub id: Uuid,
@@ -97,10 +241,10 @@ pub struct OrderItem {
     pub sku: String,
     pub name: String,
     pub quantity: i32,
-    pub unit_price: f64,
-    pub total_price: f64,
-    pub tax_amount: f64,
-    pub discount_amount: f64,
+    pub unit_price: Money,
+    pub total_price: Money,
+    pub tax_amount: Money,
+    pub discount_amount: Money,
     pub metadata: Option<serde_json::Value>,
 }
 
@@ -108,6 +252,7 @@ pub struct OrderItem {
 pub struct CreateOrderRequest {
     pub customer_id: Uuid,
     #[validate(length(min = 1))]
+    #[validate]
     pub items: Vec<CreateOrderItemRequest>,
     #[validate]
     pub billing_address: AddressRequest,
@@ -116,6 +261,9 @@ pub struct CreateOrderRequest {
     pub shipping_method: Option<String>,
     pub notes: Option<String>,
     pub coupon_code: Option<String>,
+    /// Charge in this currency instead of the catalog's
+    #[validate(length(equal = 3))]
+    pub currency: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
@@ -155,16 +303,28 @@ pub struct OrderResponse {
     pub payment_status: PaymentStatus,
     pub fulfillment_status: FulfillmentStatus,
     pub items: Vec<OrderItemResponse>,
-    pub subtotal: f64,
-    pub tax_amount: f64,
-    pub shipping_amount: f64,
-    pub discount_amount: f64,
-    pub total: f64,
+    pub subtotal: Money,
+    pub tax_amount: Money,
+    pub shipping_amount: Money,
+    pub discount_amount: Money,
+    pub total: Money,
     pub currency: String,
+    /// Currency the products are priced in
+    pub catalog_currency: String,
+    /// Units of `currency` per unit of `catalog_currency` at placement
+    pub exchange_rate: Decimal,
+    /// Whether `subtotal` already contains `tax_amount`
+    pub prices_include_tax: bool,
     pub billing_address: Address,
     pub shipping_address: Address,
+    pub shipping_method: Option<String>,
     pub tracking_number: Option<String>,
+    pub coupon_code: Option<String>,
     pub placed_at: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -174,8 +334,33 @@ pub struct OrderItemResponse {
     pub sku: String,
     pub name: String,
     pub quantity: i32,
-    pub unit_price: f64,
-    pub total_price: f64,
+    pub unit_price: Money,
+    pub total_price: Money,
+    pub tax_amount: Money,
+    pub discount_amount: Money,
+}
+
+/// Admin request moving an order along one or more of its state machines.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateOrderStatusRequest {
+    pub status: Option<OrderStatus>,
+    pub payment_status: Option<PaymentStatus>,
+    pub fulfillment_status: Option<FulfillmentStatus>,
+    pub note: Option<String>,
+}
+
+/// One recorded state change. `field` is `status`, `payment_status` or
+/// `fulfillment_status`; `actor_id` is `None` for system changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub field: String,
+    pub from_state: String,
+    pub to_state: String,
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use typed_builder::TypedBuilder;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::money::Money;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, TypedBuilder)]
 pub struct Product {
     pub id: Uuid,
@@ -13,9 +16,9 @@ pub struct Product {
     pub slug: String,
     pub description: String,
     pub short_description: Option<String>,
-    pub price: f64,
-    pub sale_price: Option<f64>,
-    pub cost_price: Option<f64>,
+    pub price: Money,
+    pub sale_price: Option<Money>,
+    pub cost_price: Option<Money>,
     pub currency: String,
     pub quantity: i32,
     pub low_stock_threshold: i32,
@@ -62,10 +65,13 @@ pub struct CreateProductRequest {
     #[validate(length(min = 1))]
     pub description: String,
     pub short_description: Option<String>,
-    #[validate(range(min = 0.0))]
-    pub price: f64,
-    pub sale_price: Option<f64>,
-    pub cost_price: Option<f64>,
+    /// In `currency`
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub price: Decimal,
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub sale_price: Option<Decimal>,
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub cost_price: Option<Decimal>,
     #[validate(length(equal = 3))]
     pub currency: Option<String>,
     #[validate(range(min = 0))]
@@ -88,8 +94,8 @@ pub struct ProductResponse {
     pub slug: String,
     pub description: String,
     pub short_description: Option<String>,
-    pub price: f64,
-    pub sale_price: Option<f64>,
+    pub price: Money,
+    pub sale_price: Option<Money>,
     pub currency: String,
     pub quantity: i32,
     pub in_stock: bool,
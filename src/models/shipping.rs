@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::money::Money;
+
+use super::CreateOrderItemRequest;
+
+/// A cart to price shipping for, before any order exists.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct ShippingQuoteRequest {
+    #[validate(length(min = 1))]
+    pub items: Vec<CreateOrderItemRequest>,
+    #[validate(length(equal = 2))]
+    pub country: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShippingOption {
+    /// Pass as `shipping_method` when creating the order
+    pub id: String,
+    pub carrier: String,
+    pub name: String,
+    pub amount: Money,
+    pub min_days: u32,
+    pub max_days: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShippingQuoteResponse {
+    pub currency: String,
+    /// Kilograms, the greater of actual and volumetric weight
+    pub billable_weight: f64,
+    /// Cheapest first; empty when nothing needs shipping
+    pub options: Vec<ShippingOption>,
+}
@@ -10,7 +10,7 @@ pub struct User {
     pub id: Uuid,
     pub email: String,
     pub username: String,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing, default)]
     pub password_hash: String,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
@@ -34,6 +34,46 @@ pub enum UserRole {
     Guest,
 }
 
+impl UserRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::Admin => "admin",
+            UserRole::Moderator => "moderator",
+            UserRole::User => "user",
+            UserRole::Guest => "guest",
+        }
+    }
+
+    /// Whether this role carries at least the privileges of `required`
+    /// (Admin > Moderator > User > Guest).
+    pub fn includes(&self, required: &UserRole) -> bool {
+        self.rank() >= required.rank()
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            UserRole::Admin => 3,
+            UserRole::Moderator => 2,
+            UserRole::User => 1,
+            UserRole::Guest => 0,
+        }
+    }
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(UserRole::Admin),
+            "moderator" => Ok(UserRole::Moderator),
+            "user" => Ok(UserRole::User),
+            "guest" => Ok(UserRole::Guest),
+            _ => Err(format!("Unknown user role: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "user_status", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -44,6 +84,31 @@ pub enum UserStatus {
     Deleted,
 }
 
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Inactive => "inactive",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Deleted => "deleted",
+        }
+    }
+}
+
+impl std::str::FromStr for UserStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(UserStatus::Active),
+            "inactive" => Ok(UserStatus::Inactive),
+            "suspended" => Ok(UserStatus::Suspended),
+            "deleted" => Ok(UserStatus::Deleted),
+            _ => Err(format!("Unknown user status: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, TypedBuilder)]
 pub struct CreateUserRequest {
     #[validate(email(message = "Invalid email address"))]
@@ -0,0 +1,191 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+use validator::ValidationError;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MoneyError {
+    #[error("Cannot combine {0} with {1}")]
+    CurrencyMismatch(String, String),
+    #[error("Amount {0} does not fit in minor units")]
+    Overflow(Decimal),
+}
+
+/// An exact amount in an ISO 4217 currency. Serializes the amount as a
+/// decimal string, e.g. `{"amount": "12.30", "currency": "USD"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl Money {
+    /// Keeps the amount as given; call `round` before storing or charging.
+    pub fn new(amount: Decimal, currency: impl Into<String>) -> Self {
+        Self {
+            amount,
+            currency: currency.into().to_ascii_uppercase(),
+        }
+    }
+
+    pub fn zero(currency: impl Into<String>) -> Self {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    /// From the integer column form, e.g. cents for USD or yen for JPY.
+    pub fn from_minor(minor: i64, currency: impl Into<String>) -> Self {
+        let currency = currency.into();
+        let amount = Decimal::new(minor, minor_unit_digits(&currency));
+        Self::new(amount, currency)
+    }
+
+    /// The amount in minor units, rounded half away from zero.
+    pub fn to_minor(&self) -> Result<i64, MoneyError> {
+        let digits = minor_unit_digits(&self.currency);
+        let mut scaled = self.round().amount;
+        scaled.rescale(digits);
+        i64::try_from(scaled.mantissa()).map_err(|_| MoneyError::Overflow(self.amount))
+    }
+
+    /// Rounds to the currency's minor unit, half away from zero.
+    pub fn round(&self) -> Self {
+        Self {
+            amount: round_amount(self.amount, &self.currency),
+            currency: self.currency.clone(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.ensure_same_currency(other)?;
+        Ok(Self::new(self.amount + other.amount, self.currency.clone()))
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.ensure_same_currency(other)?;
+        Ok(Self::new(self.amount - other.amount, self.currency.clone()))
+    }
+
+    /// Multiplies by a quantity, rate or ratio; the result is unrounded.
+    pub fn times(&self, factor: Decimal) -> Money {
+        Self::new(self.amount * factor, self.currency.clone())
+    }
+
+    /// Converts at `rate` units of `currency` per unit of this one, rounded
+    /// to the target's minor unit.
+    pub fn exchange(&self, rate: Decimal, currency: &str) -> Money {
+        Self::new(self.amount * rate, currency).round()
+    }
+
+    pub fn min(self, other: Money) -> Money {
+        if other.amount < self.amount {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Sums amounts that must all be in `currency`.
+    pub fn sum<'a>(
+        currency: &str,
+        amounts: impl IntoIterator<Item = &'a Money>,
+    ) -> Result<Money, MoneyError> {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency), |total, amount| total.checked_add(amount))
+    }
+
+    fn ensure_same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch(
+                self.currency.clone(),
+                other.currency.clone(),
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.round().amount, self.currency)
+    }
+}
+
+/// Digits after the decimal point in the currency's minor unit.
+pub fn minor_unit_digits(currency: &str) -> u32 {
+    match currency.to_ascii_uppercase().as_str() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+pub fn round_amount(amount: Decimal, currency: &str) -> Decimal {
+    let digits = minor_unit_digits(currency);
+    let mut rounded = amount.round_dp_with_strategy(digits, RoundingStrategy::MidpointAwayFromZero);
+    rounded.rescale(digits);
+    rounded
+}
+
+/// `validator` hook for decimal request fields that must not be negative.
+pub fn validate_non_negative(amount: &Decimal) -> Result<(), ValidationError> {
+    if amount.is_sign_negative() && !amount.is_zero() {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_currency_aware_rounding_and_minor_units() {
+        assert_eq!(Money::new(dec("2.675"), "usd").round().amount, dec("2.68"));
+        assert_eq!(Money::new(dec("-2.675"), "USD").round().amount, dec("-2.68"));
+        assert_eq!(Money::new(dec("1234.5"), "JPY").round().amount, dec("1235"));
+        assert_eq!(Money::new(dec("1.2345"), "KWD").to_minor().unwrap(), 1235);
+
+        let cents = Money::from_minor(1230, "USD");
+        assert_eq!(cents.amount.to_string(), "12.30");
+        assert_eq!(cents.to_minor().unwrap(), 1230);
+        assert_eq!(Money::from_minor(500, "JPY").amount, dec("500"));
+    }
+
+    #[test]
+    fn test_arithmetic_rejects_mixed_currencies() {
+        let usd = Money::new(dec("0.10"), "USD");
+        let total = Money::sum("USD", [&usd, &usd, &usd]).unwrap();
+        assert_eq!(total.amount, dec("0.30"));
+
+        assert_eq!(
+            usd.checked_add(&Money::new(dec("1"), "EUR")),
+            Err(MoneyError::CurrencyMismatch("USD".to_string(), "EUR".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_serializes_amount_as_string() {
+        let money = Money::from_minor(1999, "EUR");
+        let json = serde_json::to_value(&money).unwrap();
+        assert_eq!(json, serde_json::json!({"amount": "19.99", "currency": "EUR"}));
+
+        let parsed: Money = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, money);
+    }
+}
@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::config::{Deployment, PaymentConfig, PaymentGatewayKind};
+use crate::money::Money;
+use crate::services::payment_service::{
+    PaymentError, PaymentIntentStatus, PaymentMethod, PaymentMethodRequest, RefundStatus,
+};
+
+mod mock;
+
+pub use mock::{test_cards, MockGateway};
+
+/// The configured gateway. The mock is refused in production so a deployed
+/// server never takes test cards as real payments.
+pub fn from_config(
+    config: &PaymentConfig,
+    environment: Deployment,
+) -> Result<Arc<dyn PaymentGateway>, PaymentError> {
+    match config.gateway {
+        PaymentGatewayKind::Mock if environment == Deployment::Production => Err(
+            PaymentError::Misconfigured("the mock gateway cannot run in production".to_string()),
+        ),
+        PaymentGatewayKind::Mock => Ok(Arc::new(MockGateway::new())),
+    }
+}
+
+/// How the gateway knows a newly created intent.
+#[derive(Debug, Clone)]
+pub struct GatewayIntent {
+    pub reference: String,
+    pub client_secret: String,
+}
+
+/// The gateway's view of an intent after an action on it.
+#[derive(Debug, Clone)]
+pub struct GatewayOutcome {
+    pub status: PaymentIntentStatus,
+    /// The method as the gateway recorded it, card number masked
+    pub payment_method: Option<PaymentMethod>,
+    /// Where the customer completes authentication, for `RequiresAction`
+    pub next_action_url: Option<String>,
+    /// Decline code when the payment failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GatewayRefund {
+    pub reference: String,
+    pub status: RefundStatus,
+}
+
+/// A payment provider. Declines are outcomes, not errors; `Err` means the
+/// request itself could not be processed.
+#[async_trait]
+pub trait PaymentGateway: Send + Sync {
+    /// Stored next to each reference, e.g. `mock`
+    fn name(&self) -> &'static str;
+
+    async fn create_intent(&self, amount: &Money) -> Result<GatewayIntent, PaymentError>;
+
+    async fn confirm(
+        &self,
+        reference: &str,
+        method: &PaymentMethodRequest,
+    ) -> Result<GatewayOutcome, PaymentError>;
+
+    /// Resumes an intent once the customer finished the required action.
+    async fn complete_action(&self, reference: &str) -> Result<GatewayOutcome, PaymentError>;
+
+    async fn cancel(&self, reference: &str) -> Result<GatewayOutcome, PaymentError>;
+
+    async fn refund(&self, reference: &str, amount: &Money) -> Result<GatewayRefund, PaymentError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_gateway_is_refused_in_production() {
+        let config = PaymentConfig::default();
+        assert_eq!(
+            from_config(&config, Deployment::Development).unwrap().name(),
+            "mock"
+        );
+        assert!(from_config(&config, Deployment::Test).is_ok());
+        assert!(matches!(
+            from_config(&config, Deployment::Production),
+            Err(PaymentError::Misconfigured(_))
+        ));
+    }
+}
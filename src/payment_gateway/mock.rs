@@ -0,0 +1,316 @@
+use async_trait::async_trait;
+use chrono::{Datelike, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::{GatewayIntent, GatewayOutcome, GatewayRefund, PaymentGateway};
+use crate::money::Money;
+use crate::services::payment_service::{
+    CardDetails, PaymentError, PaymentIntentStatus, PaymentMethod, PaymentMethodRequest,
+    PaymentMethodType, RefundStatus,
+};
+
+/// Card numbers with scripted outcomes. Any other number passing the Luhn
+/// check succeeds.
+pub mod test_cards {
+    pub const SUCCESS: &str = "4242424242424242";
+    pub const DECLINED: &str = "4000000000000002";
+    pub const INSUFFICIENT_FUNDS: &str = "4000000000009995";
+    /// Needs authentication, which then succeeds
+    pub const REQUIRES_ACTION: &str = "4000000000003220";
+    /// Needs authentication, then is declined
+    pub const ACTION_DECLINED: &str = "4000008400001629";
+}
+
+struct MockIntent {
+    amount: Money,
+    refunded: Money,
+    status: PaymentIntentStatus,
+    card_number: Option<String>,
+}
+
+/// An in-process gateway for development and tests; nothing leaves the
+/// process and state lives only as long as the instance.
+#[derive(Default)]
+pub struct MockGateway {
+    intents: Mutex<HashMap<String, MockIntent>>,
+}
+
+impl MockGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_intent<T>(
+        &self,
+        reference: &str,
+        f: impl FnOnce(&mut MockIntent) -> Result<T, PaymentError>,
+    ) -> Result<T, PaymentError> {
+        let mut intents = self.intents.lock().expect("mock gateway lock poisoned");
+        let intent = intents
+            .get_mut(reference)
+            .ok_or_else(|| PaymentError::ApiError(format!("No such payment intent: {}", reference)))?;
+        f(intent)
+    }
+}
+
+#[async_trait]
+impl PaymentGateway for MockGateway {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn create_intent(&self, amount: &Money) -> Result<GatewayIntent, PaymentError> {
+        let reference = format!("mock_pi_{}", Uuid::new_v4().simple());
+        let client_secret = format!("{}_secret_{}", reference, Uuid::new_v4().simple());
+        self.intents.lock().expect("mock gateway lock poisoned").insert(
+            reference.clone(),
+            MockIntent {
+                amount: amount.clone(),
+                refunded: Money::zero(&amount.currency),
+                status: PaymentIntentStatus::RequiresPaymentMethod,
+                card_number: None,
+            },
+        );
+        Ok(GatewayIntent {
+            reference,
+            client_secret,
+        })
+    }
+
+    async fn confirm(
+        &self,
+        reference: &str,
+        method: &PaymentMethodRequest,
+    ) -> Result<GatewayOutcome, PaymentError> {
+        let (payment_method, card_number) = masked(method)?;
+        self.with_intent(reference, |intent| {
+            if !intent.status.is_confirmable() {
+                return Err(PaymentError::InvalidState(format!(
+                    "cannot confirm a payment that is {}",
+                    intent.status.as_str()
+                )));
+            }
+
+            let (status, error) = match card_number.as_deref() {
+                Some(test_cards::DECLINED) => (PaymentIntentStatus::Failed, Some("card_declined")),
+                Some(test_cards::INSUFFICIENT_FUNDS) => {
+                    (PaymentIntentStatus::Failed, Some("insufficient_funds"))
+                }
+                Some(test_cards::REQUIRES_ACTION | test_cards::ACTION_DECLINED) => {
+                    (PaymentIntentStatus::RequiresAction, None)
+                }
+                _ if is_expired(method) => (PaymentIntentStatus::Failed, Some("expired_card")),
+                _ => (PaymentIntentStatus::Succeeded, None),
+            };
+            intent.status = status.clone();
+            intent.card_number = card_number.clone();
+
+            Ok(GatewayOutcome {
+                next_action_url: (status == PaymentIntentStatus::RequiresAction)
+                    .then(|| format!("https://mock-gateway.invalid/3ds/{}", reference)),
+                status,
+                payment_method: Some(payment_method.clone()),
+                error: error.map(str::to_string),
+            })
+        })
+    }
+
+    async fn complete_action(&self, reference: &str) -> Result<GatewayOutcome, PaymentError> {
+        self.with_intent(reference, |intent| {
+            if intent.status != PaymentIntentStatus::RequiresAction {
+                return Err(PaymentError::InvalidState(format!(
+                    "no action pending on a payment that is {}",
+                    intent.status.as_str()
+                )));
+            }
+
+            let declined = intent.card_number.as_deref() == Some(test_cards::ACTION_DECLINED);
+            intent.status = if declined {
+                PaymentIntentStatus::Failed
+            } else {
+                PaymentIntentStatus::Succeeded
+            };
+            Ok(GatewayOutcome {
+                status: intent.status.clone(),
+                payment_method: None,
+                next_action_url: None,
+                error: declined.then(|| "card_declined".to_string()),
+            })
+        })
+    }
+
+    async fn cancel(&self, reference: &str) -> Result<GatewayOutcome, PaymentError> {
+        self.with_intent(reference, |intent| {
+            if matches!(
+                intent.status,
+                PaymentIntentStatus::Succeeded | PaymentIntentStatus::Canceled
+            ) {
+                return Err(PaymentError::InvalidState(format!(
+                    "cannot cancel a payment that is {}",
+                    intent.status.as_str()
+                )));
+            }
+            intent.status = PaymentIntentStatus::Canceled;
+            Ok(GatewayOutcome {
+                status: PaymentIntentStatus::Canceled,
+                payment_method: None,
+                next_action_url: None,
+                error: None,
+            })
+        })
+    }
+
+    async fn refund(&self, reference: &str, amount: &Money) -> Result<GatewayRefund, PaymentError> {
+        self.with_intent(reference, |intent| {
+            if intent.status != PaymentIntentStatus::Succeeded {
+                return Err(PaymentError::RefundFailed(format!(
+                    "payment is {}",
+                    intent.status.as_str()
+                )));
+            }
+            let refunded = intent
+                .refunded
+                .checked_add(amount)
+                .map_err(|e| PaymentError::RefundFailed(e.to_string()))?;
+            if refunded.amount > intent.amount.amount {
+                return Err(PaymentError::RefundFailed(format!(
+                    "{} exceeds the unrefunded amount",
+                    amount
+                )));
+            }
+            intent.refunded = refunded;
+            Ok(GatewayRefund {
+                reference: format!("mock_re_{}", Uuid::new_v4().simple()),
+                status: RefundStatus::Succeeded,
+            })
+        })
+    }
+}
+
+/// The method as it will be stored, plus the bare card number if any.
+fn masked(method: &PaymentMethodRequest) -> Result<(PaymentMethod, Option<String>), PaymentError> {
+    let (card, number) = match (&method.payment_type, &method.card) {
+        (PaymentMethodType::Card, None) => {
+            return Err(PaymentError::InvalidPaymentMethod("card details are required".to_string()));
+        }
+        (_, Some(card)) => {
+            let number: String = card.number.chars().filter(|c| !c.is_whitespace()).collect();
+            if !luhn_valid(&number) {
+                return Err(PaymentError::InvalidPaymentMethod(
+                    "card number is invalid".to_string(),
+                ));
+            }
+            let details = CardDetails {
+                brand: card_brand(&number).to_string(),
+                last4: number[number.len() - 4..].to_string(),
+                exp_month: card.exp_month,
+                exp_year: card.exp_year,
+                funding: "credit".to_string(),
+            };
+            (Some(details), Some(number))
+        }
+        (_, None) => (None, None),
+    };
+
+    Ok((
+        PaymentMethod {
+            id: Uuid::new_v4(),
+            payment_type: method.payment_type.clone(),
+            card,
+            billing_details: method.billing_details.clone(),
+        },
+        number,
+    ))
+}
+
+fn is_expired(method: &PaymentMethodRequest) -> bool {
+    let now = Utc::now();
+    method.card.as_ref().is_some_and(|card| {
+        (i32::from(card.exp_year), u32::from(card.exp_month)) < (now.year(), now.month())
+    })
+}
+
+fn luhn_valid(number: &str) -> bool {
+    if !(12..=19).contains(&number.len()) || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = number
+        .bytes()
+        .rev()
+        .map(|b| u32::from(b - b'0'))
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn card_brand(number: &str) -> &'static str {
+    match number.as_bytes() {
+        [b'4', ..] => "visa",
+        [b'5', b'1'..=b'5', ..] | [b'2', ..] => "mastercard",
+        [b'3', b'4' | b'7', ..] => "amex",
+        [b'6', ..] => "discover",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::payment_service::CardInput;
+
+    fn card(number: &str) -> PaymentMethodRequest {
+        PaymentMethodRequest {
+            payment_type: PaymentMethodType::Card,
+            card: Some(CardInput {
+                number: number.to_string(),
+                exp_month: 12,
+                exp_year: 2099,
+            }),
+            billing_details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_cards() {
+        let gateway = MockGateway::new();
+        let usd = Money::from_minor(1999, "USD");
+
+        let intent = gateway.create_intent(&usd).await.unwrap();
+        let outcome = gateway.confirm(&intent.reference, &card(test_cards::DECLINED)).await.unwrap();
+        assert_eq!(outcome.status, PaymentIntentStatus::Failed);
+        assert_eq!(outcome.error.as_deref(), Some("card_declined"));
+
+        // A failed intent can be retried with another card
+        let outcome = gateway
+            .confirm(&intent.reference, &card("4242 4242 4242 4242"))
+            .await
+            .unwrap();
+        assert_eq!(outcome.status, PaymentIntentStatus::Succeeded);
+        let method = outcome.payment_method.unwrap();
+        assert_eq!(method.card.as_ref().map(|c| c.last4.as_str()), Some("4242"));
+        assert!(gateway.refund(&intent.reference, &usd).await.is_ok());
+        assert!(gateway.refund(&intent.reference, &usd).await.is_err());
+
+        let intent = gateway.create_intent(&usd).await.unwrap();
+        let outcome = gateway
+            .confirm(&intent.reference, &card(test_cards::ACTION_DECLINED))
+            .await
+            .unwrap();
+        assert_eq!(outcome.status, PaymentIntentStatus::RequiresAction);
+        assert!(outcome.next_action_url.is_some());
+        let outcome = gateway.complete_action(&intent.reference).await.unwrap();
+        assert_eq!(outcome.status, PaymentIntentStatus::Failed);
+
+        assert!(matches!(
+            gateway.confirm(&intent.reference, &card("4242424242424241")).await,
+            Err(PaymentError::InvalidPaymentMethod(_))
+        ));
+    }
+}
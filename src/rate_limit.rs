@@ -0,0 +1,192 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    state::keyed::DefaultKeyedStateStore,
+    Quota, RateLimiter,
+};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{QuotaConfig, RateLimitConfig};
+
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Outcome of a rate limit check, rendered as `RateLimit-*` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the quota is fully replenished
+    pub reset: Duration,
+    /// Set when the request was rejected
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    pub fn allowed(&self) -> bool {
+        self.retry_after.is_none()
+    }
+
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(RATELIMIT_RESET, HeaderValue::from(ceil_secs(self.reset)));
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(
+                axum::http::header::RETRY_AFTER,
+                HeaderValue::from(ceil_secs(retry_after).max(1)),
+            );
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// A GCRA limiter holding one bucket per key.
+pub struct KeyedLimiter<K: Hash + Eq + Clone> {
+    limiter: RateLimiter<K, DefaultKeyedStateStore<K>, DefaultClock, StateInformationMiddleware>,
+    clock: DefaultClock,
+    quota: Quota,
+}
+
+impl<K: Hash + Eq + Clone> KeyedLimiter<K> {
+    pub fn new(config: &QuotaConfig) -> Self {
+        let burst = NonZeroU32::new(config.requests).unwrap_or(NonZeroU32::MIN);
+        let period = Duration::from_secs(config.period_seconds.max(1));
+        let quota = Quota::with_period(period / burst.get())
+            .expect("rate limit period is non-zero")
+            .allow_burst(burst);
+        let clock = DefaultClock::default();
+
+        Self {
+            limiter: RateLimiter::new(quota, DefaultKeyedStateStore::default(), &clock),
+            clock,
+            quota,
+        }
+    }
+
+    pub fn check(&self, key: &K) -> RateLimitInfo {
+        let limit = self.quota.burst_size().get();
+        match self.limiter.check_key(key) {
+            Ok(snapshot) => {
+                let remaining = snapshot.remaining_burst_capacity();
+                RateLimitInfo {
+                    limit,
+                    remaining,
+                    reset: self.quota.replenish_interval() * (limit - remaining),
+                    retry_after: None,
+                }
+            }
+            Err(not_until) => {
+                let wait = not_until.wait_time_from(self.clock.now());
+                RateLimitInfo {
+                    limit,
+                    remaining: 0,
+                    reset: self.quota.burst_size_replenished_in(),
+                    retry_after: Some(wait),
+                }
+            }
+        }
+    }
+
+    /// Drops buckets that have fully replenished.
+    pub fn retain_recent(&self) {
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+    }
+}
+
+/// Who a route quota is counted against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    User(Uuid),
+    Ip(IpAddr),
+}
+
+/// The limiters behind `rate_limit_middleware`, `auth_rate_limit_middleware`
+/// and `ip_rate_limit_middleware`.
+pub struct RateLimiters {
+    pub enabled: bool,
+    pub trust_forwarded_for: bool,
+    pub anonymous: KeyedLimiter<IpAddr>,
+    pub authenticated: KeyedLimiter<Uuid>,
+    pub auth: KeyedLimiter<IpAddr>,
+    pub per_ip: KeyedLimiter<IpAddr>,
+    /// Overrides by route path
+    pub routes: HashMap<String, KeyedLimiter<ClientKey>>,
+}
+
+impl RateLimiters {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            trust_forwarded_for: config.trust_forwarded_for,
+            anonymous: KeyedLimiter::new(&config.anonymous),
+            authenticated: KeyedLimiter::new(&config.authenticated),
+            auth: KeyedLimiter::new(&config.auth),
+            per_ip: KeyedLimiter::new(&config.per_ip),
+            routes: config
+                .routes
+                .iter()
+                .map(|(route, quota)| (route.clone(), KeyedLimiter::new(quota)))
+                .collect(),
+        }
+    }
+
+    pub fn retain_recent(&self) {
+        self.anonymous.retain_recent();
+        self.authenticated.retain_recent();
+        self.auth.retain_recent();
+        self.per_ip.retain_recent();
+        for limiter in self.routes.values() {
+            limiter.retain_recent();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exhausts_quota_per_key() {
+        let limiter = KeyedLimiter::new(&QuotaConfig::new(3, 60));
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let remaining: Vec<u32> = (0..3).map(|_| limiter.check(&a).remaining).collect();
+        assert_eq!(remaining, vec![2, 1, 0]);
+
+        let rejected = limiter.check(&a);
+        assert!(!rejected.allowed());
+        assert!(rejected.retry_after.unwrap() <= Duration::from_secs(20));
+
+        assert!(limiter.check(&b).allowed());
+    }
+
+    #[test]
+    fn test_headers() {
+        let info = RateLimitInfo {
+            limit: 5,
+            remaining: 0,
+            reset: Duration::from_secs(60),
+            retry_after: Some(Duration::from_millis(11_500)),
+        };
+        let mut headers = HeaderMap::new();
+        info.apply_headers(&mut headers);
+
+        assert_eq!(headers[RATELIMIT_LIMIT], "5");
+        assert_eq!(headers[RATELIMIT_REMAINING], "0");
+        assert_eq!(headers[RATELIMIT_RESET], "60");
+        assert_eq!(headers[axum::http::header::RETRY_AFTER], "12");
+    }
+}
@@ -1,8 +1,16 @@
 pub mod user_service;
+pub mod session_service;
 pub mod post_service;
 pub mod product_service;
 pub mod order_service;
+pub mod coupon_service;
+pub mod shipping_service;
 pub mod email_service;
 pub mod notification_service;
 pub mod payment_service;
+pub mod webhook_service;
+pub mod job_service;
+pub mod export_service;
+pub mod import_service;
+pub mod upload_service;
 pub mod external_api_service;
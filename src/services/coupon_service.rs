@@ -0,0 +1,614 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    cache::{cache_key, CacheManager},
+    database::{
+        map_unique_violation, parse_decimal, parse_timestamp, parse_uuid, Database, Nullable,
+    },
+    error::{AppError, Result},
+    models::{Coupon, CouponKind, CreateCouponRequest, PaginationParams, UpdateCouponRequest},
+    money::Money,
+};
+
+const COUPON_COLUMNS: &str = "id, code, kind, value, currency, min_order_value, max_uses, \
+     max_uses_per_user, used_count, starts_at, ends_at, product_ids, category_ids, is_active, \
+     created_at, updated_at";
+
+pub struct CouponService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct CouponRow {
+    id: String,
+    code: String,
+    kind: String,
+    value: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    currency: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    min_order_value: Option<String>,
+    #[sqlx(try_from = "Nullable<i64>")]
+    max_uses: Option<i64>,
+    #[sqlx(try_from = "Nullable<i64>")]
+    max_uses_per_user: Option<i64>,
+    used_count: i64,
+    #[sqlx(try_from = "Nullable<String>")]
+    starts_at: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    ends_at: Option<String>,
+    product_ids: String,
+    category_ids: String,
+    is_active: i64,
+    created_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<CouponRow> for Coupon {
+    type Error = AppError;
+
+    fn try_from(row: CouponRow) -> Result<Self> {
+        Ok(Coupon {
+            id: parse_uuid(&row.id)?,
+            code: row.code,
+            kind: row.kind.parse().map_err(AppError::InternalError)?,
+            value: parse_decimal(&row.value)?,
+            currency: row.currency,
+            min_order_value: row.min_order_value.as_deref().map(parse_decimal).transpose()?,
+            max_uses: row.max_uses,
+            max_uses_per_user: row.max_uses_per_user,
+            used_count: row.used_count,
+            starts_at: row.starts_at.as_deref().map(parse_timestamp).transpose()?,
+            ends_at: row.ends_at.as_deref().map(parse_timestamp).transpose()?,
+            product_ids: serde_json::from_str(&row.product_ids)?,
+            category_ids: serde_json::from_str(&row.category_ids)?,
+            is_active: row.is_active != 0,
+            created_at: parse_timestamp(&row.created_at)?,
+            updated_at: parse_timestamp(&row.updated_at)?,
+        })
+    }
+}
+
+/// An order line as seen by the discount calculation.
+#[derive(Debug, Clone)]
+pub struct DiscountLine {
+    pub product_id: Uuid,
+    pub category_id: Option<Uuid>,
+    pub total: Money,
+}
+
+/// Discount per order line (same order as the input) plus any shipping
+/// discount, all in the order's currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discount {
+    pub items: Vec<Money>,
+    pub shipping: Money,
+}
+
+impl Discount {
+    pub fn items_total(&self) -> Money {
+        let total: Decimal = self.items.iter().map(|m| m.amount).sum();
+        Money::new(total, self.shipping.currency.clone())
+    }
+
+    pub fn total(&self) -> Money {
+        Money::new(
+            self.items_total().amount + self.shipping.amount,
+            self.shipping.currency.clone(),
+        )
+    }
+}
+
+impl CouponService {
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
+        Self { db, cache }
+    }
+
+    pub async fn list_coupons(&self, pagination: &PaginationParams) -> Result<(Vec<Coupon>, i64)> {
+        let per_page = pagination.per_page.clamp(1, 100);
+        let offset = (pagination.page.max(1) - 1) * per_page;
+
+        let rows: Vec<CouponRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM coupons ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+            COUPON_COLUMNS
+        ))
+        .bind(per_page as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM coupons")
+            .fetch_one(&self.db.pool)
+            .await?;
+
+        let coupons = rows
+            .into_iter()
+            .map(Coupon::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        Ok((coupons, total))
+    }
+
+    pub async fn get_coupon_by_id(&self, id: Uuid) -> Result<Option<Coupon>> {
+        let cache_key = cache_key("coupon", &[&id.to_string()]);
+        if let Some(coupon) = self.cache.get_json::<Coupon>(&cache_key).await {
+            return Ok(Some(coupon));
+        }
+
+        let row: Option<CouponRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM coupons WHERE id = $1",
+            COUPON_COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        let coupon = row.map(Coupon::try_from).transpose()?;
+        if let Some(ref c) = coupon {
+            let _ = self.cache.set_json(cache_key, c).await;
+        }
+        Ok(coupon)
+    }
+
+    pub async fn create_coupon(&self, request: CreateCouponRequest) -> Result<Coupon> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+        let now = Utc::now();
+        let coupon = Coupon {
+            id: Uuid::new_v4(),
+            code: normalize_code(&request.code),
+            kind: request.kind,
+            value: request.value,
+            currency: request.currency.map(|c| c.to_uppercase()),
+            min_order_value: request.min_order_value,
+            max_uses: request.max_uses,
+            max_uses_per_user: request.max_uses_per_user,
+            used_count: 0,
+            starts_at: request.starts_at,
+            ends_at: request.ends_at,
+            product_ids: request.product_ids,
+            category_ids: request.category_ids,
+            is_active: request.is_active.unwrap_or(true),
+            created_at: now,
+            updated_at: now,
+        };
+        validate_coupon(&coupon)?;
+
+        sqlx::query(&format!(
+            "INSERT INTO coupons ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, \
+             $13, $14, $15, $16)",
+            COUPON_COLUMNS
+        ))
+        .bind(coupon.id.to_string())
+        .bind(&coupon.code)
+        .bind(coupon.kind.as_str())
+        .bind(coupon.value.to_string())
+        .bind(&coupon.currency)
+        .bind(coupon.min_order_value.map(|v| v.to_string()))
+        .bind(coupon.max_uses)
+        .bind(coupon.max_uses_per_user)
+        .bind(coupon.used_count)
+        .bind(coupon.starts_at.map(|t| t.to_rfc3339()))
+        .bind(coupon.ends_at.map(|t| t.to_rfc3339()))
+        .bind(serde_json::to_string(&coupon.product_ids)?)
+        .bind(serde_json::to_string(&coupon.category_ids)?)
+        .bind(coupon.is_active as i64)
+        .bind(coupon.created_at.to_rfc3339())
+        .bind(coupon.updated_at.to_rfc3339())
+        .execute(&self.db.pool)
+        .await
+        .map_err(|e| map_unique_violation(e, "Coupon code already exists"))?;
+
+        Ok(coupon)
+    }
+
+    pub async fn update_coupon(&self, id: Uuid, request: UpdateCouponRequest) -> Result<Coupon> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+        let mut coupon = self
+            .get_coupon_by_id(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Coupon {} not found", id)))?;
+
+        if let Some(value) = request.value {
+            coupon.value = value;
+        }
+        if let Some(min_order_value) = request.min_order_value {
+            coupon.min_order_value = Some(min_order_value);
+        }
+        if let Some(max_uses) = request.max_uses {
+            coupon.max_uses = Some(max_uses);
+        }
+        if let Some(max_uses_per_user) = request.max_uses_per_user {
+            coupon.max_uses_per_user = Some(max_uses_per_user);
+        }
+        if let Some(starts_at) = request.starts_at {
+            coupon.starts_at = Some(starts_at);
+        }
+        if let Some(ends_at) = request.ends_at {
+            coupon.ends_at = Some(ends_at);
+        }
+        if let Some(product_ids) = request.product_ids {
+            coupon.product_ids = product_ids;
+        }
+        if let Some(category_ids) = request.category_ids {
+            coupon.category_ids = category_ids;
+        }
+        if let Some(is_active) = request.is_active {
+            coupon.is_active = is_active;
+        }
+        coupon.updated_at = Utc::now();
+        validate_coupon(&coupon)?;
+
+        sqlx::query(
+            "UPDATE coupons SET value = $1, min_order_value = $2, max_uses = $3, \
+             max_uses_per_user = $4, starts_at = $5, ends_at = $6, product_ids = $7, \
+             category_ids = $8, is_active = $9, updated_at = $10 WHERE id = $11",
+        )
+        .bind(coupon.value.to_string())
+        .bind(coupon.min_order_value.map(|v| v.to_string()))
+        .bind(coupon.max_uses)
+        .bind(coupon.max_uses_per_user)
+        .bind(coupon.starts_at.map(|t| t.to_rfc3339()))
+        .bind(coupon.ends_at.map(|t| t.to_rfc3339()))
+        .bind(serde_json::to_string(&coupon.product_ids)?)
+        .bind(serde_json::to_string(&coupon.category_ids)?)
+        .bind(coupon.is_active as i64)
+        .bind(coupon.updated_at.to_rfc3339())
+        .bind(id.to_string())
+        .execute(&self.db.pool)
+        .await?;
+
+        self.cache
+            .delete(&cache_key("coupon", &[&id.to_string()]))
+            .await;
+        Ok(coupon)
+    }
+
+    pub async fn delete_coupon(&self, id: Uuid) -> Result<()> {
+        let deleted = sqlx::query("DELETE FROM coupons WHERE id = $1")
+            .bind(id.to_string())
+            .execute(&self.db.pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Coupon {} not found", id)));
+        }
+
+        self.cache
+            .delete(&cache_key("coupon", &[&id.to_string()]))
+            .await;
+        Ok(())
+    }
+}
+
+/// Looks up `code` and checks everything that does not depend on the items:
+/// active flag, validity window, currency and minimum order value. `rate`
+/// converts the catalog currency into the order's, as for [`compute_discount`].
+pub async fn load_applicable(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    code: &str,
+    subtotal: &Money,
+    rate: Decimal,
+    now: DateTime<Utc>,
+) -> Result<Coupon> {
+    let currency = subtotal.currency.as_str();
+    let row: Option<CouponRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM coupons WHERE code = $1",
+        COUPON_COLUMNS
+    ))
+    .bind(normalize_code(code))
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let coupon = row
+        .map(Coupon::try_from)
+        .transpose()?
+        .filter(|c| c.is_active)
+        .ok_or_else(|| AppError::BadRequest(format!("Coupon {} is not valid", code)))?;
+
+    if coupon.starts_at.is_some_and(|t| now < t) {
+        return Err(AppError::BadRequest(format!("Coupon {} is not active yet", code)));
+    }
+    if coupon.ends_at.is_some_and(|t| now >= t) {
+        return Err(AppError::BadRequest(format!("Coupon {} has expired", code)));
+    }
+    if coupon.kind == CouponKind::FixedAmount
+        && coupon.currency.as_deref().is_some_and(|c| c != currency)
+    {
+        return Err(AppError::BadRequest(format!(
+            "Coupon {} cannot be used with {} orders",
+            code, currency
+        )));
+    }
+    if let Some(min) = coupon.min_order_value {
+        let min = Money::new(in_order_currency(&coupon, min, currency, rate), currency).round();
+        if subtotal.amount < min.amount {
+            return Err(AppError::BadRequest(format!(
+                "Coupon {} requires an order of at least {}",
+                code, min
+            )));
+        }
+    }
+
+    Ok(coupon)
+}
+
+/// Records a redemption, enforcing the global and per-user caps atomically.
+pub async fn redeem(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    coupon: &Coupon,
+    user_id: Uuid,
+    order_id: Uuid,
+    discount: &Money,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if let Some(per_user) = coupon.max_uses_per_user {
+        let used: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM coupon_redemptions WHERE coupon_id = $1 AND user_id = $2",
+        )
+        .bind(coupon.id.to_string())
+        .bind(user_id.to_string())
+        .fetch_one(&mut **tx)
+        .await?;
+        if used >= per_user {
+            return Err(AppError::Conflict(format!(
+                "Coupon {} has already been used",
+                coupon.code
+            )));
+        }
+    }
+
+    let claimed = sqlx::query(
+        "UPDATE coupons SET used_count = used_count + 1 \
+         WHERE id = $1 AND (max_uses IS NULL OR used_count < max_uses)",
+    )
+    .bind(coupon.id.to_string())
+    .execute(&mut **tx)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Err(AppError::Conflict(format!(
+            "Coupon {} has reached its usage limit",
+            coupon.code
+        )));
+    }
+
+    sqlx::query(
+        "INSERT INTO coupon_redemptions (id, coupon_id, order_id, user_id, discount_amount, \
+         currency, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(coupon.id.to_string())
+    .bind(order_id.to_string())
+    .bind(user_id.to_string())
+    .bind(discount.to_minor()?)
+    .bind(&discount.currency)
+    .bind(now.to_rfc3339())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Gives a cancelled order's coupon use back.
+pub async fn release(tx: &mut sqlx::Transaction<'_, sqlx::Any>, order_id: Uuid) -> Result<()> {
+    let coupon_id: Option<String> =
+        sqlx::query_scalar("SELECT coupon_id FROM coupon_redemptions WHERE order_id = $1")
+            .bind(order_id.to_string())
+            .fetch_optional(&mut **tx)
+            .await?;
+
+    if let Some(coupon_id) = coupon_id {
+        sqlx::query("DELETE FROM coupon_redemptions WHERE order_id = $1")
+            .bind(order_id.to_string())
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("UPDATE coupons SET used_count = used_count - 1 WHERE id = $1 AND used_count > 0")
+            .bind(coupon_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Splits the coupon's discount over the lines it applies to. Fixed amounts
+/// are shared in proportion to line totals, with the rounding remainder on the
+/// last eligible line so the parts add up exactly. `rate` converts the catalog
+/// currency into the order's, for coupons that do not name a currency.
+pub fn compute_discount(
+    coupon: &Coupon,
+    lines: &[DiscountLine],
+    shipping: &Money,
+    rate: Decimal,
+) -> Result<Discount> {
+    let eligible: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| coupon.applies_to(l.product_id, l.category_id))
+        .map(|(i, _)| i)
+        .collect();
+
+    if eligible.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Coupon {} does not apply to any item in this order",
+            coupon.code
+        )));
+    }
+
+    let currency = shipping.currency.as_str();
+    let mut items = vec![Money::zero(currency); lines.len()];
+    let mut shipping_discount = Money::zero(currency);
+
+    match coupon.kind {
+        CouponKind::Percentage => {
+            let rate = coupon.value.min(Decimal::ONE_HUNDRED) / Decimal::ONE_HUNDRED;
+            for &i in &eligible {
+                items[i] = lines[i].total.times(rate).round();
+            }
+        }
+        CouponKind::FixedAmount => {
+            let eligible_total: Decimal = eligible.iter().map(|&i| lines[i].total.amount).sum();
+            let value = in_order_currency(coupon, coupon.value, currency, rate);
+            let amount = Money::new(value.min(eligible_total), currency).round();
+            let mut allocated = Money::zero(currency);
+            for (n, &i) in eligible.iter().enumerate() {
+                items[i] = if n + 1 == eligible.len() {
+                    amount.checked_sub(&allocated)?
+                } else {
+                    amount.times(lines[i].total.amount / eligible_total).round()
+                };
+                allocated = allocated.checked_add(&items[i])?;
+            }
+        }
+        CouponKind::FreeShipping => shipping_discount = shipping.clone(),
+    }
+
+    Ok(Discount {
+        items,
+        shipping: shipping_discount,
+    })
+}
+
+/// An amount of `coupon` in `currency`. Amounts are in the coupon's own
+/// currency when it names the order's, otherwise in the catalog currency,
+/// which `rate` converts.
+fn in_order_currency(coupon: &Coupon, amount: Decimal, currency: &str, rate: Decimal) -> Decimal {
+    if coupon.currency.as_deref() == Some(currency) {
+        amount
+    } else {
+        amount * rate
+    }
+}
+
+fn validate_coupon(coupon: &Coupon) -> Result<()> {
+    if coupon.kind == CouponKind::Percentage && coupon.value > Decimal::ONE_HUNDRED {
+        return Err(AppError::ValidationError(
+            "Percentage discounts cannot exceed 100".to_string(),
+        ));
+    }
+    if let (Some(start), Some(end)) = (coupon.starts_at, coupon.ends_at) {
+        if end <= start {
+            return Err(AppError::ValidationError(
+                "Coupon must end after it starts".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn usd(value: &str) -> Money {
+        Money::new(dec(value), "USD")
+    }
+
+    fn amounts(money: &[Money]) -> Vec<Decimal> {
+        money.iter().map(|m| m.amount).collect()
+    }
+
+    fn coupon(kind: CouponKind, value: &str) -> Coupon {
+        let now = Utc::now();
+        Coupon {
+            id: Uuid::new_v4(),
+            code: "SAVE".to_string(),
+            kind,
+            value: dec(value),
+            currency: None,
+            min_order_value: None,
+            max_uses: None,
+            max_uses_per_user: None,
+            used_count: 0,
+            starts_at: None,
+            ends_at: None,
+            product_ids: Vec::new(),
+            category_ids: Vec::new(),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn lines(totals: &[&str]) -> Vec<DiscountLine> {
+        totals
+            .iter()
+            .map(|total| DiscountLine {
+                product_id: Uuid::new_v4(),
+                category_id: None,
+                total: usd(total),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_percentage_discount() {
+        let discount = compute_discount(
+            &coupon(CouponKind::Percentage, "15"),
+            &lines(&["20.00", "9.99"]),
+            &usd("5.00"),
+            Decimal::ONE,
+        )
+        .unwrap();
+        assert_eq!(amounts(&discount.items), vec![dec("3.00"), dec("1.50")]);
+        assert!(discount.shipping.is_zero());
+    }
+
+    #[test]
+    fn test_fixed_discount_splits_exactly() {
+        let discount = compute_discount(
+            &coupon(CouponKind::FixedAmount, "10"),
+            &lines(&["10.00", "10.00", "10.00"]),
+            &usd("0"),
+            Decimal::ONE,
+        )
+        .unwrap();
+        assert_eq!(amounts(&discount.items), vec![dec("3.33"), dec("3.33"), dec("3.34")]);
+        assert_eq!(discount.total().amount, dec("10.00"));
+
+        // Never more than the eligible lines are worth
+        let capped =
+            compute_discount(&coupon(CouponKind::FixedAmount, "50"), &lines(&["12.50"]), &usd("0"), Decimal::ONE)
+                .unwrap();
+        assert_eq!(capped.total().amount, dec("12.50"));
+    }
+
+    #[test]
+    fn test_scoped_coupon() {
+        let order = lines(&["10.00", "40.00"]);
+        let mut scoped = coupon(CouponKind::Percentage, "50");
+        scoped.product_ids = vec![order[1].product_id];
+
+        let discount = compute_discount(&scoped, &order, &usd("0"), Decimal::ONE).unwrap();
+        assert_eq!(amounts(&discount.items), vec![dec("0"), dec("20.00")]);
+
+        scoped.product_ids = vec![Uuid::new_v4()];
+        assert!(compute_discount(&scoped, &order, &usd("0"), Decimal::ONE).is_err());
+    }
+
+    #[test]
+    fn test_free_shipping() {
+        let discount =
+            compute_discount(&coupon(CouponKind::FreeShipping, "0"), &lines(&["10.00"]), &usd("9.99"), Decimal::ONE)
+                .unwrap();
+        assert!(discount.items_total().is_zero());
+        assert_eq!(discount.total().amount, dec("9.99"));
+    }
+}
@@ -1,4 +1,29 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Attachment, Mailbox, MultiPart, SinglePart,
+    },
+    transport::smtp::{authentication::Credentials, PoolConfig},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    config::{EmailConfig, EmailTransportKind, FileSinkConfig, SmtpConfig, SmtpTls},
+    jobs::{JobError, JobHandler, JobPayload},
+    models::OrderResponse,
+    templates::{
+        OrderConfirmationData, OrderItemData, PasswordResetData, ShippingAddressData,
+        TemplateEngine, TemplateError, WelcomeEmailData,
+    },
+    utils::format_currency,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailMessage {
@@ -13,6 +38,39 @@ pub struct EmailMessage {
     pub headers: Option<std::collections::HashMap<String, String>>,
 }
 
+impl JobPayload for EmailMessage {
+    const KIND: &'static str = "email.send";
+}
+
+/// A templated email deferred to the job queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplatedEmail {
+    pub to: String,
+    pub template: String,
+    pub locale: Option<String>,
+    pub data: serde_json::Value,
+}
+
+impl TemplatedEmail {
+    pub fn new<T: Serialize>(
+        to: &str,
+        template: &str,
+        locale: Option<&str>,
+        data: &T,
+    ) -> serde_json::Result<Self> {
+        Ok(Self {
+            to: to.to_string(),
+            template: template.to_string(),
+            locale: locale.map(str::to_string),
+            data: serde_json::to_value(data)?,
+        })
+    }
+}
+
+impl JobPayload for TemplatedEmail {
+    const KIND: &'static str = "email.template";
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailAttachment {
     pub filename: String,
@@ -28,9 +86,130 @@ pub struct EmailTemplate {
     pub body_text: Option<String>,
 }
 
+/// Hands built messages to whatever delivers them.
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn deliver(&self, message: Message) -> Result<(), EmailError>;
+}
+
+pub fn transport_from_config(config: &EmailConfig) -> Result<Arc<dyn EmailTransport>, EmailError> {
+    Ok(match config.transport {
+        EmailTransportKind::Log => Arc::new(LogTransport),
+        EmailTransportKind::Smtp => Arc::new(SmtpTransport::new(&config.smtp)?),
+        EmailTransportKind::File => Arc::new(FileTransport::new(&config.file)),
+    })
+}
+
+/// Delivers nothing; the message is only logged.
+pub struct LogTransport;
+
+#[async_trait]
+impl EmailTransport for LogTransport {
+    async fn deliver(&self, message: Message) -> Result<(), EmailError> {
+        tracing::debug!(recipients = ?message.envelope().to(), "Email not delivered (log transport)");
+        Ok(())
+    }
+}
+
+/// Pooled connections to an SMTP relay.
+pub struct SmtpTransport {
+    inner: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(config: &SmtpConfig) -> Result<Self, EmailError> {
+        let builder = match config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &config.host,
+            )),
+        }
+        .map_err(|e| EmailError::ConfigError(e.to_string()))?;
+
+        let mut builder = builder
+            .port(config.port)
+            .timeout(Some(Duration::from_secs(config.timeout_seconds)))
+            .pool_config(PoolConfig::new().max_size(config.pool_size.max(1)));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            inner: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn deliver(&self, message: Message) -> Result<(), EmailError> {
+        self.inner
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| EmailError::SendError(e.to_string()))
+    }
+}
+
+/// Writes each message to an `.eml` file for local testing, either flat in
+/// a directory or into a maildir's `new/`.
+pub struct FileTransport {
+    directory: PathBuf,
+    maildir: bool,
+}
+
+impl FileTransport {
+    pub fn new(config: &FileSinkConfig) -> Self {
+        Self {
+            directory: PathBuf::from(&config.directory),
+            maildir: config.maildir,
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn deliver(&self, message: Message) -> Result<(), EmailError> {
+        let name = format!("{}.{}", Utc::now().timestamp(), Uuid::new_v4().simple());
+
+        // Bcc is not in the headers once built, so record every recipient
+        let recipients: Vec<String> = message.envelope().to().iter().map(|a| a.to_string()).collect();
+        let mut contents = format!("X-Envelope-To: {}\r\n", recipients.join(", ")).into_bytes();
+        contents.extend_from_slice(&message.formatted());
+
+        let io = |e: std::io::Error| EmailError::SendError(e.to_string());
+        if self.maildir {
+            // Written under tmp/ and moved, so readers never see a partial file
+            for sub in ["tmp", "new", "cur"] {
+                tokio::fs::create_dir_all(self.directory.join(sub)).await.map_err(io)?;
+            }
+            let staged = self.directory.join("tmp").join(&name);
+            tokio::fs::write(&staged, contents).await.map_err(io)?;
+            tokio::fs::rename(&staged, self.directory.join("new").join(&name))
+                .await
+                .map_err(io)?;
+        } else {
+            tokio::fs::create_dir_all(&self.directory).await.map_err(io)?;
+            tokio::fs::write(self.directory.join(format!("{}.eml", name)), contents)
+                .await
+                .map_err(io)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct EmailService {
     from_address: String,
     from_name: String,
+    /// Prefix for links in emails
+    base_url: String,
+    transport: Arc<dyn EmailTransport>,
+    templates: Arc<TemplateEngine>,
 }
 
 impl EmailService {
@@ -38,9 +217,31 @@ impl EmailService {
         Self {
             from_address,
             from_name,
+            base_url: "https://example.com".to_string(),
+            transport: Arc::new(LogTransport),
+            templates: Arc::new(TemplateEngine::default()),
         }
     }
 
+    /// Sends through the transport `config.transport` selects.
+    pub fn from_config(config: &EmailConfig) -> Result<Self, EmailError> {
+        Ok(Self {
+            base_url: config.base_url.clone(),
+            transport: transport_from_config(config)?,
+            ..Self::new(config.from_address.clone(), config.from_name.clone())
+        })
+    }
+
+    pub fn with_templates(mut self, templates: Arc<TemplateEngine>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Arc<dyn EmailTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
     pub async fn send(&self, message: EmailMessage) -> Result<(), EmailError> {
         tracing::info!(
             to = ?message.to,
@@ -48,71 +249,148 @@ impl EmailService {
             "Sending email"
         );
 
-        // In production, would integrate with SMTP or email API
-        Ok(())
+        let built = self.build(&message)?;
+        self.transport.deliver(built).await
     }
 
-    pub async fn send_welcome_email(&self, to: &str, username: &str) -> Result<(), EmailError> {
-        let message = EmailMessage {
-            to: vec![to.to_string()],
-            cc: None,
-            bcc: None,
-            subject: "Welcome to Our Platform!".to_string(),
-            body_text: Some(format!("Welcome, {}! We're glad to have you.", username)),
-            body_html: Some(format!(
-                "<h1>Welcome, {}!</h1><p>We're glad to have you on our platform.</p>",
-                username
-            )),
-            attachments: None,
-            reply_to: None,
-            headers: None,
+    /// The MIME message for `message`: text and HTML bodies become
+    /// `multipart/alternative`, wrapped in `multipart/mixed` with any
+    /// attachments.
+    pub fn build(&self, message: &EmailMessage) -> Result<Message, EmailError> {
+        let from = Mailbox::new(Some(self.from_name.clone()), parse_address(&self.from_address)?.email);
+        let mut builder = Message::builder().from(from).subject(message.subject.clone());
+        for to in &message.to {
+            builder = builder.to(parse_address(to)?);
+        }
+        for cc in message.cc.iter().flatten() {
+            builder = builder.cc(parse_address(cc)?);
+        }
+        for bcc in message.bcc.iter().flatten() {
+            builder = builder.bcc(parse_address(bcc)?);
+        }
+        if let Some(reply_to) = &message.reply_to {
+            builder = builder.reply_to(parse_address(reply_to)?);
+        }
+        for (name, value) in message.headers.iter().flatten() {
+            let name = HeaderName::new_from_ascii(name.clone())
+                .map_err(|_| EmailError::InvalidMessage(format!("Invalid header name: {}", name)))?;
+            builder = builder.raw_header(HeaderValue::new(name, value.clone()));
+        }
+
+        let body = match (message.body_text.clone(), message.body_html.clone()) {
+            (Some(text), Some(html)) => Body::Alternative(MultiPart::alternative_plain_html(text, html)),
+            (None, Some(html)) => Body::Single(SinglePart::html(html)),
+            (text, None) => Body::Single(SinglePart::plain(text.unwrap_or_default())),
         };
+        let attachments = message.attachments.as_deref().unwrap_or_default();
 
-        self.send(message).await
+        let built = if attachments.is_empty() {
+            match body {
+                Body::Single(part) => builder.singlepart(part),
+                Body::Alternative(parts) => builder.multipart(parts),
+            }
+        } else {
+            let mut mixed = match body {
+                Body::Single(part) => MultiPart::mixed().singlepart(part),
+                Body::Alternative(parts) => MultiPart::mixed().multipart(parts),
+            };
+            for attachment in attachments {
+                let content_type = ContentType::parse(&attachment.content_type).map_err(|_| {
+                    EmailError::InvalidMessage(format!(
+                        "Invalid content type for {}: {}",
+                        attachment.filename, attachment.content_type
+                    ))
+                })?;
+                mixed = mixed.singlepart(
+                    Attachment::new(attachment.filename.clone())
+                        .body(attachment.content.clone(), content_type),
+                );
+            }
+            builder.multipart(mixed)
+        };
+        built.map_err(|e| EmailError::InvalidMessage(e.to_string()))
     }
 
-    pub async fn send_password_reset(&self, to: &str, reset_token: &str) -> Result<(), EmailError> {
-        let reset_url = format!("https://example.com/reset-password?token={}", reset_token);
-
-        let message = EmailMessage {
-            to: vec![to.to_string()],
-            cc: None,
-            bcc: None,
-            subject: "Password Reset Request".to_string(),
-            body_text: Some(format!(
-                "Click this link to reset your password: {}",
-                reset_url
-            )),
-            body_html: Some(format!(
-                "<p>Click <a href=\"{}\">here</a> to reset your password.</p>",
-                reset_url
-            )),
-            attachments: None,
-            reply_to: None,
-            headers: None,
+    pub async fn send_welcome_email(
+        &self,
+        to: &str,
+        username: &str,
+        locale: Option<&str>,
+    ) -> Result<(), EmailError> {
+        let data = WelcomeEmailData {
+            username: username.to_string(),
+            verification_link: None,
         };
+        self.send_template(to, "welcome", locale, &data).await
+    }
 
-        self.send(message).await
+    pub async fn send_password_reset(
+        &self,
+        to: &str,
+        reset_token: &str,
+        expiry_hours: i32,
+        locale: Option<&str>,
+    ) -> Result<(), EmailError> {
+        let data = PasswordResetData {
+            reset_link: format!(
+                "{}/reset-password?token={}",
+                self.base_url.trim_end_matches('/'),
+                urlencoding::encode(reset_token)
+            ),
+            expiry_hours,
+        };
+        self.send_template(to, "password_reset", locale, &data).await
     }
 
     pub async fn send_order_confirmation(
         &self,
         to: &str,
-        order_number: &str,
+        order: &OrderResponse,
+        locale: Option<&str>,
     ) -> Result<(), EmailError> {
+        let address = &order.shipping_address;
+        let data = OrderConfirmationData {
+            order_number: order.order_number.clone(),
+            total: format_currency(&order.total),
+            items: order
+                .items
+                .iter()
+                .map(|item| OrderItemData {
+                    name: item.name.clone(),
+                    quantity: item.quantity,
+                    total_price: format_currency(&item.total_price),
+                })
+                .collect(),
+            shipping_address: ShippingAddressData {
+                first_name: address.first_name.clone(),
+                last_name: address.last_name.clone(),
+                address_line_1: address.address_line_1.clone(),
+                address_line_2: address.address_line_2.clone(),
+                city: address.city.clone(),
+                state: address.state.clone().unwrap_or_default(),
+                postal_code: address.postal_code.clone(),
+                country: address.country.clone(),
+            },
+        };
+        self.send_template(to, "order_confirmation", locale, &data).await
+    }
+
+    /// Sends `email/<template>` rendered for `locale` to one recipient.
+    pub async fn send_template<T: Serialize>(
+        &self,
+        to: &str,
+        template: &str,
+        locale: Option<&str>,
+        data: &T,
+    ) -> Result<(), EmailError> {
+        let rendered = self.templates.render_email(template, locale, data)?;
         let message = EmailMessage {
             to: vec![to.to_string()],
             cc: None,
             bcc: None,
-            subject: format!("Order Confirmation - {}", order_number),
-            body_text: Some(format!(
-                "Your order {} has been confirmed. Thank you for your purchase!",
-                order_number
-            )),
-            body_html: Some(format!(
-                "<h1>Order Confirmed</h1><p>Your order <strong>{}</strong> has been confirmed.</p>",
-                order_number
-            )),
+            subject: rendered.subject,
+            body_text: Some(rendered.text),
+            body_html: Some(rendered.html),
             attachments: None,
             reply_to: None,
             headers: None,
@@ -121,15 +399,42 @@ impl EmailService {
         self.send(message).await
     }
 
-    pub fn from_address(&self) -> &str {
+    pub fn sender_address(&self) -> &str {
         &self.from_address
     }
 
-    pub fn from_name(&self) -> &str {
+    pub fn sender_name(&self) -> &str {
         &self.from_name
     }
 }
 
+enum Body {
+    Single(SinglePart),
+    Alternative(MultiPart),
+}
+
+#[async_trait]
+impl JobHandler<EmailMessage> for EmailService {
+    async fn handle(&self, message: EmailMessage) -> Result<(), JobError> {
+        Ok(self.send(message).await?)
+    }
+}
+
+#[async_trait]
+impl JobHandler<TemplatedEmail> for EmailService {
+    async fn handle(&self, email: TemplatedEmail) -> Result<(), JobError> {
+        self.send_template(&email.to, &email.template, email.locale.as_deref(), &email.data)
+            .await?;
+        Ok(())
+    }
+}
+
+fn parse_address(address: &str) -> Result<Mailbox, EmailError> {
+    address
+        .parse()
+        .map_err(|_| EmailError::InvalidAddress(address.to_string()))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum EmailError {
     #[error("Failed to send email: {0}")]
@@ -140,4 +445,122 @@ pub enum EmailError {
     TemplateNotFound(String),
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    #[error("Invalid email message: {0}")]
+    InvalidMessage(String),
+    #[error("Template error: {0}")]
+    Template(String),
+}
+
+impl From<TemplateError> for EmailError {
+    fn from(error: TemplateError) -> Self {
+        match error {
+            TemplateError::NotFound(name) => EmailError::TemplateNotFound(name),
+            other => EmailError::Template(other.to_string()),
+        }
+    }
+}
+
+/// Only delivery failures are worth retrying; a bad message stays bad.
+impl From<EmailError> for JobError {
+    fn from(error: EmailError) -> Self {
+        match error {
+            EmailError::SendError(_) => JobError::Retryable(error.to_string()),
+            other => JobError::Permanent(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            to: vec!["Ada <ada@example.com>".to_string()],
+            cc: Some(vec!["cc@example.com".to_string()]),
+            bcc: Some(vec!["hidden@example.com".to_string()]),
+            subject: "Your invoice".to_string(),
+            body_text: Some("Invoice attached.".to_string()),
+            body_html: Some("<p>Invoice attached.</p>".to_string()),
+            attachments: Some(vec![EmailAttachment {
+                filename: "invoice.txt".to_string(),
+                content_type: "text/plain".to_string(),
+                content: b"total: 10.00".to_vec(),
+            }]),
+            reply_to: Some("billing@example.com".to_string()),
+            headers: Some(HashMap::from([("X-Order".to_string(), "ORD-1".to_string())])),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_transport_writes_mime_message() {
+        let directory = std::env::temp_dir().join(format!("mail-{}", Uuid::new_v4()));
+        let config = EmailConfig::builder()
+            .from_address("shop@example.com".to_string())
+            .from_name("Shop".to_string())
+            .transport(EmailTransportKind::File)
+            .file(
+                FileSinkConfig::builder()
+                    .directory(directory.to_string_lossy().into_owned())
+                    .maildir(true)
+                    .build(),
+            )
+            .build();
+        let service = EmailService::from_config(&config).unwrap();
+
+        service.send(message()).await.unwrap();
+
+        let mut delivered = std::fs::read_dir(directory.join("new")).unwrap();
+        let path = delivered.next().unwrap().unwrap().path();
+        let eml = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(eml.starts_with("X-Envelope-To: ada@example.com, cc@example.com, hidden@example.com"));
+        assert!(eml.contains("From: Shop <shop@example.com>"));
+        assert!(eml.contains("Reply-To: billing@example.com"));
+        assert!(eml.contains("X-Order: ORD-1"));
+        assert!(!eml.contains("Bcc:"));
+        assert!(eml.contains("multipart/mixed"));
+        assert!(eml.contains("multipart/alternative"));
+        assert!(eml.contains("filename=\"invoice.txt\""));
+
+        let mut invalid = message();
+        invalid.cc = Some(vec!["not an address".to_string()]);
+        assert!(matches!(service.build(&invalid), Err(EmailError::InvalidAddress(_))));
+    }
+
+    #[tokio::test]
+    async fn test_templates_render_localized_parts() {
+        let directory = std::env::temp_dir().join(format!("templates-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(directory.join("email")).unwrap();
+        std::fs::write(directory.join("email/welcome.es.subject"), "¡Bienvenido!").unwrap();
+        std::fs::write(
+            directory.join("email/welcome.es.html"),
+            "{% extends \"base.html\" %}{% block content %}Hola, {{ username }}{% endblock %}",
+        )
+        .unwrap();
+        let templates = TemplateEngine::with_overrides(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let data = WelcomeEmailData {
+            username: "<Ana>".to_string(),
+            verification_link: None,
+        };
+        let spanish = templates.render_email("welcome", Some("es-MX"), &data).unwrap();
+        assert_eq!(spanish.subject, "¡Bienvenido!");
+        assert!(spanish.html.contains("<html lang=\"en\">"));
+        assert!(spanish.html.contains("Hola, &lt;Ana&gt;"));
+        // No Spanish text part, so the default one is used, unescaped
+        assert!(spanish.text.starts_with("Welcome, <Ana>!"));
+
+        let default = templates.render_email("welcome", None, &data).unwrap();
+        assert_eq!(default.subject, "Welcome to Our Platform!");
+        assert!(!default.html.contains("Verify your email"));
+
+        assert!(matches!(
+            templates.render_email("missing", None, &data).map_err(EmailError::from),
+            Err(EmailError::TemplateNotFound(_))
+        ));
+    }
 }
@@ -0,0 +1,574 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::{
+    cache::CacheManager,
+    config::ExportConfig,
+    database::Database,
+    error::{AppError, Result},
+    export::{self, cell_text, ExportTable},
+    jobs::{JobError, JobHandler, JobPayload},
+    models::{
+        ExportFormat, ExportRequest, ExportResource, ExportResponse, ExportStatus, FilterParam,
+        PaginationParams,
+    },
+    services::{
+        job_service::{Job, JobService, JobStatus},
+        order_service::OrderService,
+        product_service::ProductService,
+        user_service::UserService,
+    },
+    utils::{constant_time_eq, hmac_sha256},
+};
+
+const OPERATORS: &[&str] = &["eq", "ne", "gt", "gte", "lt", "lte", "contains", "in"];
+const PAGE_SIZE: i32 = 100;
+
+/// An export waiting in the job queue; the file is named after `export_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportJob {
+    pub export_id: Uuid,
+    pub request: ExportRequest,
+}
+
+impl JobPayload for ExportJob {
+    const KIND: &'static str = "export.generate";
+    const MAX_ATTEMPTS: u32 = 3;
+}
+
+/// `?expires=&signature=` on a download link.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportFile {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// Runs exports as background jobs and serves the results from signed,
+/// expiring links.
+pub struct ExportService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+    config: ExportConfig,
+    signing_key: Vec<u8>,
+}
+
+impl ExportService {
+    pub fn new(
+        db: Arc<Database>,
+        cache: Arc<CacheManager>,
+        config: &ExportConfig,
+        signing_secret: &str,
+    ) -> Self {
+        Self {
+            db,
+            cache,
+            config: config.clone(),
+            signing_key: signing_secret.as_bytes().to_vec(),
+        }
+    }
+
+    pub async fn start(&self, request: ExportRequest) -> Result<ExportResponse> {
+        validate(&request)?;
+        let job = self
+            .jobs()
+            .enqueue(&ExportJob {
+                export_id: Uuid::new_v4(),
+                request,
+            })
+            .await?;
+
+        tracing::info!(job_id = %job.id, "Export job created");
+        Ok(self.to_response(&job))
+    }
+
+    pub async fn status(&self, job_id: Uuid) -> Result<ExportResponse> {
+        let job = self.export_job(job_id).await?;
+        Ok(self.to_response(&job))
+    }
+
+    /// The finished file behind a download link, once its signature and
+    /// expiry check out.
+    pub async fn download(&self, job_id: Uuid, params: &DownloadParams) -> Result<ExportFile> {
+        let expected = self.sign(job_id, params.expires);
+        if !constant_time_eq(expected.as_bytes(), params.signature.as_bytes()) {
+            return Err(AppError::AuthorizationError(
+                "Invalid download signature".to_string(),
+            ));
+        }
+        if params.expires <= Utc::now().timestamp() {
+            return Err(AppError::AuthorizationError(
+                "Download link has expired".to_string(),
+            ));
+        }
+
+        let job = self.export_job(job_id).await?;
+        let payload: ExportJob = serde_json::from_value(job.payload.clone())?;
+        let format = &payload.request.format;
+        let bytes = tokio::fs::read(self.path(payload.export_id, format))
+            .await
+            .map_err(|_| AppError::NotFound(format!("Export {} is not available", job_id)))?;
+
+        Ok(ExportFile {
+            filename: format!(
+                "{}-{}.{}",
+                resource_name(payload.request.resource),
+                job.created_at.format("%Y%m%d%H%M%S"),
+                format.extension()
+            ),
+            content_type: format.content_type(),
+            bytes,
+        })
+    }
+
+    /// Builds the export file for `request` in memory.
+    pub async fn generate(&self, request: &ExportRequest) -> Result<Vec<u8>> {
+        validate(request)?;
+        let fields = request
+            .fields
+            .clone()
+            .unwrap_or_else(|| default_fields(request.resource));
+        let filters = request.filters.as_deref().unwrap_or_default();
+
+        let rows = self
+            .records(request.resource, filters)
+            .await?
+            .into_iter()
+            .map(|record| {
+                fields
+                    .iter()
+                    .map(|field| lookup(&record, field).cloned().unwrap_or(Value::Null))
+                    .collect()
+            })
+            .collect();
+
+        let table = ExportTable {
+            title: resource_name(request.resource).to_string(),
+            headers: fields,
+            rows,
+        };
+        export::render(
+            &table,
+            &request.format,
+            request.include_headers.unwrap_or(true),
+        )
+        .map_err(|e| AppError::InternalError(e.to_string()))
+    }
+
+    /// Deletes export files older than the link lifetime.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let mut entries = match tokio::fs::read_dir(&self.config.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(AppError::InternalError(e.to_string())),
+        };
+
+        let ttl = Duration::from_secs(self.config.url_ttl_seconds);
+        let mut purged = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let expired = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > ttl);
+            if expired && tokio::fs::remove_file(entry.path()).await.is_ok() {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn to_response(&self, job: &Job) -> ExportResponse {
+        let status = match job.status {
+            JobStatus::Queued => ExportStatus::Pending,
+            JobStatus::Running => ExportStatus::Processing,
+            JobStatus::Succeeded => ExportStatus::Completed,
+            JobStatus::Dead | JobStatus::Canceled => ExportStatus::Failed,
+        };
+
+        let expires_at = match (job.status, job.finished_at) {
+            (JobStatus::Succeeded, Some(finished_at)) => {
+                Some(finished_at + chrono::Duration::seconds(self.config.url_ttl_seconds as i64))
+            }
+            _ => None,
+        };
+        // Past its expiry the file may already be purged, so no link is offered
+        let download_url = expires_at
+            .filter(|expires_at| *expires_at > Utc::now())
+            .map(|expires_at| self.download_url(job.id, expires_at));
+
+        ExportResponse {
+            job_id: job.id.to_string(),
+            status,
+            download_url,
+            expires_at: expires_at.map(|t| t.to_rfc3339()),
+            error: match job.status {
+                JobStatus::Dead | JobStatus::Canceled => job.last_error.clone(),
+                _ => None,
+            },
+        }
+    }
+
+    fn download_url(&self, job_id: Uuid, expires_at: DateTime<Utc>) -> String {
+        let expires = expires_at.timestamp();
+        format!(
+            "/api/v1/export/{}/download?expires={}&signature={}",
+            job_id,
+            expires,
+            self.sign(job_id, expires)
+        )
+    }
+
+    fn sign(&self, job_id: Uuid, expires: i64) -> String {
+        let message = format!("export.{}.{}", job_id, expires);
+        format!("{:x}", hmac_sha256(&self.signing_key, message.as_bytes()))
+    }
+
+    fn path(&self, export_id: Uuid, format: &ExportFormat) -> PathBuf {
+        PathBuf::from(&self.config.directory).join(format!("{}.{}", export_id, format.extension()))
+    }
+
+    fn jobs(&self) -> JobService {
+        JobService::new(self.db.clone(), self.cache.clone())
+    }
+
+    async fn export_job(&self, job_id: Uuid) -> Result<Job> {
+        self.jobs()
+            .get_job(job_id)
+            .await?
+            .filter(|job| job.kind == ExportJob::KIND)
+            .ok_or_else(|| AppError::NotFound(format!("Export {} not found", job_id)))
+    }
+
+    /// The records of `resource` matching every filter, as JSON and oldest
+    /// first where the listing allows choosing, up to `max_rows` of them.
+    async fn records(
+        &self,
+        resource: ExportResource,
+        filters: &[FilterParam],
+    ) -> Result<Vec<Value>> {
+        let mut records = Vec::new();
+        for page in 1.. {
+            let pagination = PaginationParams::builder()
+                .page(page)
+                .per_page(PAGE_SIZE)
+                .sort_order(Some("asc".to_string()))
+                .build();
+            let batch = match resource {
+                ExportResource::Users => {
+                    let (users, _) = UserService::new(self.db.clone(), self.cache.clone())
+                        .list_users(&pagination)
+                        .await?;
+                    to_values(&users)?
+                }
+                ExportResource::Orders => {
+                    let (orders, _) = OrderService::new(self.db.clone(), self.cache.clone())
+                        .list_orders(&pagination)
+                        .await?;
+                    to_values(&orders)?
+                }
+                ExportResource::Products => {
+                    let (products, _) = ProductService::new(self.db.clone(), self.cache.clone())
+                        .list_products(&pagination)
+                        .await?;
+                    to_values(&products)?
+                }
+            };
+
+            let done = batch.len() < PAGE_SIZE as usize;
+            records.extend(
+                batch
+                    .into_iter()
+                    .filter(|record| filters.iter().all(|filter| matches(record, filter))),
+            );
+            if done || records.len() >= self.config.max_rows {
+                break;
+            }
+        }
+        records.truncate(self.config.max_rows);
+        Ok(records)
+    }
+}
+
+#[async_trait]
+impl JobHandler<ExportJob> for ExportService {
+    async fn handle(&self, job: ExportJob) -> std::result::Result<(), JobError> {
+        let bytes = self.generate(&job.request).await.map_err(|e| match e {
+            AppError::BadRequest(_) | AppError::InternalError(_) => {
+                JobError::Permanent(e.to_string())
+            }
+            other => JobError::Retryable(other.to_string()),
+        })?;
+
+        let path = self.path(job.export_id, &job.request.format);
+        let write = async {
+            tokio::fs::create_dir_all(&self.config.directory).await?;
+            tokio::fs::write(&path, bytes).await
+        };
+        write
+            .await
+            .map_err(|e| JobError::Retryable(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
+
+fn resource_name(resource: ExportResource) -> &'static str {
+    match resource {
+        ExportResource::Users => "users",
+        ExportResource::Orders => "orders",
+        ExportResource::Products => "products",
+    }
+}
+
+/// Columns used when a request names none; nested values use dotted paths.
+fn default_fields(resource: ExportResource) -> Vec<String> {
+    let fields: &[&str] = match resource {
+        ExportResource::Users => &[
+            "id",
+            "email",
+            "username",
+            "first_name",
+            "last_name",
+            "role",
+            "status",
+            "email_verified",
+            "created_at",
+        ],
+        ExportResource::Orders => &[
+            "id",
+            "order_number",
+            "customer_id",
+            "status",
+            "payment_status",
+            "fulfillment_status",
+            "total.amount",
+            "currency",
+            "placed_at",
+        ],
+        ExportResource::Products => &[
+            "id",
+            "sku",
+            "name",
+            "price.amount",
+            "currency",
+            "quantity",
+            "status",
+            "created_at",
+        ],
+    };
+    fields.iter().map(|f| f.to_string()).collect()
+}
+
+fn validate(request: &ExportRequest) -> Result<()> {
+    if let Some(fields) = &request.fields {
+        if fields.is_empty() || fields.iter().any(|f| f.trim().is_empty()) {
+            return Err(AppError::BadRequest(
+                "fields must name at least one non-empty field".to_string(),
+            ));
+        }
+    }
+    for filter in request.filters.iter().flatten() {
+        if !OPERATORS.contains(&filter.operator.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Unknown filter operator '{}'; expected one of {}",
+                filter.operator,
+                OPERATORS.join(", ")
+            )));
+        }
+        if filter.operator == "in" && !filter.value.is_array() {
+            return Err(AppError::BadRequest(format!(
+                "Filter on '{}' with 'in' needs an array value",
+                filter.field
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn to_values<T: Serialize>(items: &[T]) -> Result<Vec<Value>> {
+    Ok(items
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<serde_json::Result<_>>()?)
+}
+
+/// Follows a dotted path such as `total.amount` into nested objects.
+fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(record, |value, key| value.get(key))
+}
+
+fn matches(record: &Value, filter: &FilterParam) -> bool {
+    let actual = lookup(record, &filter.field).unwrap_or(&Value::Null);
+    let ordering = compare(actual, &filter.value);
+    match filter.operator.as_str() {
+        "eq" => ordering == Some(Ordering::Equal),
+        "ne" => ordering != Some(Ordering::Equal),
+        "gt" => ordering == Some(Ordering::Greater),
+        "gte" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        "lt" => ordering == Some(Ordering::Less),
+        "lte" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        "contains" => cell_text(actual)
+            .to_lowercase()
+            .contains(&cell_text(&filter.value).to_lowercase()),
+        "in" => filter.value.as_array().is_some_and(|candidates| {
+            candidates
+                .iter()
+                .any(|candidate| compare(actual, candidate) == Some(Ordering::Equal))
+        }),
+        _ => false,
+    }
+}
+
+/// Numbers compare numerically, including decimal strings such as money
+/// amounts; other scalars compare as text, which also orders RFC 3339 times.
+fn compare(actual: &Value, expected: &Value) -> Option<Ordering> {
+    let number = |value: &Value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    };
+    match (actual, expected) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => match (number(actual), number(expected)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => Some(cell_text(actual).cmp(&cell_text(expected))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::user_service::UserService;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_export_filters_fields_and_signed_download() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let users = UserService::new(db.clone(), cache.clone());
+        for name in ["ada", "grace", "alan"] {
+            users
+                .create_user_with_password(
+                    format!("{}@example.com", name),
+                    name.to_string(),
+                    "hash".to_string(),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let directory = std::env::temp_dir().join(format!("exports-{}", Uuid::new_v4()));
+        let config = ExportConfig::builder()
+            .directory(directory.to_string_lossy().into_owned())
+            .build();
+        let exports = ExportService::new(db.clone(), cache.clone(), &config, "secret");
+        let request = ExportRequest {
+            resource: ExportResource::Users,
+            format: ExportFormat::Csv,
+            filters: Some(vec![FilterParam {
+                field: "username".to_string(),
+                operator: "in".to_string(),
+                value: json!(["ada", "alan"]),
+            }]),
+            fields: Some(vec!["username".to_string(), "email".to_string()]),
+            include_headers: Some(true),
+        };
+
+        let started = exports.start(request.clone()).await.unwrap();
+        assert_eq!(started.status, ExportStatus::Pending);
+        assert!(started.download_url.is_none());
+
+        // Run the queued job the way a worker would
+        let jobs = JobService::new(db, cache);
+        let lease = jobs
+            .claim(&[ExportJob::KIND], Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        let payload: ExportJob = serde_json::from_value(lease.job.payload.clone()).unwrap();
+        exports.handle(payload).await.unwrap();
+        jobs.complete(&lease).await.unwrap();
+
+        let job_id: Uuid = started.job_id.parse().unwrap();
+        let done = exports.status(job_id).await.unwrap();
+        assert_eq!(done.status, ExportStatus::Completed);
+        let url = done.download_url.unwrap();
+        let query = crate::utils::parse_query_string(url.split_once('?').unwrap().1);
+        let params = DownloadParams {
+            expires: query["expires"].parse().unwrap(),
+            signature: query["signature"].clone(),
+        };
+
+        let file = exports.download(job_id, &params).await.unwrap();
+        assert_eq!(file.content_type, "text/csv; charset=utf-8");
+        assert_eq!(
+            String::from_utf8(file.bytes).unwrap(),
+            "username,email\nada,ada@example.com\nalan,alan@example.com\n"
+        );
+
+        let tampered = DownloadParams {
+            expires: params.expires + 60,
+            signature: params.signature.clone(),
+        };
+        assert!(exports.download(job_id, &tampered).await.is_err());
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[tokio::test]
+    async fn test_row_limit_counts_only_matching_records() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let users = UserService::new(db.clone(), cache.clone());
+        for i in 0..PAGE_SIZE + 5 {
+            users
+                .create_user_with_password(
+                    format!("user{}@example.com", i),
+                    format!("user{}", i),
+                    "hash".to_string(),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let config = ExportConfig::builder().max_rows(1).build();
+        let exports = ExportService::new(db, cache, &config, "secret");
+        // The only match is on the second page
+        let request = ExportRequest {
+            resource: ExportResource::Users,
+            format: ExportFormat::Csv,
+            filters: Some(vec![FilterParam {
+                field: "username".to_string(),
+                operator: "eq".to_string(),
+                value: json!(format!("user{}", PAGE_SIZE + 2)),
+            }]),
+            fields: Some(vec!["username".to_string()]),
+            include_headers: Some(false),
+        };
+
+        let bytes = exports.generate(&request).await.unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), "user102\n");
+    }
+}
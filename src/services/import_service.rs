@@ -0,0 +1,967 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+    auth::AuthService,
+    cache::{cache_key, CacheManager},
+    config::{AuthConfig, ImportConfig},
+    database::{Database, Nullable},
+    error::{AppError, Result},
+    import::{self, ImportFormat, ReportRow, RowAction},
+    jobs::{JobError, JobHandler, JobPayload},
+    models::{
+        CreateProductRequest, CreateUserRequest, ImportRequest, ImportResource, ImportResponse,
+        ImportStatus, ImportSummary,
+    },
+    money::Money,
+    services::{
+        export_service::{DownloadParams, ExportFile},
+        job_service::{Job, JobService, JobStatus},
+    },
+    utils::{constant_time_eq, generate_slug, hmac_sha256},
+};
+
+const PRODUCT_FIELDS: &[&str] = &[
+    "sku",
+    "name",
+    "description",
+    "short_description",
+    "price",
+    "sale_price",
+    "cost_price",
+    "currency",
+    "quantity",
+    "low_stock_threshold",
+    "weight",
+    "is_featured",
+    "is_digital",
+];
+const USER_FIELDS: &[&str] = &["email", "username", "password", "first_name", "last_name"];
+
+/// Field name to cell text. Blank cells are left out, so they keep the
+/// stored value rather than clearing it.
+type Fields = BTreeMap<&'static str, String>;
+
+/// An uploaded file waiting in the job queue, stored under `import_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportJob {
+    pub import_id: Uuid,
+    pub resource: ImportResource,
+    pub format: ImportFormat,
+    #[serde(default)]
+    pub mapping: HashMap<String, String>,
+    pub dry_run: bool,
+}
+
+impl JobPayload for ImportJob {
+    const KIND: &'static str = "import.run";
+    const MAX_ATTEMPTS: u32 = 3;
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ProductValues {
+    id: String,
+    sku: String,
+    name: String,
+    description: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    short_description: Option<String>,
+    price: i64,
+    #[sqlx(try_from = "Nullable<i64>")]
+    sale_price: Option<i64>,
+    #[sqlx(try_from = "Nullable<i64>")]
+    cost_price: Option<i64>,
+    currency: String,
+    quantity: i64,
+    low_stock_threshold: i64,
+    #[sqlx(try_from = "Nullable<f64>")]
+    weight: Option<f64>,
+    is_featured: i64,
+    is_digital: i64,
+}
+
+impl ProductValues {
+    fn fields(&self) -> Fields {
+        let money = |minor: i64| Money::from_minor(minor, &self.currency).amount.to_string();
+        let mut fields = Fields::new();
+        fields.insert("sku", self.sku.clone());
+        fields.insert("name", self.name.clone());
+        fields.insert("description", self.description.clone());
+        if let Some(short_description) = &self.short_description {
+            fields.insert("short_description", short_description.clone());
+        }
+        fields.insert("price", money(self.price));
+        if let Some(sale_price) = self.sale_price {
+            fields.insert("sale_price", money(sale_price));
+        }
+        if let Some(cost_price) = self.cost_price {
+            fields.insert("cost_price", money(cost_price));
+        }
+        fields.insert("currency", self.currency.clone());
+        fields.insert("quantity", self.quantity.to_string());
+        fields.insert("low_stock_threshold", self.low_stock_threshold.to_string());
+        if let Some(weight) = self.weight {
+            fields.insert("weight", weight.to_string());
+        }
+        fields.insert("is_featured", (self.is_featured != 0).to_string());
+        fields.insert("is_digital", (self.is_digital != 0).to_string());
+        fields
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UserValues {
+    id: String,
+    email: String,
+    username: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    first_name: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    last_name: Option<String>,
+}
+
+impl UserValues {
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::new();
+        fields.insert("email", self.email.clone());
+        fields.insert("username", self.username.clone());
+        if let Some(first_name) = &self.first_name {
+            fields.insert("first_name", first_name.clone());
+        }
+        if let Some(last_name) = &self.last_name {
+            fields.insert("last_name", last_name.clone());
+        }
+        fields
+    }
+}
+
+/// Upserts products by SKU and users by email from uploaded CSV or XLSX
+/// files, as background jobs that leave a per-row report behind.
+pub struct ImportService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+    config: ImportConfig,
+    auth: AuthService,
+    signing_key: Vec<u8>,
+}
+
+impl ImportService {
+    pub fn new(
+        db: Arc<Database>,
+        cache: Arc<CacheManager>,
+        config: &ImportConfig,
+        auth: &AuthConfig,
+    ) -> Self {
+        Self {
+            db,
+            cache,
+            config: config.clone(),
+            auth: AuthService::from_config(auth),
+            signing_key: auth.jwt_secret.as_bytes().to_vec(),
+        }
+    }
+
+    /// Stores the upload and queues its import.
+    pub async fn start(
+        &self,
+        request: ImportRequest,
+        filename: &str,
+        bytes: &[u8],
+    ) -> Result<ImportResponse> {
+        let format = ImportFormat::from_filename(filename)
+            .ok_or_else(|| AppError::BadRequest("Upload a .csv or .xlsx file".to_string()))?;
+        if bytes.len() > self.config.max_file_bytes {
+            return Err(AppError::BadRequest(format!(
+                "Files are limited to {} bytes",
+                self.config.max_file_bytes
+            )));
+        }
+        let mapping = request.mapping.unwrap_or_default();
+        let fields = resource_fields(request.resource);
+        if let Some(field) = mapping.values().find(|f| !fields.contains(&f.as_str())) {
+            return Err(AppError::BadRequest(format!(
+                "Unknown field '{}'; expected one of {}",
+                field,
+                fields.join(", ")
+            )));
+        }
+
+        let job = ImportJob {
+            import_id: Uuid::new_v4(),
+            resource: request.resource,
+            format,
+            mapping,
+            dry_run: request.dry_run.unwrap_or(false),
+        };
+        let path = self.upload_path(&job);
+        let write = async {
+            tokio::fs::create_dir_all(&self.config.directory).await?;
+            tokio::fs::write(&path, bytes).await
+        };
+        write
+            .await
+            .map_err(|e| AppError::InternalError(format!("Failed to store upload: {}", e)))?;
+
+        let job = self.jobs().enqueue(&job).await?;
+        tracing::info!(job_id = %job.id, "Import job created");
+        self.to_response(&job).await
+    }
+
+    pub async fn status(&self, job_id: Uuid) -> Result<ImportResponse> {
+        let job = self.import_job(job_id).await?;
+        self.to_response(&job).await
+    }
+
+    /// The report behind a signed link, once its signature and expiry check
+    /// out.
+    pub async fn report(&self, job_id: Uuid, params: &DownloadParams) -> Result<ExportFile> {
+        let expected = self.sign(job_id, params.expires);
+        if !constant_time_eq(expected.as_bytes(), params.signature.as_bytes()) {
+            return Err(AppError::AuthorizationError(
+                "Invalid download signature".to_string(),
+            ));
+        }
+        if params.expires <= Utc::now().timestamp() {
+            return Err(AppError::AuthorizationError(
+                "Download link has expired".to_string(),
+            ));
+        }
+
+        let job = self.import_job(job_id).await?;
+        let payload: ImportJob = serde_json::from_value(job.payload.clone())?;
+        let bytes = tokio::fs::read(self.report_path(payload.import_id))
+            .await
+            .map_err(|_| AppError::NotFound(format!("Report for {} is not available", job_id)))?;
+
+        Ok(ExportFile {
+            filename: format!(
+                "import-{}-report.csv",
+                job.created_at.format("%Y%m%d%H%M%S")
+            ),
+            content_type: "text/csv; charset=utf-8",
+            bytes,
+        })
+    }
+
+    /// Validates and upserts every row of the uploaded file in one
+    /// transaction, which a dry run rolls back. Rows that fail validation
+    /// are reported and skipped; they never abort the rest.
+    pub async fn run(&self, job: &ImportJob) -> Result<ImportSummary> {
+        let bytes = tokio::fs::read(self.upload_path(job))
+            .await
+            .map_err(|e| AppError::InternalError(format!("Upload is missing: {}", e)))?;
+        let table = import::parse(&bytes, job.format)
+            .map_err(|e| AppError::BadRequest(format!("Unreadable file: {}", e)))?;
+        if table.rows.len() > self.config.max_rows {
+            return Err(AppError::BadRequest(format!(
+                "Imports are limited to {} rows",
+                self.config.max_rows
+            )));
+        }
+        let columns = resolve_columns(&table.headers, &job.mapping, job.resource)?;
+
+        let mut tx = self.db.pool.begin().await?;
+        let mut stale = Vec::new();
+        let mut report = Vec::with_capacity(table.rows.len());
+        for sheet_row in &table.rows {
+            let cells = &sheet_row.cells;
+            let provided: Fields = columns
+                .iter()
+                .filter(|(_, column)| !cells[*column].is_empty())
+                .map(|(field, column)| (*field, cells[*column].clone()))
+                .collect();
+            let mut row = match job.resource {
+                ImportResource::Products => {
+                    self.upsert_product(&mut tx, provided, &mut stale).await?
+                }
+                ImportResource::Users => {
+                    self.upsert_user(&mut tx, provided, job.dry_run, &mut stale)
+                        .await?
+                }
+            };
+            row.row = sheet_row.line;
+            report.push(row);
+        }
+
+        if job.dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+            for key in stale {
+                self.cache.delete(&key).await;
+            }
+        }
+
+        let summary = summarize(&report);
+        let bytes =
+            import::write_report(&report).map_err(|e| AppError::InternalError(e.to_string()))?;
+        let write = async {
+            tokio::fs::write(self.report_path(job.import_id), bytes).await?;
+            tokio::fs::write(
+                self.summary_path(job.import_id),
+                serde_json::to_vec(&summary)?,
+            )
+            .await
+        };
+        write
+            .await
+            .map_err(|e| AppError::InternalError(format!("Failed to write report: {}", e)))?;
+        let _ = tokio::fs::remove_file(self.upload_path(job)).await;
+
+        tracing::info!(import_id = %job.import_id, dry_run = job.dry_run, ?summary, "Import finished");
+        Ok(summary)
+    }
+
+    /// Deletes reports, and uploads whose job never finished, older than the
+    /// link lifetime.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let mut entries = match tokio::fs::read_dir(&self.config.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(AppError::InternalError(e.to_string())),
+        };
+
+        let ttl = Duration::from_secs(self.config.url_ttl_seconds);
+        let mut purged = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let expired = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > ttl);
+            if expired && tokio::fs::remove_file(entry.path()).await.is_ok() {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    async fn upsert_product(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+        provided: Fields,
+        stale: &mut Vec<String>,
+    ) -> Result<ReportRow> {
+        let sku = provided.get("sku").cloned().unwrap_or_default();
+        if sku.is_empty() {
+            return Ok(failed(sku, vec!["sku is required".to_string()]));
+        }
+
+        let existing: Option<ProductValues> = sqlx::query_as(
+            "SELECT id, sku, name, description, short_description, price, sale_price, \
+             cost_price, currency, quantity, low_stock_threshold, weight, is_featured, \
+             is_digital FROM products WHERE sku = $1",
+        )
+        .bind(&sku)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let base = existing
+            .as_ref()
+            .map(ProductValues::fields)
+            .unwrap_or_default();
+        let mut merged = base.clone();
+        merged.extend(provided);
+
+        let mut errors = Vec::new();
+        for field in ["name", "description", "price", "quantity"] {
+            if !merged.contains_key(field) {
+                errors.push(format!("{} is required", field));
+            }
+        }
+        let request = CreateProductRequest {
+            sku: sku.clone(),
+            name: merged.get("name").cloned().unwrap_or_default(),
+            description: merged.get("description").cloned().unwrap_or_default(),
+            short_description: merged.get("short_description").cloned(),
+            price: parsed(&merged, "price", &mut errors).unwrap_or_default(),
+            sale_price: parsed(&merged, "sale_price", &mut errors),
+            cost_price: parsed(&merged, "cost_price", &mut errors),
+            currency: merged.get("currency").cloned(),
+            quantity: parsed(&merged, "quantity", &mut errors).unwrap_or_default(),
+            low_stock_threshold: parsed(&merged, "low_stock_threshold", &mut errors),
+            weight: parsed(&merged, "weight", &mut errors),
+            dimensions: None,
+            images: None,
+            category_id: None,
+            brand_id: None,
+            is_featured: flag(&merged, "is_featured", &mut errors),
+            is_digital: flag(&merged, "is_digital", &mut errors),
+        };
+        if errors.is_empty() {
+            if let Err(e) = request.validate() {
+                errors.extend(messages(&e, &[]));
+            }
+        }
+        if !errors.is_empty() {
+            return Ok(failed(sku, errors));
+        }
+
+        let currency = request
+            .currency
+            .clone()
+            .unwrap_or_else(|| "USD".to_string())
+            .to_ascii_uppercase();
+        let minor = |amount: Decimal| Money::new(amount, &currency).to_minor();
+        let (price, sale_price, cost_price) = match (
+            minor(request.price),
+            request.sale_price.map(minor).transpose(),
+            request.cost_price.map(minor).transpose(),
+        ) {
+            (Ok(price), Ok(sale_price), Ok(cost_price)) => (price, sale_price, cost_price),
+            _ => return Ok(failed(sku, vec!["prices are out of range".to_string()])),
+        };
+        let values = ProductValues {
+            id: existing
+                .as_ref()
+                .map(|p| p.id.clone())
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            sku: sku.clone(),
+            name: request.name,
+            description: request.description,
+            short_description: request.short_description,
+            price,
+            sale_price,
+            cost_price,
+            currency,
+            quantity: request.quantity as i64,
+            low_stock_threshold: request.low_stock_threshold.unwrap_or(10) as i64,
+            weight: request.weight,
+            is_featured: request.is_featured.unwrap_or(false) as i64,
+            is_digital: request.is_digital.unwrap_or(false) as i64,
+        };
+        let changes = changed(&base, &values.fields(), PRODUCT_FIELDS);
+
+        let now = Utc::now().to_rfc3339();
+        let action = match &existing {
+            None => {
+                // The id suffix keeps slugs unique when names repeat
+                let slug = format!("{}-{}", generate_slug(&values.name), &values.id[..8]);
+                sqlx::query(
+                    "INSERT INTO products (id, sku, name, slug, description, \
+                     short_description, price, sale_price, cost_price, currency, quantity, \
+                     low_stock_threshold, weight, is_featured, is_digital, created_at, \
+                     updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, \
+                     $13, $14, $15, $16, $16)",
+                )
+                .bind(&values.id)
+                .bind(&values.sku)
+                .bind(&values.name)
+                .bind(slug)
+                .bind(&values.description)
+                .bind(&values.short_description)
+                .bind(values.price)
+                .bind(values.sale_price)
+                .bind(values.cost_price)
+                .bind(&values.currency)
+                .bind(values.quantity)
+                .bind(values.low_stock_threshold)
+                .bind(values.weight)
+                .bind(values.is_featured)
+                .bind(values.is_digital)
+                .bind(&now)
+                .execute(&mut **tx)
+                .await?;
+                RowAction::Created
+            }
+            Some(_) if changes.is_empty() => RowAction::Unchanged,
+            Some(_) => {
+                sqlx::query(
+                    "UPDATE products SET name = $1, description = $2, short_description = $3, \
+                     price = $4, sale_price = $5, cost_price = $6, currency = $7, \
+                     quantity = $8, low_stock_threshold = $9, weight = $10, \
+                     is_featured = $11, is_digital = $12, updated_at = $13 WHERE id = $14",
+                )
+                .bind(&values.name)
+                .bind(&values.description)
+                .bind(&values.short_description)
+                .bind(values.price)
+                .bind(values.sale_price)
+                .bind(values.cost_price)
+                .bind(&values.currency)
+                .bind(values.quantity)
+                .bind(values.low_stock_threshold)
+                .bind(values.weight)
+                .bind(values.is_featured)
+                .bind(values.is_digital)
+                .bind(&now)
+                .bind(&values.id)
+                .execute(&mut **tx)
+                .await?;
+                stale.push(cache_key("product", &[&values.id]));
+                stale.push(cache_key("product:sku", &[&values.sku]));
+                RowAction::Updated
+            }
+        };
+
+        Ok(ReportRow {
+            row: 0,
+            key: sku,
+            action,
+            changes: if action == RowAction::Updated {
+                changes
+            } else {
+                Vec::new()
+            },
+            errors: Vec::new(),
+        })
+    }
+
+    /// New users need a password; existing ones keep theirs unless the row
+    /// sets one. Imported users always get the default role.
+    async fn upsert_user(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+        provided: Fields,
+        dry_run: bool,
+        stale: &mut Vec<String>,
+    ) -> Result<ReportRow> {
+        let email = provided.get("email").cloned().unwrap_or_default();
+        if email.is_empty() {
+            return Ok(failed(email, vec!["email is required".to_string()]));
+        }
+
+        let existing: Option<UserValues> = sqlx::query_as(
+            "SELECT id, email, username, first_name, last_name FROM users WHERE email = $1",
+        )
+        .bind(&email)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let base = existing
+            .as_ref()
+            .map(UserValues::fields)
+            .unwrap_or_default();
+        let mut merged = base.clone();
+        merged.extend(provided);
+        let password = merged.remove("password");
+
+        let mut errors = Vec::new();
+        if !merged.contains_key("username") {
+            errors.push("username is required".to_string());
+        }
+        if existing.is_none() && password.is_none() {
+            errors.push("password is required".to_string());
+        }
+        let request = CreateUserRequest {
+            email: email.clone(),
+            username: merged.get("username").cloned().unwrap_or_default(),
+            password: password.clone().unwrap_or_default(),
+            first_name: merged.get("first_name").cloned(),
+            last_name: merged.get("last_name").cloned(),
+        };
+        if errors.is_empty() {
+            if let Err(e) = request.validate() {
+                // A missing password was either reported above or is allowed
+                let skip: &[&str] = if password.is_none() {
+                    &["password"]
+                } else {
+                    &[]
+                };
+                errors.extend(messages(&e, skip));
+            }
+        }
+        if errors.is_empty() {
+            let taken: Option<String> =
+                sqlx::query_scalar("SELECT id FROM users WHERE username = $1 AND email <> $2")
+                    .bind(&request.username)
+                    .bind(&email)
+                    .fetch_optional(&mut **tx)
+                    .await?;
+            if taken.is_some() {
+                errors.push("username is already taken".to_string());
+            }
+        }
+        if !errors.is_empty() {
+            return Ok(failed(email, errors));
+        }
+
+        // A dry run rolls back, so the costly hash would be thrown away
+        let password_hash = match &password {
+            Some(password) if !dry_run => Some(self.auth.hash_password(password)?),
+            Some(_) => Some(String::new()),
+            None => None,
+        };
+        let values = UserValues {
+            id: existing
+                .as_ref()
+                .map(|u| u.id.clone())
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            email: email.clone(),
+            username: request.username,
+            first_name: request.first_name,
+            last_name: request.last_name,
+        };
+        let mut changes = changed(&base, &values.fields(), USER_FIELDS);
+        if password_hash.is_some() {
+            changes.push("password".to_string());
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let action = match &existing {
+            None => {
+                sqlx::query(
+                    "INSERT INTO users (id, email, username, password_hash, first_name, \
+                     last_name, role, status, email_verified, created_at, updated_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, 'user', 'active', 0, $7, $7)",
+                )
+                .bind(&values.id)
+                .bind(&values.email)
+                .bind(&values.username)
+                .bind(password_hash.unwrap_or_default())
+                .bind(&values.first_name)
+                .bind(&values.last_name)
+                .bind(&now)
+                .execute(&mut **tx)
+                .await?;
+                RowAction::Created
+            }
+            Some(_) if changes.is_empty() => RowAction::Unchanged,
+            Some(previous) => {
+                sqlx::query(
+                    "UPDATE users SET username = $1, first_name = $2, last_name = $3, \
+                     password_hash = COALESCE($4, password_hash), updated_at = $5 WHERE id = $6",
+                )
+                .bind(&values.username)
+                .bind(&values.first_name)
+                .bind(&values.last_name)
+                .bind(password_hash)
+                .bind(&now)
+                .bind(&values.id)
+                .execute(&mut **tx)
+                .await?;
+                stale.push(cache_key("user", &[&values.id]));
+                stale.push(cache_key("user:email", &[&values.email]));
+                stale.push(cache_key("user:username", &[&previous.username]));
+                stale.push(cache_key("user:username", &[&values.username]));
+                RowAction::Updated
+            }
+        };
+
+        Ok(ReportRow {
+            row: 0,
+            key: email,
+            action,
+            changes: if action == RowAction::Updated {
+                changes
+            } else {
+                Vec::new()
+            },
+            errors: Vec::new(),
+        })
+    }
+
+    async fn to_response(&self, job: &Job) -> Result<ImportResponse> {
+        let payload: ImportJob = serde_json::from_value(job.payload.clone())?;
+        let status = match job.status {
+            JobStatus::Queued => ImportStatus::Pending,
+            JobStatus::Running => ImportStatus::Processing,
+            JobStatus::Succeeded => ImportStatus::Completed,
+            JobStatus::Dead | JobStatus::Canceled => ImportStatus::Failed,
+        };
+
+        let expires_at = match (job.status, job.finished_at) {
+            (JobStatus::Succeeded, Some(finished_at)) => {
+                Some(finished_at + chrono::Duration::seconds(self.config.url_ttl_seconds as i64))
+            }
+            _ => None,
+        };
+        let live = expires_at.filter(|expires_at| *expires_at > Utc::now());
+        let summary = match live {
+            Some(_) => tokio::fs::read(self.summary_path(payload.import_id))
+                .await
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok()),
+            None => None,
+        };
+
+        Ok(ImportResponse {
+            job_id: job.id.to_string(),
+            status,
+            dry_run: payload.dry_run,
+            summary,
+            report_url: live.map(|expires_at| self.report_url(job.id, expires_at)),
+            expires_at: expires_at.map(|t| t.to_rfc3339()),
+            error: match job.status {
+                JobStatus::Dead | JobStatus::Canceled => job.last_error.clone(),
+                _ => None,
+            },
+        })
+    }
+
+    fn report_url(&self, job_id: Uuid, expires_at: DateTime<Utc>) -> String {
+        let expires = expires_at.timestamp();
+        format!(
+            "/api/v1/imports/{}/report?expires={}&signature={}",
+            job_id,
+            expires,
+            self.sign(job_id, expires)
+        )
+    }
+
+    fn sign(&self, job_id: Uuid, expires: i64) -> String {
+        let message = format!("import.{}.{}", job_id, expires);
+        format!("{:x}", hmac_sha256(&self.signing_key, message.as_bytes()))
+    }
+
+    fn upload_path(&self, job: &ImportJob) -> PathBuf {
+        PathBuf::from(&self.config.directory).join(format!(
+            "{}.{}",
+            job.import_id,
+            job.format.extension()
+        ))
+    }
+
+    fn report_path(&self, import_id: Uuid) -> PathBuf {
+        PathBuf::from(&self.config.directory).join(format!("{}-report.csv", import_id))
+    }
+
+    fn summary_path(&self, import_id: Uuid) -> PathBuf {
+        PathBuf::from(&self.config.directory).join(format!("{}-summary.json", import_id))
+    }
+
+    fn jobs(&self) -> JobService {
+        JobService::new(self.db.clone(), self.cache.clone())
+    }
+
+    async fn import_job(&self, job_id: Uuid) -> Result<Job> {
+        self.jobs()
+            .get_job(job_id)
+            .await?
+            .filter(|job| job.kind == ImportJob::KIND)
+            .ok_or_else(|| AppError::NotFound(format!("Import {} not found", job_id)))
+    }
+}
+
+#[async_trait]
+impl JobHandler<ImportJob> for ImportService {
+    async fn handle(&self, job: ImportJob) -> std::result::Result<(), JobError> {
+        self.run(&job).await.map(|_| ()).map_err(|e| match e {
+            AppError::BadRequest(_) => JobError::Permanent(e.to_string()),
+            other => JobError::Retryable(other.to_string()),
+        })
+    }
+}
+
+fn resource_fields(resource: ImportResource) -> &'static [&'static str] {
+    match resource {
+        ImportResource::Products => PRODUCT_FIELDS,
+        ImportResource::Users => USER_FIELDS,
+    }
+}
+
+/// The column feeding each field. Headers named in `mapping` go to the
+/// field given there; the rest match a field by name, ignoring case,
+/// spaces and dashes, or are ignored.
+fn resolve_columns(
+    headers: &[String],
+    mapping: &HashMap<String, String>,
+    resource: ImportResource,
+) -> Result<Vec<(&'static str, usize)>> {
+    let fields = resource_fields(resource);
+    let normalize = |name: &str| name.trim().to_lowercase().replace([' ', '-'], "_");
+    let mapping: HashMap<String, &str> = mapping
+        .iter()
+        .map(|(header, field)| (normalize(header), field.as_str()))
+        .collect();
+
+    let mut columns: Vec<(&'static str, usize)> = Vec::new();
+    for (column, header) in headers.iter().enumerate() {
+        let header = normalize(header);
+        let target = mapping.get(&header).copied().unwrap_or(&header);
+        let Some(field) = fields.iter().find(|f| **f == target) else {
+            continue;
+        };
+        if columns.iter().any(|(mapped, _)| mapped == field) {
+            return Err(AppError::BadRequest(format!(
+                "More than one column maps to '{}'",
+                field
+            )));
+        }
+        columns.push((field, column));
+    }
+
+    if let Some(header) = mapping
+        .keys()
+        .find(|header| !headers.iter().any(|h| normalize(h) == **header))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Mapped column '{}' is not in the file",
+            header
+        )));
+    }
+    let key = fields[0];
+    if !columns.iter().any(|(field, _)| *field == key) {
+        return Err(AppError::BadRequest(format!(
+            "No column maps to '{}', which identifies each row",
+            key
+        )));
+    }
+    Ok(columns)
+}
+
+fn parsed<T: FromStr>(fields: &Fields, name: &str, errors: &mut Vec<String>) -> Option<T> {
+    let value = fields.get(name)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            errors.push(format!("{} '{}' is not a valid number", name, value));
+            None
+        }
+    }
+}
+
+fn flag(fields: &Fields, name: &str, errors: &mut Vec<String>) -> Option<bool> {
+    let value = fields.get(name)?;
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => {
+            errors.push(format!("{} '{}' is not true or false", name, value));
+            None
+        }
+    }
+}
+
+/// One line per failed rule, skipping the fields in `skip`.
+fn messages(errors: &ValidationErrors, skip: &[&str]) -> Vec<String> {
+    let mut messages: Vec<String> = errors
+        .field_errors()
+        .into_iter()
+        .filter(|(field, _)| !skip.contains(field))
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| match &error.message {
+                Some(message) => format!("{}: {}", field, message),
+                None => format!("{} is invalid ({})", field, error.code),
+            })
+        })
+        .collect();
+    messages.sort();
+    messages
+}
+
+fn changed(before: &Fields, after: &Fields, fields: &[&str]) -> Vec<String> {
+    fields
+        .iter()
+        .filter(|field| before.get(*field) != after.get(*field))
+        .map(|field| field.to_string())
+        .collect()
+}
+
+fn failed(key: String, errors: Vec<String>) -> ReportRow {
+    ReportRow {
+        row: 0,
+        key,
+        action: RowAction::Failed,
+        changes: Vec::new(),
+        errors,
+    }
+}
+
+fn summarize(report: &[ReportRow]) -> ImportSummary {
+    let count = |action: RowAction| report.iter().filter(|row| row.action == action).count();
+    ImportSummary {
+        total_rows: report.len(),
+        created: count(RowAction::Created),
+        updated: count(RowAction::Updated),
+        unchanged: count(RowAction::Unchanged),
+        failed: count(RowAction::Failed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::product_service::ProductService;
+
+    async fn run_import(
+        imports: &ImportService,
+        csv: &str,
+        dry_run: bool,
+    ) -> (ImportSummary, String) {
+        let request = ImportRequest {
+            resource: ImportResource::Products,
+            mapping: Some(HashMap::from([(
+                "Stock".to_string(),
+                "quantity".to_string(),
+            )])),
+            dry_run: Some(dry_run),
+        };
+        let started = imports
+            .start(request, "catalog.csv", csv.as_bytes())
+            .await
+            .unwrap();
+        let lease = imports
+            .jobs()
+            .claim(&[ImportJob::KIND], Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.job.id.to_string(), started.job_id);
+        let payload: ImportJob = serde_json::from_value(lease.job.payload.clone()).unwrap();
+        let summary = imports.run(&payload).await.unwrap();
+        imports.jobs().complete(&lease).await.unwrap();
+        let report = std::fs::read_to_string(imports.report_path(payload.import_id)).unwrap();
+        (summary, report)
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_then_import_upserts_by_sku() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let directory = std::env::temp_dir().join(format!("imports-{}", Uuid::new_v4()));
+        let config = ImportConfig::builder()
+            .directory(directory.to_string_lossy().into_owned())
+            .build();
+        let imports =
+            ImportService::new(db.clone(), cache.clone(), &config, &AuthConfig::default());
+
+        let first = "sku,name,description,price,Stock\n\
+                     MUG-1,Mug,Blue mug,9.5,10\n\
+                     BAD-1,,No name,1,x\n\
+                     NEG-1,Cup,Red cup,-1,1\n";
+        let (summary, report) = run_import(&imports, first, false).await;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                total_rows: 3,
+                created: 1,
+                failed: 2,
+                ..Default::default()
+            }
+        );
+        assert!(report.contains("2,MUG-1,created,,\n"));
+        assert!(report
+            .contains("3,BAD-1,failed,,name is required; quantity 'x' is not a valid number\n"));
+        assert!(report.contains("4,NEG-1,failed,,price is invalid (range)\n"));
+
+        // Only the stock column: the rest of the product is kept
+        let second = "sku,Stock\nMUG-1,4\nMUG-1,4\nNEW-1,1\n";
+        let (summary, report) = run_import(&imports, second, true).await;
+        assert_eq!(
+            (summary.updated, summary.unchanged, summary.failed),
+            (1, 1, 1)
+        );
+        assert!(report.contains("2,MUG-1,updated,quantity,\n"));
+        let products = ProductService::new(db.clone(), cache.clone());
+        let mug = products.get_product_by_sku("MUG-1").await.unwrap().unwrap();
+        assert_eq!(mug.quantity, 10);
+
+        let (summary, _) = run_import(&imports, second, false).await;
+        assert_eq!(summary.updated, 1);
+        let mug = products.get_product_by_sku("MUG-1").await.unwrap().unwrap();
+        assert_eq!((mug.quantity, mug.name.as_str()), (4, "Mug"));
+        assert_eq!(mug.price.amount, Decimal::new(950, 2));
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
@@ -0,0 +1,479 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    cache::CacheManager,
+    database::{parse_timestamp, parse_uuid, Database, Nullable},
+    error::{AppError, Result},
+    jobs::JobPayload,
+    models::PaginationParams,
+};
+
+const JOB_COLUMNS: &str = "id, kind, payload, status, attempts, max_attempts, run_at, lease_id, \
+     locked_until, last_error, created_at, updated_at, finished_at";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When the job is next due
+    pub run_at: DateTime<Utc>,
+    /// While running, when another worker may take it over
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for `run_at`, including retries after a failure
+    Queued,
+    Running,
+    Succeeded,
+    /// Out of attempts, or failed permanently; only an admin retry revives it
+    Dead,
+    Canceled,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Dead => "dead",
+            JobStatus::Canceled => "canceled",
+        }
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "succeeded" => Ok(JobStatus::Succeeded),
+            "dead" => Ok(JobStatus::Dead),
+            "canceled" => Ok(JobStatus::Canceled),
+            _ => Err(format!("Unknown job status: {}", s)),
+        }
+    }
+}
+
+/// `?status=&kind=` on the admin job list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobFilter {
+    pub status: Option<JobStatus>,
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobListResponse {
+    pub jobs: Vec<Job>,
+    pub total: i64,
+    pub page: i32,
+    pub per_page: i32,
+}
+
+/// A job claimed by a worker. Only the holder of `lease_id` may settle it.
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub job: Job,
+    lease_id: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct JobRow {
+    id: String,
+    kind: String,
+    payload: String,
+    status: String,
+    attempts: i64,
+    max_attempts: i64,
+    run_at: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    lease_id: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    locked_until: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    last_error: Option<String>,
+    created_at: String,
+    updated_at: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    finished_at: Option<String>,
+}
+
+/// Stores jobs in the `jobs` table and hands them to workers under a lease.
+pub struct JobService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+}
+
+impl JobService {
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
+        Self { db, cache }
+    }
+
+    pub async fn enqueue<P: JobPayload>(&self, payload: &P) -> Result<Job> {
+        self.enqueue_at(payload, Utc::now()).await
+    }
+
+    pub async fn enqueue_at<P: JobPayload>(
+        &self,
+        payload: &P,
+        run_at: DateTime<Utc>,
+    ) -> Result<Job> {
+        let id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO jobs (id, kind, payload, status, attempts, max_attempts, run_at, \
+             created_at, updated_at) VALUES ($1, $2, $3, $4, 0, $5, $6, $7, $8)",
+        )
+        .bind(id.to_string())
+        .bind(P::KIND)
+        .bind(serde_json::to_string(payload)?)
+        .bind(JobStatus::Queued.as_str())
+        .bind(i64::from(P::MAX_ATTEMPTS))
+        .bind(run_at.to_rfc3339())
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db.pool)
+        .await?;
+
+        tracing::debug!(job_id = %id, kind = P::KIND, "Job enqueued");
+        self.require_job(id).await
+    }
+
+    pub async fn get_job(&self, id: Uuid) -> Result<Option<Job>> {
+        let row: Option<JobRow> =
+            sqlx::query_as(&format!("SELECT {} FROM jobs WHERE id = $1", JOB_COLUMNS))
+                .bind(id.to_string())
+                .fetch_optional(&self.db.pool)
+                .await?;
+
+        row.map(to_job).transpose()
+    }
+
+    pub async fn list_jobs(
+        &self,
+        filter: &JobFilter,
+        pagination: &PaginationParams,
+    ) -> Result<(Vec<Job>, i64)> {
+        let per_page = pagination.per_page.clamp(1, 100);
+        let offset = (pagination.page.max(1) - 1) * per_page;
+
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(status) = filter.status {
+            params.push(status.as_str().to_string());
+            conditions.push(format!("status = ${}", params.len()));
+        }
+        if let Some(kind) = &filter.kind {
+            params.push(kind.clone());
+            conditions.push(format!("kind = ${}", params.len()));
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let sql = format!(
+            "SELECT {} FROM jobs {} ORDER BY created_at DESC LIMIT ${} OFFSET ${}",
+            JOB_COLUMNS,
+            where_clause,
+            params.len() + 1,
+            params.len() + 2
+        );
+        let mut query = sqlx::query_as::<_, JobRow>(&sql);
+        for param in &params {
+            query = query.bind(param);
+        }
+        let rows = query
+            .bind(per_page as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        let count_sql = format!("SELECT COUNT(*) FROM jobs {}", where_clause);
+        let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+        for param in &params {
+            count = count.bind(param);
+        }
+        let total = count.fetch_one(&self.db.pool).await?;
+
+        let jobs = rows.into_iter().map(to_job).collect::<Result<Vec<_>>>()?;
+        Ok((jobs, total))
+    }
+
+    /// Puts a dead or canceled job back in the queue with fresh attempts.
+    pub async fn retry_job(&self, id: Uuid) -> Result<Job> {
+        let now = Utc::now().to_rfc3339();
+        let updated = sqlx::query(
+            "UPDATE jobs SET status = $1, attempts = 0, run_at = $2, lease_id = NULL, \
+             locked_until = NULL, finished_at = NULL, updated_at = $2 \
+             WHERE id = $3 AND status IN ($4, $5)",
+        )
+        .bind(JobStatus::Queued.as_str())
+        .bind(&now)
+        .bind(id.to_string())
+        .bind(JobStatus::Dead.as_str())
+        .bind(JobStatus::Canceled.as_str())
+        .execute(&self.db.pool)
+        .await?;
+
+        self.settled(id, updated.rows_affected(), "retry").await
+    }
+
+    /// Cancels a job that has not started; a running job cannot be stopped.
+    pub async fn cancel_job(&self, id: Uuid) -> Result<Job> {
+        let now = Utc::now().to_rfc3339();
+        let updated = sqlx::query(
+            "UPDATE jobs SET status = $1, finished_at = $2, updated_at = $2 \
+             WHERE id = $3 AND status = $4",
+        )
+        .bind(JobStatus::Canceled.as_str())
+        .bind(&now)
+        .bind(id.to_string())
+        .bind(JobStatus::Queued.as_str())
+        .execute(&self.db.pool)
+        .await?;
+
+        self.settled(id, updated.rows_affected(), "cancel").await
+    }
+
+    /// Claims the next due job of one of `kinds`: a queued job whose time
+    /// has come, or a running one whose lease expired.
+    pub async fn claim(&self, kinds: &[&str], visibility: Duration) -> Result<Option<Lease>> {
+        if kinds.is_empty() {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let due = "((status = $1 AND run_at <= $3) OR (status = $2 AND locked_until <= $3))";
+        let kind_placeholders: Vec<String> =
+            (0..kinds.len()).map(|i| format!("${}", i + 4)).collect();
+        let sql = format!(
+            "SELECT id FROM jobs WHERE {} AND kind IN ({}) ORDER BY run_at LIMIT 10",
+            due,
+            kind_placeholders.join(", ")
+        );
+        let mut query = sqlx::query_scalar::<_, String>(&sql)
+            .bind(JobStatus::Queued.as_str())
+            .bind(JobStatus::Running.as_str())
+            .bind(now.to_rfc3339());
+        for kind in kinds {
+            query = query.bind(*kind);
+        }
+        let candidates = query.fetch_all(&self.db.pool).await?;
+
+        let locked_until =
+            now + chrono::Duration::from_std(visibility).unwrap_or(chrono::Duration::minutes(5));
+        for id in candidates {
+            // Another worker may win the race for the same row
+            let lease_id = Uuid::new_v4().to_string();
+            let claimed = sqlx::query(&format!(
+                "UPDATE jobs SET status = $2, lease_id = $4, locked_until = $5, \
+                 attempts = attempts + 1, updated_at = $3 WHERE id = $6 AND {}",
+                due
+            ))
+            .bind(JobStatus::Queued.as_str())
+            .bind(JobStatus::Running.as_str())
+            .bind(now.to_rfc3339())
+            .bind(&lease_id)
+            .bind(locked_until.to_rfc3339())
+            .bind(&id)
+            .execute(&self.db.pool)
+            .await?;
+
+            if claimed.rows_affected() == 1 {
+                let job = self.require_job(parse_uuid(&id)?).await?;
+                return Ok(Some(Lease { job, lease_id }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn complete(&self, lease: &Lease) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.settle(lease, JobStatus::Succeeded, None, None, Some(&now), &now)
+            .await
+    }
+
+    /// Records a failed attempt: queued again for `retry_at`, or dead when
+    /// `None`.
+    pub async fn fail(
+        &self,
+        lease: &Lease,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        match retry_at {
+            Some(retry_at) => {
+                self.settle(
+                    lease,
+                    JobStatus::Queued,
+                    Some(error),
+                    Some(&retry_at.to_rfc3339()),
+                    None,
+                    &now,
+                )
+                .await
+            }
+            None => {
+                self.settle(lease, JobStatus::Dead, Some(error), None, Some(&now), &now)
+                    .await
+            }
+        }
+    }
+
+    async fn settle(
+        &self,
+        lease: &Lease,
+        status: JobStatus,
+        error: Option<&str>,
+        run_at: Option<&str>,
+        finished_at: Option<&str>,
+        now: &str,
+    ) -> Result<()> {
+        let updated = sqlx::query(
+            "UPDATE jobs SET status = $1, last_error = COALESCE($2, last_error), \
+             run_at = COALESCE($3, run_at), finished_at = $4, lease_id = NULL, \
+             locked_until = NULL, updated_at = $5 WHERE id = $6 AND lease_id = $7",
+        )
+        .bind(status.as_str())
+        .bind(error)
+        .bind(run_at)
+        .bind(finished_at)
+        .bind(now)
+        .bind(lease.job.id.to_string())
+        .bind(&lease.lease_id)
+        .execute(&self.db.pool)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            // The lease expired and another worker took the job over
+            tracing::warn!(job_id = %lease.job.id, "Job lease lost before it was settled");
+        }
+        Ok(())
+    }
+
+    async fn settled(&self, id: Uuid, rows_affected: u64, action: &str) -> Result<Job> {
+        let job = self.require_job(id).await?;
+        if rows_affected == 0 {
+            return Err(AppError::Conflict(format!(
+                "Cannot {} a job that is {}",
+                action,
+                job.status.as_str()
+            )));
+        }
+        Ok(job)
+    }
+
+    async fn require_job(&self, id: Uuid) -> Result<Job> {
+        self.get_job(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+    }
+}
+
+fn to_job(row: JobRow) -> Result<Job> {
+    Ok(Job {
+        id: parse_uuid(&row.id)?,
+        kind: row.kind,
+        payload: serde_json::from_str(&row.payload)?,
+        status: row.status.parse().map_err(AppError::InternalError)?,
+        attempts: row.attempts as i32,
+        max_attempts: row.max_attempts as i32,
+        run_at: parse_timestamp(&row.run_at)?,
+        locked_until: row
+            .locked_until
+            .as_deref()
+            .map(parse_timestamp)
+            .transpose()?,
+        last_error: row.last_error,
+        created_at: parse_timestamp(&row.created_at)?,
+        updated_at: parse_timestamp(&row.updated_at)?,
+        finished_at: row
+            .finished_at
+            .as_deref()
+            .map(parse_timestamp)
+            .transpose()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Ping;
+
+    impl JobPayload for Ping {
+        const KIND: &'static str = "test.ping";
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_is_taken_over_and_stale_holder_ignored() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let jobs = JobService::new(db, Arc::new(CacheManager::new()));
+        let job = jobs.enqueue(&Ping).await.unwrap();
+        let later = jobs
+            .enqueue_at(&Ping, Utc::now() + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+
+        let stale = jobs
+            .claim(&[Ping::KIND], Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stale.job.id, job.id);
+        assert!(jobs.cancel_job(job.id).await.is_err());
+
+        // The lease has already lapsed, so another worker picks the job up
+        let lease = jobs
+            .claim(&[Ping::KIND], Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.job.id, job.id);
+        assert_eq!(lease.job.attempts, 2);
+        assert!(jobs
+            .claim(&[Ping::KIND], Duration::from_secs(60))
+            .await
+            .unwrap()
+            .is_none());
+
+        jobs.fail(&stale, "too late", None).await.unwrap();
+        jobs.complete(&lease).await.unwrap();
+        let done = jobs.get_job(job.id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert_eq!(done.last_error, None);
+
+        jobs.cancel_job(later.id).await.unwrap();
+        let filter = JobFilter {
+            status: Some(JobStatus::Canceled),
+            kind: Some(Ping::KIND.to_string()),
+        };
+        let (canceled, total) = jobs
+            .list_jobs(&filter, &PaginationParams::builder().build())
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(canceled[0].id, later.id);
+    }
+}
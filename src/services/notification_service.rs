@@ -1,7 +1,10 @@
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::jobs::{JobError, JobHandler, JobPayload};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub id: Uuid,
@@ -42,6 +45,17 @@ pub struct PushNotification {
     pub data: Option<serde_json::Value>,
 }
 
+/// A push notification deferred to the job queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushDelivery {
+    pub user_id: Uuid,
+    pub notification: PushNotification,
+}
+
+impl JobPayload for PushDelivery {
+    const KIND: &'static str = "notification.push";
+}
+
 pub struct NotificationService {
     // Would hold connections to push services, WebSocket, etc.
 }
@@ -132,6 +146,18 @@ impl Default for NotificationService {
     }
 }
 
+#[async_trait]
+impl JobHandler<PushDelivery> for NotificationService {
+    async fn handle(&self, delivery: PushDelivery) -> Result<(), JobError> {
+        self.send_push_notification(delivery.user_id, delivery.notification)
+            .await
+            .map_err(|e| match e {
+                NotificationError::NotFound(_) => JobError::Permanent(e.to_string()),
+                other => JobError::Retryable(other.to_string()),
+            })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum NotificationError {
     #[error("Notification not found: {0}")]
@@ -1,33 +1,204 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
 use std::sync::Arc;
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::{
     cache::{cache_key, CacheManager},
-    database::Database,
-    error::Result,
+    config::{ShippingConfig, TaxConfig},
+    database::{parse_decimal, parse_timestamp, parse_uuid, Database, Nullable},
+    error::{AppError, Result},
+    exchange::{RateProvider, StaticRates},
     models::{
-        Address, CreateOrderRequest, FulfillmentStatus, Order, OrderItemResponse, OrderResponse,
-        OrderStatus, PaginationParams, PaymentStatus,
+        Address, AddressRequest, CreateOrderItemRequest, CreateOrderRequest, FulfillmentStatus,
+        OrderEvent, OrderItemResponse, OrderResponse, OrderStatus, PaginationParams, PaymentStatus,
+        UpdateOrderStatusRequest,
     },
+    services::coupon_service::{self, compute_discount, DiscountLine},
+    shipping::{ShippingItem, ShippingRate, ShippingRates},
+    money::Money,
+    tax::{RateTable, TaxCalculator, TaxLine},
 };
 
+const ORDER_COLUMNS: &str = "id, order_number, customer_id, status, payment_status, \
+     fulfillment_status, subtotal, tax_amount, shipping_amount, discount_amount, total, currency, \
+     catalog_currency, exchange_rate, prices_include_tax, billing_address, shipping_address, shipping_method, tracking_number, \
+     coupon_code, placed_at, paid_at, \
+     shipped_at, delivered_at, cancelled_at";
+
 pub struct OrderService {
     db: Arc<Database>,
     cache: Arc<CacheManager>,
+    tax: Arc<dyn TaxCalculator>,
+    shipping: Arc<ShippingRates>,
+    exchange: Arc<dyn RateProvider>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OrderRow {
+    id: String,
+    order_number: String,
+    customer_id: String,
+    status: String,
+    payment_status: String,
+    fulfillment_status: String,
+    subtotal: i64,
+    tax_amount: i64,
+    shipping_amount: i64,
+    discount_amount: i64,
+    total: i64,
+    currency: String,
+    catalog_currency: String,
+    exchange_rate: String,
+    prices_include_tax: i64,
+    billing_address: String,
+    shipping_address: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    shipping_method: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    tracking_number: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    coupon_code: Option<String>,
+    placed_at: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    paid_at: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    shipped_at: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    delivered_at: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    cancelled_at: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OrderEventRow {
+    id: String,
+    order_id: String,
+    field: String,
+    from_state: String,
+    to_state: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    actor_id: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    note: Option<String>,
+    created_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OrderItemRow {
+    id: String,
+    product_id: String,
+    sku: String,
+    name: String,
+    quantity: i64,
+    unit_price: i64,
+    total_price: i64,
+    tax_amount: i64,
+    discount_amount: i64,
+}
+
+/// The product columns order placement needs.
+#[derive(Debug, sqlx::FromRow)]
+struct PricedProduct {
+    id: String,
+    sku: String,
+    name: String,
+    price: i64,
+    #[sqlx(try_from = "Nullable<i64>")]
+    sale_price: Option<i64>,
+    currency: String,
+    status: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    category_id: Option<String>,
+    is_digital: i64,
+    #[sqlx(try_from = "Nullable<f64>")]
+    weight: Option<f64>,
+    #[sqlx(try_from = "Nullable<String>")]
+    dimensions: Option<String>,
+}
+
+impl PricedProduct {
+    /// The catalog price converted at `rate` into `currency`.
+    fn unit_price(&self, currency: &str, rate: Decimal) -> Money {
+        Money::from_minor(self.sale_price.unwrap_or(self.price), &self.currency)
+            .exchange(rate, currency)
+    }
 }
 
 impl OrderService {
     pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
-        Self { db, cache }
+        Self {
+            db,
+            cache,
+            tax: Arc::new(RateTable::new(&TaxConfig::default())),
+            shipping: Arc::new(ShippingRates::new(&ShippingConfig::default())),
+            exchange: Arc::new(StaticRates::default()),
+        }
+    }
+
+    pub fn with_tax_calculator(mut self, tax: Arc<dyn TaxCalculator>) -> Self {
+        self.tax = tax;
+        self
+    }
+
+    pub fn with_shipping_rates(mut self, shipping: Arc<ShippingRates>) -> Self {
+        self.shipping = shipping;
+        self
+    }
+
+    pub fn with_exchange_rates(mut self, exchange: Arc<dyn RateProvider>) -> Self {
+        self.exchange = exchange;
+        self
     }
 
     pub async fn list_orders(&self, pagination: &PaginationParams) -> Result<(Vec<OrderResponse>, i64)> {
-        let _offset = (pagination.page - 1) * pagination.per_page;
-        let _ = &self.db.pool;
+        self.list_page(None, pagination).await
+    }
+
+    /// One page of a customer's orders, newest first, with their total count.
+    pub async fn list_orders_by_customer(
+        &self,
+        customer_id: Uuid,
+        pagination: &PaginationParams,
+    ) -> Result<(Vec<OrderResponse>, i64)> {
+        self.list_page(Some(customer_id), pagination).await
+    }
 
-        let orders: Vec<OrderResponse> = Vec::new();
-        let total: i64 = 0;
+    async fn list_page(
+        &self,
+        customer_id: Option<Uuid>,
+        pagination: &PaginationParams,
+    ) -> Result<(Vec<OrderResponse>, i64)> {
+        let per_page = pagination.per_page.clamp(1, 100);
+        let offset = (pagination.page.max(1) - 1) * per_page;
+        let filter = if customer_id.is_some() {
+            "WHERE customer_id = $3"
+        } else {
+            ""
+        };
+
+        let select = format!(
+            "SELECT {} FROM orders {} ORDER BY placed_at DESC LIMIT $1 OFFSET $2",
+            ORDER_COLUMNS, filter
+        );
+        let count_query = format!("SELECT COUNT(*) FROM orders {}", filter.replace("$3", "$1"));
+        let mut query = sqlx::query_as::<_, OrderRow>(&select)
+            .bind(per_page as i64)
+            .bind(offset as i64);
+        let mut count = sqlx::query_scalar(&count_query);
+        if let Some(customer_id) = customer_id {
+            query = query.bind(customer_id.to_string());
+            count = count.bind(customer_id.to_string());
+        }
+        let rows = query.fetch_all(&self.db.pool).await?;
+        let total: i64 = count.fetch_one(&self.db.pool).await?;
+
+        let mut orders = Vec::with_capacity(rows.len());
+        for row in rows {
+            orders.push(self.load_items(row).await?);
+        }
 
         Ok((orders, total))
     }
@@ -39,60 +210,178 @@ impl OrderService {
             return Ok(Some(order));
         }
 
-        let order: Option<OrderResponse> = None;
-        Ok(order)
+        let row: Option<OrderRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM orders WHERE id = $1",
+            ORDER_COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        let order = match row {
+            Some(row) => self.load_items(row).await?,
+            None => return Ok(None),
+        };
+
+        let _ = self.cache.set_json(cache_key, &order).await;
+        Ok(Some(order))
     }
 
+    /// Prices the items from the catalog and reserves stock. Either the order,
+    /// its items and every stock decrement are committed, or none are.
     pub async fn create_order(&self, request: CreateOrderRequest) -> Result<OrderResponse> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
         let now = Utc::now();
         let order_id = Uuid::new_v4();
         let order_number = generate_order_number();
+        let lines = merge_lines(&request.items)?;
 
-        let billing_address = Address {
-            first_name: request.billing_address.first_name,
-            last_name: request.billing_address.last_name,
-            company: request.billing_address.company,
-            address_line_1: request.billing_address.address_line_1,
-            address_line_2: request.billing_address.address_line_2,
-            city: request.billing_address.city,
-            state: request.billing_address.state,
-            postal_code: request.billing_address.postal_code,
-            country: request.billing_address.country,
-            phone: request.billing_address.phone,
-        };
+        // Rates can come over the network, so they are fetched before the
+        // transaction takes any locks
+        let catalog_currency = self.catalog_currency(&lines).await?;
+        let currency = request
+            .currency
+            .as_deref()
+            .map(str::to_ascii_uppercase)
+            .unwrap_or_else(|| catalog_currency.clone());
+        let exchange_rate = self.exchange.rate(&catalog_currency, &currency).await?;
+        let shipping_exchange = self.exchange.rate(self.shipping.currency(), &currency).await?;
+
+        let mut tx = self.db.pool.begin().await?;
+
+        let mut items = Vec::with_capacity(lines.len());
+        let mut discount_lines = Vec::with_capacity(lines.len());
+        let mut digital = Vec::with_capacity(lines.len());
+        let mut parcel = Vec::with_capacity(lines.len());
+        for (product_id, quantity) in &lines {
+            let product: PricedProduct = sqlx::query_as(
+                "SELECT id, sku, name, price, sale_price, currency, status, category_id, \
+                 is_digital, weight, dimensions FROM products WHERE id = $1",
+            )
+            .bind(product_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+
+            if product.status != "active" {
+                return Err(AppError::BadRequest(format!(
+                    "Product {} is not available",
+                    product.sku
+                )));
+            }
+            if product.currency != catalog_currency {
+                return Err(AppError::Conflict(format!(
+                    "The price of {} changed currency, retry",
+                    product.sku
+                )));
+            }
 
-        let shipping_address = Address {
-            first_name: request.shipping_address.first_name,
-            last_name: request.shipping_address.last_name,
-            company: request.shipping_address.company,
-            address_line_1: request.shipping_address.address_line_1,
-            address_line_2: request.shipping_address.address_line_2,
-            city: request.shipping_address.city,
-            state: request.shipping_address.state,
-            postal_code: request.shipping_address.postal_code,
-            country: request.shipping_address.country,
-            phone: request.shipping_address.phone,
+            // The guard makes the check and the decrement one atomic step
+            let reserved = sqlx::query(
+                "UPDATE products SET quantity = quantity - $1, updated_at = $2 \
+                 WHERE id = $3 AND quantity >= $1",
+            )
+            .bind(*quantity as i64)
+            .bind(now.to_rfc3339())
+            .bind(&product.id)
+            .execute(&mut *tx)
+            .await?;
+
+            if reserved.rows_affected() == 0 {
+                return Err(AppError::Conflict(format!(
+                    "Insufficient stock for {}",
+                    product.sku
+                )));
+            }
+
+            let unit_price = product.unit_price(&currency, exchange_rate);
+            let total_price = unit_price.times(Decimal::from(*quantity)).round();
+            discount_lines.push(DiscountLine {
+                product_id: *product_id,
+                category_id: product.category_id.as_deref().map(parse_uuid).transpose()?,
+                total: total_price.clone(),
+            });
+            digital.push(product.is_digital != 0);
+            parcel.push(ShippingItem::from_columns(
+                product.weight,
+                product.dimensions.as_deref(),
+                *quantity as u32,
+                product.is_digital != 0,
+            )?);
+            items.push(OrderItemResponse {
+                id: Uuid::new_v4(),
+                product_id: *product_id,
+                sku: product.sku,
+                name: product.name,
+                quantity: *quantity,
+                unit_price,
+                total_price,
+                tax_amount: Money::zero(&currency),
+                discount_amount: Money::zero(&currency),
+            });
+        }
+
+        let subtotal = Money::sum(&currency, items.iter().map(|i| &i.total_price))?;
+        let shipping_rate = choose_shipping(
+            self.shipping.quote(
+                &request.shipping_address.country,
+                &parcel,
+                &subtotal,
+                shipping_exchange,
+            ),
+            request.shipping_method.as_deref(),
+            parcel.iter().any(|item| !item.is_digital),
+        )?;
+        let shipping_amount = shipping_rate
+            .as_ref()
+            .map(|rate| rate.amount.clone())
+            .unwrap_or_else(|| Money::zero(&currency));
+
+        let coupon = match &request.coupon_code {
+            Some(code) => {
+                let coupon = coupon_service::load_applicable(&mut tx, code, &subtotal, exchange_rate, now).await?;
+                let discount = compute_discount(&coupon, &discount_lines, &shipping_amount, exchange_rate)?;
+                for (item, amount) in items.iter_mut().zip(&discount.items) {
+                    item.discount_amount = amount.clone();
+                }
+                Some((coupon, discount))
+            }
+            None => None,
         };
+        let discount_amount = coupon
+            .as_ref()
+            .map(|(_, d)| d.total())
+            .unwrap_or_else(|| Money::zero(&currency));
 
-        let items: Vec<OrderItemResponse> = request
-            .items
+        // Tax follows the shipping address and applies to discounted lines
+        let shipping_address = to_address(request.shipping_address);
+        let taxable = items
             .iter()
-            .map(|item| OrderItemResponse {
-                id: Uuid::new_v4(),
-                product_id: item.product_id,
-                sku: "SKU-XXX".to_string(),
-                name: "Product Name".to_string(),
-                quantity: item.quantity,
-                unit_price: 29.99,
-                total_price: 29.99 * item.quantity as f64,
+            .zip(&digital)
+            .map(|(item, is_digital)| {
+                Ok(TaxLine {
+                    amount: item.total_price.checked_sub(&item.discount_amount)?.amount,
+                    is_digital: *is_digital,
+                })
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
+        let tax = self.tax.calculate(&shipping_address, &currency, &taxable);
+        for (item, amount) in items.iter_mut().zip(&tax.lines) {
+            item.tax_amount = Money::new(*amount, &currency);
+        }
 
-        let subtotal: f64 = items.iter().map(|i| i.total_price).sum();
-        let tax_amount = subtotal * 0.1;
-        let shipping_amount = 9.99;
+        let tax_amount = Money::new(tax.total(), &currency);
+        let mut total = subtotal
+            .checked_sub(&discount_amount)?
+            .checked_add(&shipping_amount)?;
+        if !tax.inclusive {
+            total = total.checked_add(&tax_amount)?;
+        }
 
-        let response = OrderResponse {
+        let order = OrderResponse {
             id: order_id,
             order_number,
             customer_id: request.customer_id,
@@ -103,40 +392,466 @@ impl OrderService {
             subtotal,
             tax_amount,
             shipping_amount,
-            discount_amount: 0.0,
-            total: subtotal + tax_amount + shipping_amount,
-            currency: "USD".to_string(),
-            billing_address,
+            discount_amount,
+            total,
+            currency,
+            catalog_currency,
+            exchange_rate,
+            prices_include_tax: tax.inclusive,
+            billing_address: to_address(request.billing_address),
             shipping_address,
+            shipping_method: shipping_rate.map(|rate| rate.id),
             tracking_number: None,
+            coupon_code: coupon.as_ref().map(|(c, _)| c.code.clone()),
             placed_at: now,
+            paid_at: None,
+            shipped_at: None,
+            delivered_at: None,
+            cancelled_at: None,
         };
 
+        sqlx::query(
+            "INSERT INTO orders (id, order_number, customer_id, status, payment_status, \
+             fulfillment_status, subtotal, tax_amount, shipping_amount, discount_amount, total, \
+             currency, catalog_currency, exchange_rate, prices_include_tax, billing_address, \
+             shipping_address, shipping_method, notes, coupon_code, placed_at, created_at, \
+             updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, \
+             $15, $16, $17, $18, $19, $20, $21, $22, $23)",
+        )
+        .bind(order.id.to_string())
+        .bind(&order.order_number)
+        .bind(order.customer_id.to_string())
+        .bind(order.status.as_str())
+        .bind(order.payment_status.as_str())
+        .bind(order.fulfillment_status.as_str())
+        .bind(order.subtotal.to_minor()?)
+        .bind(order.tax_amount.to_minor()?)
+        .bind(order.shipping_amount.to_minor()?)
+        .bind(order.discount_amount.to_minor()?)
+        .bind(order.total.to_minor()?)
+        .bind(&order.currency)
+        .bind(&order.catalog_currency)
+        .bind(order.exchange_rate.to_string())
+        .bind(order.prices_include_tax as i64)
+        .bind(serde_json::to_string(&order.billing_address)?)
+        .bind(serde_json::to_string(&order.shipping_address)?)
+        .bind(&order.shipping_method)
+        .bind(&request.notes)
+        .bind(&order.coupon_code)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await
+        .map_err(map_foreign_key_violation)?;
+
+        for item in &order.items {
+            sqlx::query(
+                "INSERT INTO order_items (id, order_id, product_id, sku, name, quantity, \
+                 unit_price, total_price, tax_amount, discount_amount) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+            )
+            .bind(item.id.to_string())
+            .bind(order.id.to_string())
+            .bind(item.product_id.to_string())
+            .bind(&item.sku)
+            .bind(&item.name)
+            .bind(item.quantity as i64)
+            .bind(item.unit_price.to_minor()?)
+            .bind(item.total_price.to_minor()?)
+            .bind(item.tax_amount.to_minor()?)
+            .bind(item.discount_amount.to_minor()?)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if let Some((coupon, discount)) = &coupon {
+            coupon_service::redeem(
+                &mut tx,
+                coupon,
+                order.customer_id,
+                order.id,
+                &discount.total(),
+                now,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        for (product_id, _) in &lines {
+            self.cache
+                .delete(&cache_key("product", &[&product_id.to_string()]))
+                .await;
+        }
         let cache_key = cache_key("order", &[&order_id.to_string()]);
-        let _ = self.cache.set_json(cache_key, &response).await;
+        let _ = self.cache.set_json(cache_key, &order).await;
 
-        Ok(response)
+        Ok(order)
     }
 
-    pub async fn update_order_status(&self, id: Uuid, status: OrderStatus) -> Result<()> {
-        let _ = &self.db.pool;
-        let _ = id;
-        let _ = status;
+    /// Applies the requested state changes in one transaction. Each must be
+    /// allowed by the status's transition table, otherwise nothing changes and
+    /// a `Conflict` is returned.
+    pub async fn transition(
+        &self,
+        id: Uuid,
+        request: UpdateOrderStatusRequest,
+        actor_id: Option<Uuid>,
+    ) -> Result<OrderResponse> {
+        if request.status.is_none()
+            && request.payment_status.is_none()
+            && request.fulfillment_status.is_none()
+        {
+            return Err(AppError::BadRequest("No status change requested".to_string()));
+        }
 
-        let cache_key = cache_key("order", &[&id.to_string()]);
-        self.cache.delete(&cache_key).await;
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.db.pool.begin().await?;
+        let restocked = apply_transition(&mut tx, id, &request, actor_id, &now).await?;
+
+        tx.commit().await?;
+
+        for (product_id, _) in &restocked {
+            self.cache.delete(&cache_key("product", &[product_id])).await;
+        }
+        self.cache
+            .delete(&cache_key("order", &[&id.to_string()]))
+            .await;
+
+        self.get_order_by_id(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Order {} not found", id)))
+    }
+
+    /// The currency every product in `lines` is priced in.
+    async fn catalog_currency(&self, lines: &[(Uuid, i32)]) -> Result<String> {
+        let mut catalog: Option<String> = None;
+        for (product_id, _) in lines {
+            let currency: String = sqlx::query_scalar("SELECT currency FROM products WHERE id = $1")
+                .bind(product_id.to_string())
+                .fetch_optional(&self.db.pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+            match &catalog {
+                Some(c) if *c != currency => {
+                    return Err(AppError::BadRequest(
+                        "All items in an order must share one currency".to_string(),
+                    ));
+                }
+                Some(_) => {}
+                None => catalog = Some(currency),
+            }
+        }
+        catalog.ok_or_else(|| AppError::ValidationError("An order needs items".to_string()))
+    }
+
+    pub async fn update_order_status(
+        &self,
+        id: Uuid,
+        status: OrderStatus,
+        actor_id: Option<Uuid>,
+    ) -> Result<OrderResponse> {
+        let request = UpdateOrderStatusRequest {
+            status: Some(status),
+            ..Default::default()
+        };
+        self.transition(id, request, actor_id).await
+    }
+
+    pub async fn order_events(&self, id: Uuid) -> Result<Vec<OrderEvent>> {
+        let rows: Vec<OrderEventRow> = sqlx::query_as(
+            "SELECT id, order_id, field, from_state, to_state, actor_id, note, created_at \
+             FROM order_events WHERE order_id = $1 ORDER BY created_at, field",
+        )
+        .bind(id.to_string())
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(OrderEvent {
+                    id: parse_uuid(&row.id)?,
+                    order_id: parse_uuid(&row.order_id)?,
+                    field: row.field,
+                    from_state: row.from_state,
+                    to_state: row.to_state,
+                    actor_id: row.actor_id.as_deref().map(parse_uuid).transpose()?,
+                    note: row.note,
+                    created_at: parse_timestamp(&row.created_at)?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn cancel_order(&self, id: Uuid, actor_id: Option<Uuid>) -> Result<OrderResponse> {
+        self.update_order_status(id, OrderStatus::Cancelled, actor_id)
+            .await
+    }
+
+    async fn load_items(&self, row: OrderRow) -> Result<OrderResponse> {
+        let items: Vec<OrderItemRow> = sqlx::query_as(
+            "SELECT id, product_id, sku, name, quantity, unit_price, total_price, tax_amount, \
+             discount_amount FROM order_items WHERE order_id = $1 ORDER BY sku",
+        )
+        .bind(&row.id)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let items = items
+            .into_iter()
+            .map(|item| {
+                Ok(OrderItemResponse {
+                    id: parse_uuid(&item.id)?,
+                    product_id: parse_uuid(&item.product_id)?,
+                    sku: item.sku,
+                    name: item.name,
+                    quantity: item.quantity as i32,
+                    unit_price: Money::from_minor(item.unit_price, &row.currency),
+                    total_price: Money::from_minor(item.total_price, &row.currency),
+                    tax_amount: Money::from_minor(item.tax_amount, &row.currency),
+                    discount_amount: Money::from_minor(item.discount_amount, &row.currency),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(OrderResponse {
+            id: parse_uuid(&row.id)?,
+            order_number: row.order_number,
+            customer_id: parse_uuid(&row.customer_id)?,
+            status: row.status.parse().map_err(AppError::InternalError)?,
+            payment_status: row.payment_status.parse().map_err(AppError::InternalError)?,
+            fulfillment_status: row
+                .fulfillment_status
+                .parse()
+                .map_err(AppError::InternalError)?,
+            items,
+            subtotal: Money::from_minor(row.subtotal, &row.currency),
+            tax_amount: Money::from_minor(row.tax_amount, &row.currency),
+            shipping_amount: Money::from_minor(row.shipping_amount, &row.currency),
+            discount_amount: Money::from_minor(row.discount_amount, &row.currency),
+            total: Money::from_minor(row.total, &row.currency),
+            currency: row.currency,
+            catalog_currency: row.catalog_currency,
+            exchange_rate: parse_decimal(&row.exchange_rate)?,
+            prices_include_tax: row.prices_include_tax != 0,
+            billing_address: serde_json::from_str(&row.billing_address)?,
+            shipping_address: serde_json::from_str(&row.shipping_address)?,
+            shipping_method: row.shipping_method,
+            tracking_number: row.tracking_number,
+            coupon_code: row.coupon_code,
+            placed_at: parse_timestamp(&row.placed_at)?,
+            paid_at: row.paid_at.as_deref().map(parse_timestamp).transpose()?,
+            shipped_at: row.shipped_at.as_deref().map(parse_timestamp).transpose()?,
+            delivered_at: row.delivered_at.as_deref().map(parse_timestamp).transpose()?,
+            cancelled_at: row.cancelled_at.as_deref().map(parse_timestamp).transpose()?,
+        })
+    }
+}
+
+/// Validates and applies `request` inside `tx`, recording an order event per
+/// change. Returns the `(product_id, quantity)` lines restocked by a
+/// cancellation so the caller can drop their cached products after commit.
+pub(crate) async fn apply_transition(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    id: Uuid,
+    request: &UpdateOrderStatusRequest,
+    actor_id: Option<Uuid>,
+    now: &str,
+) -> Result<Vec<(String, i64)>> {
+    let (status, payment_status, fulfillment_status): (String, String, String) =
+        sqlx::query_as(
+            "SELECT status, payment_status, fulfillment_status FROM orders WHERE id = $1",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", id)))?;
+
+    let current_status: OrderStatus = status.parse().map_err(AppError::InternalError)?;
+    let current_payment: PaymentStatus =
+        payment_status.parse().map_err(AppError::InternalError)?;
+    let current_fulfillment: FulfillmentStatus =
+        fulfillment_status.parse().map_err(AppError::InternalError)?;
+
+    // (field, from, to) for each change, plus the SET clauses to apply
+    let mut changes: Vec<(&str, &str, &str)> = Vec::new();
+    // A set, as status and fulfillment can both stamp `shipped_at` or
+    // `delivered_at` and a column may only be assigned once
+    let mut stamps: BTreeSet<&str> = BTreeSet::new();
+
+    if let Some(next) = &request.status {
+        if !current_status.can_transition_to(next) {
+            return Err(illegal_transition("status", current_status.as_str(), next.as_str()));
+        }
+        // Cancelling would keep the customer's money; it goes back through a
+        // refund first. The guarded update below catches a payment landing
+        // after this read.
+        if *next == OrderStatus::Cancelled && current_payment.holds_funds() {
+            return Err(AppError::Conflict(format!(
+                "Order payment is {}; refund it before cancelling",
+                current_payment.as_str()
+            )));
+        }
+        changes.push(("status", current_status.as_str(), next.as_str()));
+        stamps.extend(match next {
+            OrderStatus::Shipped => Some("shipped_at"),
+            OrderStatus::Delivered => Some("delivered_at"),
+            OrderStatus::Cancelled => Some("cancelled_at"),
+            _ => None,
+        });
+    }
+    if let Some(next) = &request.payment_status {
+        if !current_payment.can_transition_to(next) {
+            return Err(illegal_transition(
+                "payment status",
+                current_payment.as_str(),
+                next.as_str(),
+            ));
+        }
+        changes.push(("payment_status", current_payment.as_str(), next.as_str()));
+        if *next == PaymentStatus::Paid {
+            stamps.insert("paid_at");
+        }
+    }
+    if let Some(next) = &request.fulfillment_status {
+        if !current_fulfillment.can_transition_to(next) {
+            return Err(illegal_transition(
+                "fulfillment status",
+                current_fulfillment.as_str(),
+                next.as_str(),
+            ));
+        }
+        changes.push(("fulfillment_status", current_fulfillment.as_str(), next.as_str()));
+        stamps.extend(match next {
+            FulfillmentStatus::Shipped => Some("shipped_at"),
+            FulfillmentStatus::Delivered => Some("delivered_at"),
+            _ => None,
+        });
+    }
+
+    // Placeholders: $1 = now, $2 = id, $3.. = current states, then new states
+    let mut sets = vec!["updated_at = $1".to_string()];
+    for stamp in &stamps {
+        sets.push(format!("{0} = COALESCE({0}, $1)", stamp));
+    }
+    for (i, (field, _, _)) in changes.iter().enumerate() {
+        sets.push(format!("{} = ${}", field, i + 6));
+    }
+    let sql = format!(
+        "UPDATE orders SET {} WHERE id = $2 AND status = $3 AND payment_status = $4 \
+         AND fulfillment_status = $5",
+        sets.join(", ")
+    );
 
-        Ok(())
+    let mut query = sqlx::query(&sql)
+        .bind(now)
+        .bind(id.to_string())
+        .bind(current_status.as_str())
+        .bind(current_payment.as_str())
+        .bind(current_fulfillment.as_str());
+    for (_, _, to) in &changes {
+        query = query.bind(*to);
+    }
+    if query.execute(&mut **tx).await?.rows_affected() == 0 {
+        return Err(AppError::Conflict(
+            "Order was modified concurrently, retry".to_string(),
+        ));
+    }
+
+    for (field, from, to) in &changes {
+        sqlx::query(
+            "INSERT INTO order_events (id, order_id, field, from_state, to_state, actor_id, \
+             note, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(id.to_string())
+        .bind(*field)
+        .bind(*from)
+        .bind(*to)
+        .bind(actor_id.map(|a| a.to_string()))
+        .bind(&request.note)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
     }
 
-    pub async fn get_orders_by_customer(&self, customer_id: Uuid) -> Result<Vec<OrderResponse>> {
-        let _ = &self.db.pool;
-        let _ = customer_id;
+    // Cancellation is only reachable before shipping, so the stock is still ours
+    if request.status == Some(OrderStatus::Cancelled) {
+        let items: Vec<(String, i64)> =
+            sqlx::query_as("SELECT product_id, quantity FROM order_items WHERE order_id = $1")
+                .bind(id.to_string())
+                .fetch_all(&mut **tx)
+                .await?;
+        for (product_id, quantity) in &items {
+            sqlx::query(
+                "UPDATE products SET quantity = quantity + $1, updated_at = $2 WHERE id = $3",
+            )
+            .bind(*quantity)
+            .bind(now)
+            .bind(product_id)
+            .execute(&mut **tx)
+            .await?;
+        }
+        coupon_service::release(tx, id).await?;
+        Ok(items)
+    } else {
         Ok(Vec::new())
     }
+}
+
+/// Sums quantities of repeated products so each stock check sees the full amount.
+fn merge_lines(items: &[CreateOrderItemRequest]) -> Result<Vec<(Uuid, i32)>> {
+    let mut lines: Vec<(Uuid, i32)> = Vec::with_capacity(items.len());
+    for item in items {
+        match lines.iter_mut().find(|(id, _)| *id == item.product_id) {
+            Some((_, quantity)) => {
+                *quantity = quantity.checked_add(item.quantity).ok_or_else(|| {
+                    AppError::ValidationError(format!(
+                        "Quantity of product {} is too large",
+                        item.product_id
+                    ))
+                })?;
+            }
+            None => lines.push((item.product_id, item.quantity)),
+        }
+    }
+    Ok(lines)
+}
+
+/// The requested method, or the cheapest when none was asked for. `None`
+/// only for carts with nothing to ship.
+fn choose_shipping(
+    options: Vec<ShippingRate>,
+    requested: Option<&str>,
+    needs_shipping: bool,
+) -> Result<Option<ShippingRate>> {
+    if !needs_shipping {
+        return Ok(None);
+    }
+    let chosen = match requested {
+        Some(id) => options.into_iter().find(|rate| rate.id == id).ok_or_else(|| {
+            AppError::BadRequest(format!("Shipping method {} is not available", id))
+        })?,
+        None => options.into_iter().next().ok_or_else(|| {
+            AppError::BadRequest("No shipping method serves this address".to_string())
+        })?,
+    };
+    Ok(Some(chosen))
+}
 
-    pub async fn cancel_order(&self, id: Uuid) -> Result<()> {
-        self.update_order_status(id, OrderStatus::Cancelled).await
+fn to_address(request: AddressRequest) -> Address {
+    Address {
+        first_name: request.first_name,
+        last_name: request.last_name,
+        company: request.company,
+        address_line_1: request.address_line_1,
+        address_line_2: request.address_line_2,
+        city: request.city,
+        state: request.state,
+        postal_code: request.postal_code,
+        country: request.country,
+        phone: request.phone,
     }
 }
 
@@ -148,3 +863,518 @@ fn generate_order_number() -> String {
         &Uuid::new_v4().to_string()[..8].to_uppercase()
     )
 }
+
+
+fn illegal_transition(what: &str, from: &str, to: &str) -> AppError {
+    AppError::Conflict(format!("Cannot change order {} from {} to {}", what, from, to))
+}
+
+fn map_foreign_key_violation(error: sqlx::Error) -> AppError {
+    match &error {
+        sqlx::Error::Database(db_error) if db_error.is_foreign_key_violation() => {
+            AppError::BadRequest("Unknown customer".to_string())
+        }
+        _ => AppError::DatabaseError(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExchangeRateConfig;
+    use crate::models::{CouponKind, CreateCouponRequest};
+    use crate::services::{coupon_service::CouponService, user_service::UserService};
+
+    async fn setup() -> (OrderService, Uuid) {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let customer = UserService::new(db.clone(), cache.clone())
+            .create_user_with_password(
+                "buyer@example.com".to_string(),
+                "buyer".to_string(),
+                "hash".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        (OrderService::new(db, cache), customer.id)
+    }
+
+    fn usd(amount: &str) -> Money {
+        Money::new(amount.parse().unwrap(), "USD")
+    }
+
+    async fn insert_product(
+        service: &OrderService,
+        sku: &str,
+        price: &str,
+        sale_price: Option<&str>,
+        quantity: i64,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO products (id, sku, name, slug, description, price, sale_price, quantity, \
+             created_at, updated_at) VALUES ($1, $2, $3, $4, '', $5, $6, $7, $8, $9)",
+        )
+        .bind(id.to_string())
+        .bind(sku)
+        .bind(format!("Product {}", sku))
+        .bind(sku.to_lowercase())
+        .bind(usd(price).to_minor().unwrap())
+        .bind(sale_price.map(|p| usd(p).to_minor().unwrap()))
+        .bind(quantity)
+        .bind(&now)
+        .bind(&now)
+        .execute(&service.db.pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn stock(service: &OrderService, id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT quantity FROM products WHERE id = $1")
+            .bind(id.to_string())
+            .fetch_one(&service.db.pool)
+            .await
+            .unwrap()
+    }
+
+    fn address() -> AddressRequest {
+        AddressRequest {
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            company: None,
+            address_line_1: "1 Main St".to_string(),
+            address_line_2: None,
+            city: "London".to_string(),
+            state: None,
+            postal_code: "N1 9GU".to_string(),
+            country: "GB".to_string(),
+            phone: None,
+        }
+    }
+
+    fn order(customer_id: Uuid, items: &[(Uuid, i32)]) -> CreateOrderRequest {
+        CreateOrderRequest::builder()
+            .customer_id(customer_id)
+            .items(
+                items
+                    .iter()
+                    .map(|&(product_id, quantity)| CreateOrderItemRequest {
+                        product_id,
+                        variant_id: None,
+                        quantity,
+                    })
+                    .collect(),
+            )
+            .billing_address(address())
+            .shipping_address(address())
+            .shipping_method(None)
+            .notes(None)
+            .coupon_code(None)
+            .currency(None)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_create_order_prices_and_reserves_stock() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", Some("10"), 5).await;
+        let tee = insert_product(&service, "TEE", "20", None, 3).await;
+
+        let created = service
+            .create_order(order(customer, &[(mug, 2), (tee, 1), (mug, 1)]))
+            .await
+            .unwrap();
+
+        assert_eq!(created.items.len(), 2);
+        assert_eq!(created.subtotal, usd("50"));
+        assert_eq!(stock(&service, mug).await, 2);
+        assert_eq!(stock(&service, tee).await, 2);
+
+        let uncached = OrderService::new(service.db.clone(), Arc::new(CacheManager::new()));
+        let loaded = uncached.get_order_by_id(created.id).await.unwrap().unwrap();
+        assert_eq!(loaded.total, created.total);
+        assert_eq!(loaded.items.len(), 2);
+
+        // Customers page through their own orders with a real total
+        service.create_order(order(customer, &[(tee, 1)])).await.unwrap();
+        let first_page = PaginationParams::builder().per_page(1).build();
+        let (orders, total) = uncached
+            .list_orders_by_customer(customer, &first_page)
+            .await
+            .unwrap();
+        assert_eq!((orders.len(), total), (1, 2));
+        let (orders, total) = uncached
+            .list_orders_by_customer(Uuid::new_v4(), &first_page)
+            .await
+            .unwrap();
+        assert_eq!((orders.len(), total), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_stock_rolls_back() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", None, 5).await;
+        let tee = insert_product(&service, "TEE", "20", None, 1).await;
+
+        let result = service
+            .create_order(order(customer, &[(mug, 2), (tee, 2)]))
+            .await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+        assert_eq!(stock(&service, mug).await, 5);
+        let (orders, total) = service
+            .list_orders(&PaginationParams::builder().build())
+            .await
+            .unwrap();
+        assert!(orders.is_empty());
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_quantities_must_be_positive_and_in_range() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", None, 5).await;
+
+        for quantity in [0, -5] {
+            assert!(matches!(
+                service.create_order(order(customer, &[(mug, quantity)])).await,
+                Err(AppError::ValidationError(_))
+            ));
+        }
+        assert!(matches!(
+            service
+                .create_order(order(customer, &[(mug, i32::MAX), (mug, 1)]))
+                .await,
+            Err(AppError::ValidationError(_))
+        ));
+        assert_eq!(stock(&service, mug).await, 5);
+    }
+
+    #[tokio::test]
+    async fn test_transitions_stamp_and_record_events() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", None, 5).await;
+        let order_id = service
+            .create_order(order(customer, &[(mug, 1)]))
+            .await
+            .unwrap()
+            .id;
+
+        let paid = service
+            .transition(
+                order_id,
+                UpdateOrderStatusRequest {
+                    status: Some(OrderStatus::Confirmed),
+                    payment_status: Some(PaymentStatus::Paid),
+                    ..Default::default()
+                },
+                Some(customer),
+            )
+            .await
+            .unwrap();
+        assert_eq!(paid.status, OrderStatus::Confirmed);
+        assert!(paid.paid_at.is_some());
+
+        for status in [OrderStatus::Processing, OrderStatus::Shipped, OrderStatus::Delivered] {
+            service.update_order_status(order_id, status, None).await.unwrap();
+        }
+
+        let result = service.cancel_order(order_id, None).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        let delivered = service.get_order_by_id(order_id).await.unwrap().unwrap();
+        assert!(delivered.shipped_at.is_some() && delivered.delivered_at.is_some());
+        assert!(delivered.cancelled_at.is_none());
+
+        let events = service.order_events(order_id).await.unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].actor_id, Some(customer));
+        assert_eq!(events.last().unwrap().to_state, "delivered");
+    }
+
+    #[tokio::test]
+    async fn test_status_and_fulfillment_ship_together() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", None, 5).await;
+        let order_id = service
+            .create_order(order(customer, &[(mug, 1)]))
+            .await
+            .unwrap()
+            .id;
+        for status in [OrderStatus::Confirmed, OrderStatus::Processing] {
+            service.update_order_status(order_id, status, None).await.unwrap();
+        }
+        let fulfilled = UpdateOrderStatusRequest {
+            fulfillment_status: Some(FulfillmentStatus::Fulfilled),
+            ..Default::default()
+        };
+        service.transition(order_id, fulfilled, None).await.unwrap();
+
+        // Both fields stamp `shipped_at`, which must be set only once
+        let shipped = service
+            .transition(
+                order_id,
+                UpdateOrderStatusRequest {
+                    status: Some(OrderStatus::Shipped),
+                    fulfillment_status: Some(FulfillmentStatus::Shipped),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(shipped.status, OrderStatus::Shipped);
+        assert_eq!(shipped.fulfillment_status, FulfillmentStatus::Shipped);
+        assert!(shipped.shipped_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_restocks() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "12", None, 5).await;
+        let order_id = service
+            .create_order(order(customer, &[(mug, 2)]))
+            .await
+            .unwrap()
+            .id;
+        assert_eq!(stock(&service, mug).await, 3);
+
+        let cancelled = service.cancel_order(order_id, Some(customer)).await.unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert!(cancelled.cancelled_at.is_some());
+        assert_eq!(stock(&service, mug).await, 5);
+
+        // A paid order stays as it is until its payment is refunded
+        let paid = service
+            .create_order(order(customer, &[(mug, 1)]))
+            .await
+            .unwrap()
+            .id;
+        let pay = UpdateOrderStatusRequest {
+            payment_status: Some(PaymentStatus::Paid),
+            ..Default::default()
+        };
+        service.transition(paid, pay, None).await.unwrap();
+        assert!(matches!(
+            service.cancel_order(paid, Some(customer)).await,
+            Err(AppError::Conflict(_))
+        ));
+        let kept = service.get_order_by_id(paid).await.unwrap().unwrap();
+        assert_eq!(kept.status, OrderStatus::Pending);
+        assert_eq!(stock(&service, mug).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_coupon_discounts_and_caps() {
+        let (service, customer) = setup().await;
+        let mug = insert_product(&service, "MUG", "30", None, 10).await;
+        let tee = insert_product(&service, "TEE", "10", None, 10).await;
+
+        let coupons = CouponService::new(service.db.clone(), service.cache.clone());
+        coupons
+            .create_coupon(CreateCouponRequest {
+                code: "mugs10".to_string(),
+                kind: CouponKind::FixedAmount,
+                value: Decimal::from(10),
+                currency: None,
+                min_order_value: Some(Decimal::from(20)),
+                max_uses: None,
+                max_uses_per_user: Some(1),
+                starts_at: None,
+                ends_at: None,
+                product_ids: vec![mug],
+                category_ids: Vec::new(),
+                is_active: None,
+            })
+            .await
+            .unwrap();
+
+        let mut request = order(customer, &[(mug, 1), (tee, 1)]);
+        request.coupon_code = Some("MUGS10".to_string());
+        let placed = service.create_order(request.clone()).await.unwrap();
+
+        assert_eq!(placed.coupon_code.as_deref(), Some("MUGS10"));
+        assert_eq!(placed.discount_amount, usd("10"));
+        let discounts: Vec<Money> = placed.items.iter().map(|i| i.discount_amount.clone()).collect();
+        assert_eq!(discounts, vec![usd("10"), usd("0")]);
+        assert_eq!(placed.tax_amount, usd("3"));
+        assert_eq!(placed.total, usd("42.99"));
+
+        // Per-user cap, then released again by cancelling
+        assert!(matches!(
+            service.create_order(request.clone()).await,
+            Err(AppError::Conflict(_))
+        ));
+        assert_eq!(stock(&service, mug).await, 9);
+        service.cancel_order(placed.id, None).await.unwrap();
+        assert!(service.create_order(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tax_follows_shipping_address() {
+        let (service, customer) = setup().await;
+        let tee = insert_product(&service, "TEE", "19.99", None, 5).await;
+        let ebook = insert_product(&service, "EBOOK", "5", None, 5).await;
+        sqlx::query("UPDATE products SET is_digital = 1 WHERE id = $1")
+            .bind(ebook.to_string())
+            .execute(&service.db.pool)
+            .await
+            .unwrap();
+
+        let tax = TaxConfig::builder()
+            .jurisdictions(vec![crate::config::TaxJurisdiction {
+                country: "GB".to_string(),
+                state: None,
+                postal_prefix: None,
+                rate: Decimal::new(20, 2),
+            }])
+            .build();
+        let exclusive = OrderService::new(service.db.clone(), service.cache.clone())
+            .with_tax_calculator(Arc::new(RateTable::new(&tax)));
+
+        let placed = exclusive
+            .create_order(order(customer, &[(tee, 1), (ebook, 1)]))
+            .await
+            .unwrap();
+        let taxes: Vec<Money> = placed.items.iter().map(|i| i.tax_amount.clone()).collect();
+        assert_eq!(taxes, vec![usd("4"), usd("0")]);
+        assert_eq!(placed.tax_amount, usd("4"));
+        assert_eq!(placed.total, usd("38.98"));
+
+        let loaded = OrderService::new(service.db.clone(), Arc::new(CacheManager::new()))
+            .get_order_by_id(placed.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.items[1].tax_amount, usd("4"));
+
+        let inclusive = OrderService::new(service.db.clone(), service.cache.clone())
+            .with_tax_calculator(Arc::new(RateTable::new(&TaxConfig {
+                prices_include_tax: true,
+                ..tax
+            })));
+        let placed = inclusive.create_order(order(customer, &[(tee, 1)])).await.unwrap();
+        assert!(placed.prices_include_tax);
+        assert_eq!(placed.tax_amount, usd("3.33"));
+        assert_eq!(placed.total, usd("29.98"));
+    }
+
+    #[tokio::test]
+    async fn test_order_in_another_currency_records_rate() {
+        let (service, customer) = setup().await;
+        let tee = insert_product(&service, "TEE", "19.99", None, 5).await;
+        let service = OrderService::new(service.db.clone(), service.cache.clone())
+            .with_tax_calculator(Arc::new(RateTable::new(
+                &TaxConfig::builder().default_rate(Decimal::ZERO).build(),
+            )))
+            .with_exchange_rates(Arc::new(StaticRates::new(&[ExchangeRateConfig {
+                base: "USD".to_string(),
+                target: "EUR".to_string(),
+                rate: Decimal::new(8, 1),
+            }])));
+
+        let mut request = order(customer, &[(tee, 2)]);
+        request.currency = Some("eur".to_string());
+        let placed = service.create_order(request).await.unwrap();
+
+        let eur = |amount: &str| Money::new(amount.parse().unwrap(), "EUR");
+        assert_eq!(placed.currency, "EUR");
+        assert_eq!(placed.catalog_currency, "USD");
+        assert_eq!(placed.exchange_rate, Decimal::new(8, 1));
+        assert_eq!(placed.items[0].unit_price, eur("15.99"));
+        assert_eq!(placed.subtotal, eur("31.98"));
+        // Shipping's 9.99 USD flat rate is converted as well
+        assert_eq!(placed.shipping_amount, eur("7.99"));
+        assert_eq!(placed.total, eur("39.97"));
+
+        let loaded = OrderService::new(service.db.clone(), Arc::new(CacheManager::new()))
+            .get_order_by_id(placed.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.exchange_rate, Decimal::new(8, 1));
+        assert_eq!(loaded.total, placed.total);
+
+        let mut request = order(customer, &[(tee, 1)]);
+        request.currency = Some("GBP".to_string());
+        assert!(matches!(
+            service.create_order(request).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&service, tee).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_coupon_amounts_are_converted_from_catalog_currency() {
+        let (service, customer) = setup().await;
+        let tee = insert_product(&service, "TEE", "21.25", None, 5).await;
+        let service = OrderService::new(service.db.clone(), service.cache.clone())
+            .with_tax_calculator(Arc::new(RateTable::new(
+                &TaxConfig::builder().default_rate(Decimal::ZERO).build(),
+            )))
+            .with_exchange_rates(Arc::new(StaticRates::new(&[ExchangeRateConfig {
+                base: "USD".to_string(),
+                target: "EUR".to_string(),
+                rate: Decimal::new(8, 1),
+            }])));
+        let coupons = CouponService::new(service.db.clone(), service.cache.clone());
+        for (code, currency) in [("TEN", None), ("TENEUR", Some("EUR".to_string()))] {
+            coupons
+                .create_coupon(CreateCouponRequest {
+                    code: code.to_string(),
+                    kind: CouponKind::FixedAmount,
+                    value: Decimal::from(10),
+                    currency,
+                    min_order_value: Some(Decimal::from(20)),
+                    max_uses: None,
+                    max_uses_per_user: None,
+                    starts_at: None,
+                    ends_at: None,
+                    product_ids: Vec::new(),
+                    category_ids: Vec::new(),
+                    is_active: None,
+                })
+                .await
+                .unwrap();
+        }
+        let eur = |amount: &str| Money::new(amount.parse().unwrap(), "EUR");
+
+        // 10 USD off with a 20 USD minimum is 8 EUR off from 16 EUR
+        let mut request = order(customer, &[(tee, 1)]);
+        request.currency = Some("EUR".to_string());
+        request.coupon_code = Some("TEN".to_string());
+        let placed = service.create_order(request.clone()).await.unwrap();
+        assert_eq!(placed.subtotal, eur("17.00"));
+        assert_eq!(placed.discount_amount, eur("8.00"));
+
+        // A coupon priced in EUR is taken as is
+        request.coupon_code = Some("TENEUR".to_string());
+        assert!(matches!(
+            service.create_order(request).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&service, tee).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_shipping_method_is_chosen_from_quote() {
+        let (service, customer) = setup().await;
+        let tee = insert_product(&service, "TEE", "20", None, 5).await;
+
+        let placed = service.create_order(order(customer, &[(tee, 1)])).await.unwrap();
+        assert_eq!(placed.shipping_method.as_deref(), Some("standard:ground"));
+        assert_eq!(placed.shipping_amount, usd("9.99"));
+
+        let mut request = order(customer, &[(tee, 1)]);
+        request.shipping_method = Some("standard:overnight".to_string());
+        assert!(matches!(
+            service.create_order(request).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(stock(&service, tee).await, 4);
+    }
+}
@@ -1,22 +1,44 @@
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
 use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    cache::{cache_key, CacheManager},
+    database::{parse_timestamp, parse_uuid, Database, Nullable},
+    error::{AppError, Result},
+    models::{OrderStatus, PaymentStatus, UpdateOrderStatusRequest},
+    money::Money,
+    payment_gateway::{GatewayOutcome, MockGateway, PaymentGateway},
+    services::order_service::apply_transition,
+};
+
+const INTENT_COLUMNS: &str = "id, order_id, gateway, gateway_reference, amount, currency, status, \
+     payment_method, client_secret, next_action_url, last_error, created_at, updated_at";
+
+const REFUND_COLUMNS: &str =
+    "id, payment_intent_id, amount, currency, status, reason, restock, created_at";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaymentIntent {
     pub id: Uuid,
     pub order_id: Uuid,
-    pub amount: f64,
-    pub currency: String,
+    pub amount: Money,
     pub status: PaymentIntentStatus,
     pub payment_method: Option<PaymentMethod>,
     pub client_secret: String,
-    pub metadata: Option<serde_json::Value>,
+    /// Where the customer authenticates while `RequiresAction`
+    pub next_action_url: Option<String>,
+    /// Decline code of the last failed attempt
+    pub last_error: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PaymentIntentStatus {
     RequiresPaymentMethod,
@@ -28,6 +50,65 @@ pub enum PaymentIntentStatus {
     Failed,
 }
 
+impl PaymentIntentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentIntentStatus::RequiresPaymentMethod => "requires_payment_method",
+            PaymentIntentStatus::RequiresConfirmation => "requires_confirmation",
+            PaymentIntentStatus::RequiresAction => "requires_action",
+            PaymentIntentStatus::Processing => "processing",
+            PaymentIntentStatus::Succeeded => "succeeded",
+            PaymentIntentStatus::Canceled => "canceled",
+            PaymentIntentStatus::Failed => "failed",
+        }
+    }
+
+    /// A failed attempt may be retried with another method.
+    pub fn is_confirmable(&self) -> bool {
+        matches!(
+            self,
+            PaymentIntentStatus::RequiresPaymentMethod
+                | PaymentIntentStatus::RequiresConfirmation
+                | PaymentIntentStatus::Failed
+        )
+    }
+
+    /// Still waiting on the customer or the gateway.
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            self,
+            PaymentIntentStatus::Succeeded | PaymentIntentStatus::Canceled
+        )
+    }
+
+    /// What the owning order's payment status becomes, if anything.
+    pub fn order_payment_status(&self) -> Option<PaymentStatus> {
+        match self {
+            PaymentIntentStatus::Succeeded => Some(PaymentStatus::Paid),
+            PaymentIntentStatus::Failed => Some(PaymentStatus::Failed),
+            PaymentIntentStatus::Canceled => Some(PaymentStatus::Voided),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for PaymentIntentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "requires_payment_method" => Ok(PaymentIntentStatus::RequiresPaymentMethod),
+            "requires_confirmation" => Ok(PaymentIntentStatus::RequiresConfirmation),
+            "requires_action" => Ok(PaymentIntentStatus::RequiresAction),
+            "processing" => Ok(PaymentIntentStatus::Processing),
+            "succeeded" => Ok(PaymentIntentStatus::Succeeded),
+            "canceled" => Ok(PaymentIntentStatus::Canceled),
+            "failed" => Ok(PaymentIntentStatus::Failed),
+            _ => Err(format!("Unknown payment intent status: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaymentMethod {
     pub id: Uuid,
@@ -36,7 +117,7 @@ pub struct PaymentMethod {
     pub billing_details: Option<BillingDetails>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PaymentMethodType {
     Card,
@@ -73,18 +154,79 @@ pub struct BillingAddress {
     pub country: Option<String>,
 }
 
+/// Confirms an intent. Card numbers go to the gateway and are never stored.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct PaymentMethodRequest {
+    pub payment_type: PaymentMethodType,
+    #[validate]
+    pub card: Option<CardInput>,
+    pub billing_details: Option<BillingDetails>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Validate)]
+pub struct CardInput {
+    #[validate(length(min = 12, max = 23))]
+    pub number: String,
+    #[validate(range(min = 1, max = 12))]
+    pub exp_month: u8,
+    pub exp_year: u16,
+}
+
+impl fmt::Debug for CardInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last4 = self.number.len().saturating_sub(4);
+        f.debug_struct("CardInput")
+            .field("number", &format!("****{}", self.number.get(last4..).unwrap_or("")))
+            .field("exp_month", &self.exp_month)
+            .field("exp_year", &self.exp_year)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct CreateRefundRequest {
+    /// In the payment's currency. Defaults to the value of `items`, which it
+    /// must equal when both are given, or to everything not yet refunded
+    #[validate(custom = "crate::money::validate_non_negative")]
+    pub amount: Option<Decimal>,
+    #[serde(default)]
+    #[validate]
+    pub items: Vec<RefundItemRequest>,
+    /// Puts the refunded quantities back in stock once the refund succeeds
+    #[serde(default)]
+    pub restock: bool,
+    pub reason: Option<RefundReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct RefundItemRequest {
+    pub order_item_id: Uuid,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Refund {
     pub id: Uuid,
     pub payment_intent_id: Uuid,
-    pub amount: f64,
-    pub currency: String,
+    pub amount: Money,
     pub status: RefundStatus,
     pub reason: Option<RefundReason>,
+    pub items: Vec<RefundItem>,
+    pub restock: bool,
     pub created_at: DateTime<Utc>,
 }
 
+/// The part of an order line a refund covers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundItem {
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+    /// What the customer paid for these units, discount and tax included
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RefundStatus {
     Pending,
@@ -93,7 +235,32 @@ pub enum RefundStatus {
     Canceled,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl RefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "pending",
+            RefundStatus::Succeeded => "succeeded",
+            RefundStatus::Failed => "failed",
+            RefundStatus::Canceled => "canceled",
+        }
+    }
+}
+
+impl std::str::FromStr for RefundStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(RefundStatus::Pending),
+            "succeeded" => Ok(RefundStatus::Succeeded),
+            "failed" => Ok(RefundStatus::Failed),
+            "canceled" => Ok(RefundStatus::Canceled),
+            _ => Err(format!("Unknown refund status: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RefundReason {
     Duplicate,
@@ -102,101 +269,880 @@ pub enum RefundReason {
     Other,
 }
 
+impl RefundReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefundReason::Duplicate => "duplicate",
+            RefundReason::Fraudulent => "fraudulent",
+            RefundReason::RequestedByCustomer => "requested_by_customer",
+            RefundReason::Other => "other",
+        }
+    }
+}
+
+impl std::str::FromStr for RefundReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "duplicate" => Ok(RefundReason::Duplicate),
+            "fraudulent" => Ok(RefundReason::Fraudulent),
+            "requested_by_customer" => Ok(RefundReason::RequestedByCustomer),
+            "other" => Ok(RefundReason::Other),
+            _ => Err(format!("Unknown refund reason: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct IntentRow {
+    id: String,
+    order_id: String,
+    gateway: String,
+    gateway_reference: String,
+    amount: i64,
+    currency: String,
+    status: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    payment_method: Option<String>,
+    client_secret: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    next_action_url: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    last_error: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RefundRow {
+    id: String,
+    payment_intent_id: String,
+    amount: i64,
+    currency: String,
+    status: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    reason: Option<String>,
+    restock: i64,
+    created_at: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RefundItemRow {
+    refund_id: String,
+    order_item_id: String,
+    quantity: i64,
+    amount: i64,
+}
+
+/// An order line with what was paid for it, in the order's minor units.
+#[derive(Debug, sqlx::FromRow)]
+struct RefundableLine {
+    quantity: i64,
+    total_price: i64,
+    tax_amount: i64,
+    discount_amount: i64,
+    prices_include_tax: i64,
+}
+
+/// The order columns a payment needs.
+#[derive(Debug, sqlx::FromRow)]
+struct PayableOrder {
+    status: String,
+    payment_status: String,
+    total: i64,
+    currency: String,
+}
+
+/// Persists payment intents and refunds and drives them through a
+/// `PaymentGateway`, keeping the owning order's `PaymentStatus` in step.
 pub struct PaymentService {
-    api_key: String,
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+    gateway: Arc<dyn PaymentGateway>,
 }
 
 impl PaymentService {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
+        Self {
+            db,
+            cache,
+            gateway: Arc::new(MockGateway::new()),
+        }
     }
 
-    pub async fn create_payment_intent(
-        &self,
-        order_id: Uuid,
-        amount: f64,
-        currency: &str,
-    ) -> Result<PaymentIntent, PaymentError> {
-        let intent = PaymentIntent {
-            id: Uuid::new_v4(),
-            order_id,
-            amount,
-            currency: currency.to_string(),
-            status: PaymentIntentStatus::RequiresPaymentMethod,
-            payment_method: None,
-            client_secret: format!("pi_{}_secret_{}", Uuid::new_v4(), Uuid::new_v4()),
-            metadata: None,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-        };
+    pub fn with_gateway(mut self, gateway: Arc<dyn PaymentGateway>) -> Self {
+        self.gateway = gateway;
+        self
+    }
+
+    /// Starts paying the order's total. An intent that is still open is
+    /// returned instead of creating a second one.
+    pub async fn create_payment_intent(&self, order_id: Uuid) -> Result<PaymentIntent> {
+        let mut tx = self.db.pool.begin().await?;
+        let order: PayableOrder = sqlx::query_as(
+            "SELECT status, payment_status, total, currency FROM orders WHERE id = $1",
+        )
+        .bind(order_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", order_id)))?;
+
+        if order.status == OrderStatus::Cancelled.as_str() {
+            return Err(AppError::Conflict("Order is cancelled".to_string()));
+        }
+        let payment_status: PaymentStatus =
+            order.payment_status.parse().map_err(AppError::InternalError)?;
+        if !matches!(payment_status, PaymentStatus::Pending | PaymentStatus::Failed) {
+            return Err(AppError::Conflict(format!(
+                "Order payment is already {}",
+                payment_status.as_str()
+            )));
+        }
+
+        if let Some(open) = open_intent(&mut tx, order_id).await? {
+            return Ok(open);
+        }
+
+        let amount = Money::from_minor(order.total, &order.currency);
+        let created = self.gateway.create_intent(&amount).await?;
+
+        let id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        // The open-intent index turns a concurrent call that got here first
+        // into a conflict, which is skipped rather than failing
+        let inserted = sqlx::query(
+            "INSERT INTO payment_intents (id, order_id, gateway, gateway_reference, amount, \
+             currency, status, client_secret, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT DO NOTHING",
+        )
+        .bind(id.to_string())
+        .bind(order_id.to_string())
+        .bind(self.gateway.name())
+        .bind(&created.reference)
+        .bind(amount.to_minor()?)
+        .bind(&amount.currency)
+        .bind(PaymentIntentStatus::RequiresPaymentMethod.as_str())
+        .bind(&created.client_secret)
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            tx.rollback().await?;
+            if let Err(e) = self.gateway.cancel(&created.reference).await {
+                tracing::warn!(
+                    error = %e,
+                    reference = %created.reference,
+                    "Failed to cancel duplicate gateway intent"
+                );
+            }
+            return self
+                .payment_intents_for_order(order_id)
+                .await?
+                .into_iter()
+                .find(|intent| intent.status.is_open())
+                .ok_or_else(|| {
+                    AppError::Conflict("Another payment attempt is in progress".to_string())
+                });
+        }
+
+        // A fresh attempt after a failed one puts the order back to pending
+        sync_order_payment(&mut tx, order_id, PaymentStatus::Pending, id, &now).await?;
+        tx.commit().await?;
+        self.forget_order(order_id).await;
 
         tracing::info!(
-            payment_intent_id = %intent.id,
+            payment_intent_id = %id,
             order_id = %order_id,
             amount = %amount,
             "Payment intent created"
         );
 
-        Ok(intent)
+        self.require_intent(id).await
     }
 
     pub async fn confirm_payment(
         &self,
         payment_intent_id: Uuid,
-        payment_method: PaymentMethod,
-    ) -> Result<PaymentIntent, PaymentError> {
-        let intent = PaymentIntent {
-            id: payment_intent_id,
-            order_id: Uuid::new_v4(),
-            amount: 0.0,
-            currency: "USD".to_string(),
-            status: PaymentIntentStatus::Succeeded,
-            payment_method: Some(payment_method),
-            client_secret: String::new(),
-            metadata: None,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-        };
+        payment_method: PaymentMethodRequest,
+    ) -> Result<PaymentIntent> {
+        payment_method
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+        let row = self.intent_row(payment_intent_id).await?;
+        let status = parse_intent_status(&row.status)?;
+        if !status.is_confirmable() {
+            return Err(AppError::Conflict(format!(
+                "Cannot confirm a payment that is {}",
+                status.as_str()
+            )));
+        }
+
+        let outcome = self
+            .gateway
+            .confirm(&row.gateway_reference, &payment_method)
+            .await?;
+        self.record_outcome(row, outcome).await
+    }
+
+    /// Continues a payment after the customer completed authentication.
+    pub async fn complete_action(&self, payment_intent_id: Uuid) -> Result<PaymentIntent> {
+        let row = self.intent_row(payment_intent_id).await?;
+        if parse_intent_status(&row.status)? != PaymentIntentStatus::RequiresAction {
+            return Err(AppError::Conflict(format!(
+                "No action pending on a payment that is {}",
+                row.status
+            )));
+        }
+
+        let outcome = self.gateway.complete_action(&row.gateway_reference).await?;
+        self.record_outcome(row, outcome).await
+    }
+
+    pub async fn cancel_payment_intent(&self, payment_intent_id: Uuid) -> Result<PaymentIntent> {
+        let row = self.intent_row(payment_intent_id).await?;
+        if !parse_intent_status(&row.status)?.is_open() {
+            return Err(AppError::Conflict(format!(
+                "Cannot cancel a payment that is {}",
+                row.status
+            )));
+        }
 
-        Ok(intent)
+        let outcome = self.gateway.cancel(&row.gateway_reference).await?;
+        self.record_outcome(row, outcome).await
     }
 
+    /// Refunds part or all of a captured payment. Pending and settled
+    /// refunds together never exceed the captured amount, nor an order
+    /// line's quantity.
     pub async fn create_refund(
         &self,
         payment_intent_id: Uuid,
-        amount: Option<f64>,
-        reason: Option<RefundReason>,
-    ) -> Result<Refund, PaymentError> {
-        let refund = Refund {
-            id: Uuid::new_v4(),
-            payment_intent_id,
-            amount: amount.unwrap_or(0.0),
-            currency: "USD".to_string(),
-            status: RefundStatus::Pending,
-            reason,
-            created_at: Utc::now(),
+        request: CreateRefundRequest,
+    ) -> Result<Refund> {
+        let intent = self.require_intent(payment_intent_id).await?;
+        if intent.status != PaymentIntentStatus::Succeeded {
+            return Err(AppError::Conflict(format!(
+                "Cannot refund a payment that is {}",
+                intent.status.as_str()
+            )));
+        }
+        if request.restock && request.items.is_empty() {
+            return Err(AppError::BadRequest(
+                "Restocking needs the refunded items".to_string(),
+            ));
+        }
+
+        let row = self.intent_row(payment_intent_id).await?;
+        let currency = intent.amount.currency.clone();
+        let id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+
+        // The refund is reserved as pending while the intent's row is locked,
+        // so concurrent refunds see each other's amounts and quantities
+        let mut tx = self.db.pool.begin().await?;
+        let locked = sqlx::query(
+            "UPDATE payment_intents SET status = status WHERE id = $1 AND status = $2",
+        )
+        .bind(payment_intent_id.to_string())
+        .bind(PaymentIntentStatus::Succeeded.as_str())
+        .execute(&mut *tx)
+        .await?;
+        if locked.rows_affected() == 0 {
+            return Err(AppError::Conflict(
+                "The payment changed while refunding".to_string(),
+            ));
+        }
+
+        let items = refund_lines(&mut tx, intent.order_id, &request.items, &currency).await?;
+        let refundable = intent
+            .amount
+            .checked_sub(&committed_refunds(&mut tx, &intent).await?)?;
+        let amount = match request.amount {
+            Some(amount) => Money::new(amount, &currency).round(),
+            None if !items.is_empty() => Money::sum(&currency, items.iter().map(|i| &i.amount))?,
+            None => refundable.clone(),
+        };
+        if !items.is_empty() {
+            let lines = Money::sum(&currency, items.iter().map(|i| &i.amount))?;
+            if amount.amount != lines.amount {
+                return Err(AppError::BadRequest(format!(
+                    "Refund amount {} does not match the {} the items come to",
+                    amount, lines
+                )));
+            }
+        }
+        if amount.is_zero() || amount.is_negative() {
+            return Err(AppError::BadRequest("Refund amount must be positive".to_string()));
+        }
+        if amount.amount > refundable.amount {
+            return Err(AppError::BadRequest(format!(
+                "Refund of {} exceeds the {} still refundable",
+                amount, refundable
+            )));
+        }
+
+        sqlx::query(
+            "INSERT INTO refunds (id, payment_intent_id, gateway_reference, amount, currency, \
+             status, reason, restock, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(id.to_string())
+        .bind(payment_intent_id.to_string())
+        .bind("")
+        .bind(amount.to_minor()?)
+        .bind(&amount.currency)
+        .bind(RefundStatus::Pending.as_str())
+        .bind(request.reason.as_ref().map(RefundReason::as_str))
+        .bind(request.restock as i64)
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        for item in &items {
+            sqlx::query(
+                "INSERT INTO refund_items (refund_id, order_item_id, quantity, amount) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(id.to_string())
+            .bind(item.order_item_id.to_string())
+            .bind(item.quantity as i64)
+            .bind(item.amount.to_minor()?)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        let refunded = match self.gateway.refund(&row.gateway_reference, &amount).await {
+            Ok(refunded) => refunded,
+            Err(e) => {
+                // Release the reservation; nothing was refunded
+                sqlx::query("UPDATE refunds SET status = $1, updated_at = $2 WHERE id = $3")
+                    .bind(RefundStatus::Failed.as_str())
+                    .bind(Utc::now().to_rfc3339())
+                    .bind(id.to_string())
+                    .execute(&self.db.pool)
+                    .await?;
+                return Err(e.into());
+            }
         };
 
+        let mut tx = self.db.pool.begin().await?;
+        sqlx::query(
+            "UPDATE refunds SET gateway_reference = $1, status = $2, updated_at = $3 WHERE id = $4",
+        )
+        .bind(&refunded.reference)
+        .bind(refunded.status.as_str())
+        .bind(Utc::now().to_rfc3339())
+        .bind(id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        let restocked = if refunded.status == RefundStatus::Succeeded {
+            settle_refund(&mut tx, id, payment_intent_id, &now).await?
+        } else {
+            Vec::new()
+        };
+        tx.commit().await?;
+        self.forget_order(intent.order_id).await;
+        for product_id in &restocked {
+            self.cache.delete(&cache_key("product", &[product_id])).await;
+        }
+
         tracing::info!(
-            refund_id = %refund.id,
+            refund_id = %id,
             payment_intent_id = %payment_intent_id,
+            amount = %amount,
+            items = items.len(),
             "Refund created"
         );
 
-        Ok(refund)
+        Ok(Refund {
+            id,
+            payment_intent_id,
+            amount,
+            status: refunded.status,
+            reason: request.reason,
+            items,
+            restock: request.restock,
+            created_at: parse_timestamp(&now)?,
+        })
+    }
+
+    pub async fn get_payment_intent(&self, payment_intent_id: Uuid) -> Result<Option<PaymentIntent>> {
+        let row: Option<IntentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM payment_intents WHERE id = $1",
+            INTENT_COLUMNS
+        ))
+        .bind(payment_intent_id.to_string())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        row.map(to_intent).transpose()
     }
 
-    pub async fn get_payment_intent(
-        &self,
-        payment_intent_id: Uuid,
-    ) -> Result<Option<PaymentIntent>, PaymentError> {
-        let _ = payment_intent_id;
-        Ok(None)
+    pub async fn payment_intents_for_order(&self, order_id: Uuid) -> Result<Vec<PaymentIntent>> {
+        let rows: Vec<IntentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM payment_intents WHERE order_id = $1 ORDER BY created_at",
+            INTENT_COLUMNS
+        ))
+        .bind(order_id.to_string())
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        rows.into_iter().map(to_intent).collect()
+    }
+
+    pub async fn refunds(&self, payment_intent_id: Uuid) -> Result<Vec<Refund>> {
+        let rows: Vec<RefundRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM refunds WHERE payment_intent_id = $1 ORDER BY created_at",
+            REFUND_COLUMNS
+        ))
+        .bind(payment_intent_id.to_string())
+        .fetch_all(&self.db.pool)
+        .await?;
+        let items: Vec<RefundItemRow> = sqlx::query_as(
+            "SELECT ri.refund_id, ri.order_item_id, ri.quantity, ri.amount FROM refund_items ri \
+             JOIN refunds r ON r.id = ri.refund_id WHERE r.payment_intent_id = $1",
+        )
+        .bind(payment_intent_id.to_string())
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let lines: Vec<&RefundItemRow> =
+                    items.iter().filter(|item| item.refund_id == row.id).collect();
+                to_refund(row, &lines)
+            })
+            .collect()
+    }
+
+    /// Stores the gateway's answer and moves the order along with it. The
+    /// update is guarded on the status we read, so a concurrent change wins
+    /// and this one reports a conflict.
+    async fn record_outcome(&self, row: IntentRow, outcome: GatewayOutcome) -> Result<PaymentIntent> {
+        let id = parse_uuid(&row.id)?;
+        let order_id = parse_uuid(&row.order_id)?;
+        let now = Utc::now().to_rfc3339();
+
+        let mut tx = self.db.pool.begin().await?;
+        apply_outcome(&mut tx, &row, &outcome, &now).await?;
+        tx.commit().await?;
+        self.forget_order(order_id).await;
+
+        self.require_intent(id).await
     }
 
-    pub fn api_key(&self) -> &str {
-        &self.api_key
+    async fn intent_row(&self, payment_intent_id: Uuid) -> Result<IntentRow> {
+        sqlx::query_as(&format!(
+            "SELECT {} FROM payment_intents WHERE id = $1",
+            INTENT_COLUMNS
+        ))
+        .bind(payment_intent_id.to_string())
+        .fetch_optional(&self.db.pool)
+        .await?
+        .ok_or_else(|| PaymentError::NotFound(payment_intent_id).into())
     }
+
+    async fn require_intent(&self, payment_intent_id: Uuid) -> Result<PaymentIntent> {
+        to_intent(self.intent_row(payment_intent_id).await?)
+    }
+
+    async fn forget_order(&self, order_id: Uuid) {
+        self.cache
+            .delete(&cache_key("order", &[&order_id.to_string()]))
+            .await;
+    }
+}
+
+/// Stores the gateway's answer and moves the order along with it. The update
+/// is guarded on the status in `row`, so a concurrent change wins and this
+/// one reports a conflict.
+async fn apply_outcome(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    row: &IntentRow,
+    outcome: &GatewayOutcome,
+    now: &str,
+) -> Result<()> {
+    let id = parse_uuid(&row.id)?;
+    let order_id = parse_uuid(&row.order_id)?;
+    let payment_method = outcome
+        .payment_method
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    let updated = sqlx::query(
+        "UPDATE payment_intents SET status = $1, \
+         payment_method = COALESCE($2, payment_method), next_action_url = $3, \
+         last_error = $4, updated_at = $5 WHERE id = $6 AND status = $7",
+    )
+    .bind(outcome.status.as_str())
+    .bind(payment_method)
+    .bind(&outcome.next_action_url)
+    .bind(&outcome.error)
+    .bind(now)
+    .bind(&row.id)
+    .bind(&row.status)
+    .execute(&mut **tx)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(AppError::Conflict(
+            "Payment was modified concurrently, retry".to_string(),
+        ));
+    }
+
+    if let Some(target) = outcome.status.order_payment_status() {
+        sync_order_payment(tx, order_id, target, id, now).await?;
+    }
+
+    tracing::info!(
+        payment_intent_id = %id,
+        order_id = %order_id,
+        status = outcome.status.as_str(),
+        "Payment intent updated"
+    );
+    Ok(())
+}
+
+/// Applies an asynchronous intent update from `gateway`. Finished intents and
+/// repeats of the current status are left alone, so late, duplicated or
+/// out-of-order events change nothing. Returns the owning order when it may
+/// have changed.
+pub(crate) async fn apply_intent_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    gateway: &str,
+    reference: &str,
+    outcome: &GatewayOutcome,
+    now: &str,
+) -> Result<Option<Uuid>> {
+    let row: IntentRow = sqlx::query_as(&format!(
+        "SELECT {} FROM payment_intents WHERE gateway = $1 AND gateway_reference = $2",
+        INTENT_COLUMNS
+    ))
+    .bind(gateway)
+    .bind(reference)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Payment intent {} not found", reference)))?;
+
+    let current = parse_intent_status(&row.status)?;
+    if current == outcome.status || !current.is_open() {
+        return Ok(None);
+    }
+    apply_outcome(tx, &row, outcome, now).await?;
+    Ok(Some(parse_uuid(&row.order_id)?))
+}
+
+/// Settles a refund the gateway reported asynchronously; only pending
+/// refunds change. Returns the owning order when it may have changed.
+pub(crate) async fn apply_refund_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    gateway: &str,
+    reference: &str,
+    status: RefundStatus,
+    now: &str,
+) -> Result<Option<Uuid>> {
+    let (refund_id, payment_intent_id, order_id, current): (String, String, String, String) =
+        sqlx::query_as(
+            "SELECT r.id, r.payment_intent_id, p.order_id, r.status FROM refunds r \
+             JOIN payment_intents p ON p.id = r.payment_intent_id \
+             WHERE p.gateway = $1 AND r.gateway_reference = $2",
+        )
+        .bind(gateway)
+        .bind(reference)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Refund {} not found", reference)))?;
+
+    let current: RefundStatus = current.parse().map_err(AppError::InternalError)?;
+    if current != RefundStatus::Pending || status == RefundStatus::Pending {
+        return Ok(None);
+    }
+
+    sqlx::query("UPDATE refunds SET status = $1, updated_at = $2 WHERE id = $3 AND status = $4")
+        .bind(status.as_str())
+        .bind(now)
+        .bind(&refund_id)
+        .bind(current.as_str())
+        .execute(&mut **tx)
+        .await?;
+    if status == RefundStatus::Succeeded {
+        settle_refund(tx, parse_uuid(&refund_id)?, parse_uuid(&payment_intent_id)?, now).await?;
+    }
+    Ok(Some(parse_uuid(&order_id)?))
+}
+
+/// Books a refund that just succeeded: restocks its items when asked and
+/// updates the order's payment status. Returns the restocked product ids.
+async fn settle_refund(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    refund_id: Uuid,
+    payment_intent_id: Uuid,
+    now: &str,
+) -> Result<Vec<String>> {
+    let restock: i64 = sqlx::query_scalar("SELECT restock FROM refunds WHERE id = $1")
+        .bind(refund_id.to_string())
+        .fetch_one(&mut **tx)
+        .await?;
+    let mut restocked = Vec::new();
+    if restock != 0 {
+        let lines: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT i.product_id, ri.quantity FROM refund_items ri \
+             JOIN order_items i ON i.id = ri.order_item_id WHERE ri.refund_id = $1",
+        )
+        .bind(refund_id.to_string())
+        .fetch_all(&mut **tx)
+        .await?;
+        for (product_id, quantity) in lines {
+            sqlx::query(
+                "UPDATE products SET quantity = quantity + $1, updated_at = $2 WHERE id = $3",
+            )
+            .bind(quantity)
+            .bind(now)
+            .bind(&product_id)
+            .execute(&mut **tx)
+            .await?;
+            restocked.push(product_id);
+        }
+    }
+
+    sync_refunded_status(tx, payment_intent_id, now).await?;
+    Ok(restocked)
+}
+
+/// Marks the order partially or fully refunded from the intent's settled
+/// refunds.
+async fn sync_refunded_status(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    payment_intent_id: Uuid,
+    now: &str,
+) -> Result<()> {
+    let (order_id, amount): (String, i64) =
+        sqlx::query_as("SELECT order_id, amount FROM payment_intents WHERE id = $1")
+            .bind(payment_intent_id.to_string())
+            .fetch_one(&mut **tx)
+            .await?;
+    let refunded: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount), 0) FROM refunds \
+         WHERE payment_intent_id = $1 AND status = $2",
+    )
+    .bind(payment_intent_id.to_string())
+    .bind(RefundStatus::Succeeded.as_str())
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let target = if refunded >= amount {
+        PaymentStatus::Refunded
+    } else {
+        PaymentStatus::PartiallyRefunded
+    };
+    sync_order_payment(tx, parse_uuid(&order_id)?, target, payment_intent_id, now).await
+}
+
+/// Pending and settled refunds, which both count against the payment.
+async fn committed_refunds(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    intent: &PaymentIntent,
+) -> Result<Money> {
+    let minor: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount), 0) FROM refunds \
+         WHERE payment_intent_id = $1 AND status IN ($2, $3)",
+    )
+    .bind(intent.id.to_string())
+    .bind(RefundStatus::Pending.as_str())
+    .bind(RefundStatus::Succeeded.as_str())
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(Money::from_minor(minor, &intent.amount.currency))
+}
+
+/// Prices the requested lines from what was paid for them. Each unit's
+/// share is taken from the line total so refunding every unit returns
+/// exactly the line's amount.
+async fn refund_lines(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    order_id: Uuid,
+    requested: &[RefundItemRequest],
+    currency: &str,
+) -> Result<Vec<RefundItem>> {
+    let mut lines: Vec<RefundItem> = Vec::with_capacity(requested.len());
+    for request in requested {
+        if lines.iter().any(|line| line.order_item_id == request.order_item_id) {
+            return Err(AppError::BadRequest(format!(
+                "Order item {} is listed more than once",
+                request.order_item_id
+            )));
+        }
+
+        let line: RefundableLine = sqlx::query_as(
+            "SELECT i.quantity, i.total_price, i.tax_amount, i.discount_amount, \
+             o.prices_include_tax FROM order_items i JOIN orders o ON o.id = i.order_id \
+             WHERE i.id = $1 AND i.order_id = $2",
+        )
+        .bind(request.order_item_id.to_string())
+        .bind(order_id.to_string())
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Order item {} is not part of this order",
+                request.order_item_id
+            ))
+        })?;
+        let already: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(ri.quantity), 0) FROM refund_items ri \
+             JOIN refunds r ON r.id = ri.refund_id \
+             WHERE ri.order_item_id = $1 AND r.status IN ($2, $3)",
+        )
+        .bind(request.order_item_id.to_string())
+        .bind(RefundStatus::Pending.as_str())
+        .bind(RefundStatus::Succeeded.as_str())
+        .fetch_one(&mut **tx)
+        .await?;
+
+        let quantity = i64::from(request.quantity);
+        if already + quantity > line.quantity {
+            return Err(AppError::BadRequest(format!(
+                "Only {} of order item {} can still be refunded",
+                line.quantity - already,
+                request.order_item_id
+            )));
+        }
+
+        let mut paid = line.total_price - line.discount_amount;
+        if line.prices_include_tax == 0 {
+            paid += line.tax_amount;
+        }
+        let amount = prorate(paid, already + quantity, line.quantity)
+            - prorate(paid, already, line.quantity);
+        lines.push(RefundItem {
+            order_item_id: request.order_item_id,
+            quantity: request.quantity,
+            amount: Money::from_minor(amount, currency),
+        });
+    }
+    Ok(lines)
+}
+
+/// The order's intent still awaiting the customer or gateway, if any.
+async fn open_intent(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    order_id: Uuid,
+) -> Result<Option<PaymentIntent>> {
+    let rows: Vec<IntentRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM payment_intents WHERE order_id = $1 ORDER BY created_at",
+        INTENT_COLUMNS
+    ))
+    .bind(order_id.to_string())
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for row in rows {
+        let intent = to_intent(row)?;
+        if intent.status.is_open() {
+            return Ok(Some(intent));
+        }
+    }
+    Ok(None)
+}
+
+/// Moves the order's payment status to `target`, recording an order event.
+/// A failed order goes back through pending first, as its transition table
+/// requires.
+async fn sync_order_payment(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    order_id: Uuid,
+    target: PaymentStatus,
+    payment_intent_id: Uuid,
+    now: &str,
+) -> Result<()> {
+    let current: String = sqlx::query_scalar("SELECT payment_status FROM orders WHERE id = $1")
+        .bind(order_id.to_string())
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", order_id)))?;
+    let current: PaymentStatus = current.parse().map_err(AppError::InternalError)?;
+    if current == target {
+        return Ok(());
+    }
+
+    let mut steps = Vec::with_capacity(2);
+    if current == PaymentStatus::Failed && target != PaymentStatus::Pending {
+        steps.push(PaymentStatus::Pending);
+    }
+    steps.push(target);
+
+    for step in steps {
+        let request = UpdateOrderStatusRequest {
+            payment_status: Some(step),
+            note: Some(format!("Payment intent {}", payment_intent_id)),
+            ..Default::default()
+        };
+        apply_transition(tx, order_id, &request, None, now).await?;
+    }
+    Ok(())
+}
+
+fn to_intent(row: IntentRow) -> Result<PaymentIntent> {
+    Ok(PaymentIntent {
+        id: parse_uuid(&row.id)?,
+        order_id: parse_uuid(&row.order_id)?,
+        amount: Money::from_minor(row.amount, &row.currency),
+        status: parse_intent_status(&row.status)?,
+        payment_method: row
+            .payment_method
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?,
+        client_secret: row.client_secret,
+        next_action_url: row.next_action_url,
+        last_error: row.last_error,
+        created_at: parse_timestamp(&row.created_at)?,
+        updated_at: parse_timestamp(&row.updated_at)?,
+    })
+}
+
+fn to_refund(row: RefundRow, items: &[&RefundItemRow]) -> Result<Refund> {
+    let items = items
+        .iter()
+        .map(|item| {
+            Ok(RefundItem {
+                order_item_id: parse_uuid(&item.order_item_id)?,
+                quantity: item.quantity as i32,
+                amount: Money::from_minor(item.amount, &row.currency),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Refund {
+        id: parse_uuid(&row.id)?,
+        payment_intent_id: parse_uuid(&row.payment_intent_id)?,
+        amount: Money::from_minor(row.amount, &row.currency),
+        status: row.status.parse().map_err(AppError::InternalError)?,
+        reason: row
+            .reason
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(AppError::InternalError)?,
+        items,
+        restock: row.restock != 0,
+        created_at: parse_timestamp(&row.created_at)?,
+    })
+}
+
+/// `amount * part / whole`, rounded half up.
+fn prorate(amount: i64, part: i64, whole: i64) -> i64 {
+    let scaled = i128::from(amount) * i128::from(part) * 2 + i128::from(whole);
+    (scaled / (i128::from(whole) * 2)) as i64
+}
+
+fn parse_intent_status(value: &str) -> Result<PaymentIntentStatus> {
+    value.parse().map_err(AppError::InternalError)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -207,8 +1153,315 @@ pub enum PaymentError {
     PaymentFailed(String),
     #[error("Invalid payment method: {0}")]
     InvalidPaymentMethod(String),
+    #[error("Invalid payment state: {0}")]
+    InvalidState(String),
     #[error("Refund failed: {0}")]
     RefundFailed(String),
     #[error("API error: {0}")]
     ApiError(String),
+    #[error("Payment gateway misconfigured: {0}")]
+    Misconfigured(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AddressRequest, CreateOrderItemRequest, CreateOrderRequest};
+    use crate::payment_gateway::test_cards;
+    use crate::services::{order_service::OrderService, user_service::UserService};
+
+    /// A service and a pending order for 29.99 including shipping.
+    async fn setup() -> (PaymentService, OrderService, Uuid) {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let customer = UserService::new(db.clone(), cache.clone())
+            .create_user_with_password(
+                "payer@example.com".to_string(),
+                "payer".to_string(),
+                "hash".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let product_id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO products (id, sku, name, slug, description, price, quantity, \
+             created_at, updated_at) VALUES ($1, 'TEE', 'Tee', 'tee', '', 2000, 5, $2, $3)",
+        )
+        .bind(product_id.to_string())
+        .bind(&now)
+        .bind(&now)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let address = AddressRequest {
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            company: None,
+            address_line_1: "1 Main St".to_string(),
+            address_line_2: None,
+            city: "Paris".to_string(),
+            state: None,
+            postal_code: "75001".to_string(),
+            country: "FR".to_string(),
+            phone: None,
+        };
+        let orders = OrderService::new(db.clone(), cache.clone()).with_tax_calculator(Arc::new(
+            crate::tax::RateTable::new(
+                &crate::config::TaxConfig::builder()
+                    .default_rate(Decimal::ZERO)
+                    .build(),
+            ),
+        ));
+        let order = orders
+            .create_order(
+                CreateOrderRequest::builder()
+                    .customer_id(customer.id)
+                    .items(vec![CreateOrderItemRequest {
+                        product_id,
+                        variant_id: None,
+                        quantity: 1,
+                    }])
+                    .billing_address(address.clone())
+                    .shipping_address(address)
+                    .shipping_method(None)
+                    .notes(None)
+                    .coupon_code(None)
+                    .currency(None)
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        (PaymentService::new(db, cache), orders, order.id)
+    }
+
+    fn card(number: &str) -> PaymentMethodRequest {
+        PaymentMethodRequest {
+            payment_type: PaymentMethodType::Card,
+            card: Some(CardInput {
+                number: number.to_string(),
+                exp_month: 12,
+                exp_year: 2099,
+            }),
+            billing_details: None,
+        }
+    }
+
+    fn refund(amount: Option<Decimal>, items: Vec<RefundItemRequest>) -> CreateRefundRequest {
+        CreateRefundRequest {
+            amount,
+            items,
+            restock: false,
+            reason: None,
+        }
+    }
+
+    async fn payment_status(orders: &OrderService, order_id: Uuid) -> PaymentStatus {
+        orders
+            .get_order_by_id(order_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .payment_status
+    }
+
+    #[tokio::test]
+    async fn test_decline_then_retry_pays_the_order() {
+        let (payments, orders, order_id) = setup().await;
+
+        let intent = payments.create_payment_intent(order_id).await.unwrap();
+        assert_eq!(intent.amount, Money::from_minor(2999, "USD"));
+        assert_eq!(intent.status, PaymentIntentStatus::RequiresPaymentMethod);
+
+        let declined = payments
+            .confirm_payment(intent.id, card(test_cards::INSUFFICIENT_FUNDS))
+            .await
+            .unwrap();
+        assert_eq!(declined.status, PaymentIntentStatus::Failed);
+        assert_eq!(declined.last_error.as_deref(), Some("insufficient_funds"));
+        assert_eq!(payment_status(&orders, order_id).await, PaymentStatus::Failed);
+
+        // The open intent is reused rather than duplicated
+        let again = payments.create_payment_intent(order_id).await.unwrap();
+        assert_eq!(again.id, intent.id);
+
+        let paid = payments
+            .confirm_payment(intent.id, card(test_cards::SUCCESS))
+            .await
+            .unwrap();
+        assert_eq!(paid.status, PaymentIntentStatus::Succeeded);
+        assert_eq!(paid.last_error, None);
+        assert_eq!(
+            paid.payment_method.and_then(|m| m.card).map(|c| c.last4),
+            Some("4242".to_string())
+        );
+        let order = orders.get_order_by_id(order_id).await.unwrap().unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Paid);
+        assert!(order.paid_at.is_some());
+
+        let moves: Vec<(String, String)> = orders
+            .order_events(order_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.from_state, e.to_state))
+            .collect();
+        assert_eq!(moves.len(), 3);
+        assert!(moves.contains(&("failed".to_string(), "pending".to_string())));
+
+        assert!(matches!(
+            payments.create_payment_intent(order_id).await,
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_checkouts_share_one_intent() {
+        let (payments, _, order_id) = setup().await;
+
+        let (first, second) = tokio::join!(
+            payments.create_payment_intent(order_id),
+            payments.create_payment_intent(order_id)
+        );
+        assert_eq!(first.unwrap().id, second.unwrap().id);
+        assert_eq!(payments.payment_intents_for_order(order_id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_authentication_and_refunds() {
+        let (payments, orders, order_id) = setup().await;
+        let intent = payments.create_payment_intent(order_id).await.unwrap();
+
+        let pending = payments
+            .confirm_payment(intent.id, card(test_cards::REQUIRES_ACTION))
+            .await
+            .unwrap();
+        assert_eq!(pending.status, PaymentIntentStatus::RequiresAction);
+        assert!(pending.next_action_url.is_some());
+        assert_eq!(payment_status(&orders, order_id).await, PaymentStatus::Pending);
+
+        let paid = payments.complete_action(intent.id).await.unwrap();
+        assert_eq!(paid.status, PaymentIntentStatus::Succeeded);
+        assert_eq!(paid.next_action_url, None);
+
+        let partial = payments
+            .create_refund(intent.id, refund(Some(Decimal::new(999, 2)), Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(partial.status, RefundStatus::Succeeded);
+        assert_eq!(
+            payment_status(&orders, order_id).await,
+            PaymentStatus::PartiallyRefunded
+        );
+
+        payments
+            .create_refund(
+                intent.id,
+                CreateRefundRequest {
+                    reason: Some(RefundReason::RequestedByCustomer),
+                    ..refund(Some(Decimal::new(2000, 2)), Vec::new())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(payment_status(&orders, order_id).await, PaymentStatus::Refunded);
+
+        let refunds = payments.refunds(intent.id).await.unwrap();
+        assert_eq!(refunds.len(), 2);
+        assert_eq!(refunds[1].reason, Some(RefundReason::RequestedByCustomer));
+    }
+
+    #[tokio::test]
+    async fn test_item_refunds_restock_and_stay_within_the_payment() {
+        let (payments, orders, order_id) = setup().await;
+        let intent = payments.create_payment_intent(order_id).await.unwrap();
+        payments
+            .confirm_payment(intent.id, card(test_cards::SUCCESS))
+            .await
+            .unwrap();
+        let item = orders.get_order_by_id(order_id).await.unwrap().unwrap().items[0].clone();
+        let stock = || async {
+            sqlx::query_scalar::<_, i64>("SELECT quantity FROM products WHERE id = $1")
+                .bind(item.product_id.to_string())
+                .fetch_one(&payments.db.pool)
+                .await
+                .unwrap()
+        };
+        assert_eq!(stock().await, 4);
+
+        let line = || {
+            vec![RefundItemRequest {
+                order_item_id: item.id,
+                quantity: 1,
+            }]
+        };
+        // An amount has to agree with the lines it is sent with
+        assert!(matches!(
+            payments
+                .create_refund(intent.id, refund(Some(Decimal::new(2999, 2)), line()))
+                .await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        let returned = payments
+            .create_refund(
+                intent.id,
+                CreateRefundRequest {
+                    restock: true,
+                    ..refund(None, line())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(returned.amount, Money::from_minor(2000, "USD"));
+        assert_eq!(returned.items.len(), 1);
+        assert_eq!(stock().await, 5);
+        assert_eq!(
+            payment_status(&orders, order_id).await,
+            PaymentStatus::PartiallyRefunded
+        );
+
+        // The unit is already refunded, and only shipping is left
+        assert!(matches!(
+            payments.create_refund(intent.id, refund(None, line())).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            payments
+                .create_refund(intent.id, refund(Some(Decimal::new(1000, 2)), Vec::new()))
+                .await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        let rest = payments
+            .create_refund(intent.id, refund(None, Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(rest.amount, Money::from_minor(999, "USD"));
+        assert_eq!(payment_status(&orders, order_id).await, PaymentStatus::Refunded);
+        assert_eq!(payments.refunds(intent.id).await.unwrap()[0].items[0].quantity, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refunds_cannot_exceed_the_payment() {
+        let (payments, orders, order_id) = setup().await;
+        let intent = payments.create_payment_intent(order_id).await.unwrap();
+        payments
+            .confirm_payment(intent.id, card(test_cards::SUCCESS))
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            payments.create_refund(intent.id, refund(None, Vec::new())),
+            payments.create_refund(intent.id, refund(None, Vec::new()))
+        );
+        assert!(first.is_ok() != second.is_ok());
+        assert_eq!(payments.refunds(intent.id).await.unwrap().len(), 1);
+        assert_eq!(payment_status(&orders, order_id).await, PaymentStatus::Refunded);
+    }
 }
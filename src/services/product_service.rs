@@ -1,13 +1,60 @@
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
     cache::{cache_key, CacheManager},
-    database::Database,
-    error::Result,
+    database::{parse_timestamp, parse_uuid, Database, Nullable},
+    error::{AppError, Result},
     models::{PaginationParams, Product},
+    money::Money,
 };
 
+const PRODUCT_COLUMNS: &str = "id, sku, name, slug, description, short_description, price, \
+     sale_price, cost_price, currency, quantity, low_stock_threshold, weight, dimensions, images, \
+     thumbnail_url, category_id, brand_id, status, is_featured, is_digital, meta_title, \
+     meta_description, created_at, updated_at";
+
+#[derive(Debug, sqlx::FromRow)]
+struct ProductRow {
+    id: String,
+    sku: String,
+    name: String,
+    slug: String,
+    description: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    short_description: Option<String>,
+    price: i64,
+    #[sqlx(try_from = "Nullable<i64>")]
+    sale_price: Option<i64>,
+    #[sqlx(try_from = "Nullable<i64>")]
+    cost_price: Option<i64>,
+    currency: String,
+    quantity: i64,
+    low_stock_threshold: i64,
+    #[sqlx(try_from = "Nullable<f64>")]
+    weight: Option<f64>,
+    #[sqlx(try_from = "Nullable<String>")]
+    dimensions: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    images: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    thumbnail_url: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    category_id: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    brand_id: Option<String>,
+    status: String,
+    is_featured: i64,
+    is_digital: i64,
+    #[sqlx(try_from = "Nullable<String>")]
+    meta_title: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    meta_description: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
 pub struct ProductService {
     db: Arc<Database>,
     cache: Arc<CacheManager>,
@@ -19,12 +66,23 @@ impl ProductService {
     }
 
     pub async fn list_products(&self, pagination: &PaginationParams) -> Result<(Vec<Product>, i64)> {
-        let _offset = (pagination.page - 1) * pagination.per_page;
-        let _ = &self.db.pool;
-
-        let products: Vec<Product> = Vec::new();
-        let total: i64 = 0;
-
+        let per_page = pagination.per_page.clamp(1, 100);
+        let offset = (pagination.page.max(1) - 1) * per_page;
+
+        let rows: Vec<ProductRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM products ORDER BY created_at DESC, id LIMIT $1 OFFSET $2",
+            PRODUCT_COLUMNS
+        ))
+        .bind(per_page as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM products")
+            .fetch_one(&self.db.pool)
+            .await?;
+
+        let products = rows.into_iter().map(to_product).collect::<Result<Vec<_>>>()?;
         Ok((products, total))
     }
 
@@ -35,7 +93,7 @@ impl ProductService {
             return Ok(Some(product));
         }
 
-        let product: Option<Product> = None;
+        let product = self.fetch_one("id", &id.to_string()).await?;
 
         if let Some(ref p) = product {
             let _ = self.cache.set_json(cache_key, p).await;
@@ -51,7 +109,12 @@ impl ProductService {
             return Ok(Some(product));
         }
 
-        let product: Option<Product> = None;
+        let product = self.fetch_one("sku", sku).await?;
+
+        if let Some(ref p) = product {
+            let _ = self.cache.set_json(cache_key, p).await;
+        }
+
         Ok(product)
     }
 
@@ -89,4 +152,51 @@ impl ProductService {
         let _ = threshold;
         Ok(Vec::new())
     }
+
+    async fn fetch_one(&self, column: &str, value: &str) -> Result<Option<Product>> {
+        let query = format!("SELECT {} FROM products WHERE {} = $1", PRODUCT_COLUMNS, column);
+        let row: Option<ProductRow> = sqlx::query_as(&query)
+            .bind(value)
+            .fetch_optional(&self.db.pool)
+            .await?;
+
+        row.map(to_product).transpose()
+    }
+}
+
+fn to_product(row: ProductRow) -> Result<Product> {
+    let money = |minor: i64| Money::from_minor(minor, &row.currency);
+    Ok(Product {
+        id: parse_uuid(&row.id)?,
+        price: money(row.price),
+        sale_price: row.sale_price.map(money),
+        cost_price: row.cost_price.map(money),
+        quantity: row.quantity as i32,
+        low_stock_threshold: row.low_stock_threshold as i32,
+        weight: row.weight,
+        dimensions: row.dimensions.as_deref().and_then(|d| serde_json::from_str(d).ok()),
+        images: row
+            .images
+            .as_deref()
+            .and_then(|i| serde_json::from_str(i).ok())
+            .unwrap_or_default(),
+        category_id: row.category_id.as_deref().map(parse_uuid).transpose()?,
+        brand_id: row.brand_id.as_deref().map(parse_uuid).transpose()?,
+        status: serde_json::from_value(serde_json::Value::String(row.status.clone()))
+            .map_err(|_| AppError::InternalError(format!("Invalid product status '{}'", row.status)))?,
+        is_featured: row.is_featured != 0,
+        is_digital: row.is_digital != 0,
+        created_at: parse_timestamp(&row.created_at)?,
+        updated_at: parse_timestamp(&row.updated_at)?,
+        sku: row.sku,
+        name: row.name,
+        slug: row.slug,
+        description: row.description,
+        short_description: row.short_description,
+        currency: row.currency,
+        thumbnail_url: row.thumbnail_url,
+        meta_title: row.meta_title,
+        meta_description: row.meta_description,
+    })
 }
+
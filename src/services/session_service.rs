@@ -0,0 +1,307 @@
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    auth::{AuthService, IssuedTokens, TokenPair},
+    cache::{cache_key, CacheManager},
+    database::{Database, Nullable},
+    error::{AppError, Result},
+    models::{User, UserStatus},
+    services::user_service::UserService,
+};
+
+/// Server-side record of refresh tokens. Each issued pair is one row; rows
+/// descending from the same login share a `family_id`.
+pub struct SessionService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SessionRow {
+    id: String,
+    user_id: String,
+    family_id: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    revoked_at: Option<String>,
+}
+
+impl SessionService {
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
+        Self { db, cache }
+    }
+
+    /// Starts a new token family for a fresh login.
+    pub async fn start(&self, auth: &AuthService, user: &User) -> Result<TokenPair> {
+        let issued = auth.issue_tokens(user.id, &user.email, user.role.as_str())?;
+        self.record(&issued, Uuid::new_v4()).await?;
+        Ok(issued.tokens)
+    }
+
+    /// Exchanges a refresh token for a new pair. A token that was already
+    /// rotated or revoked is treated as stolen and its whole family revoked.
+    pub async fn rotate(&self, auth: &AuthService, refresh_token: &str) -> Result<TokenPair> {
+        let claims = auth.validate_refresh_token(refresh_token)?.claims;
+
+        let session: SessionRow = sqlx::query_as(
+            "SELECT id, user_id, family_id, revoked_at FROM sessions WHERE token_hash = $1",
+        )
+        .bind(hash_token(refresh_token))
+        .fetch_optional(&self.db.pool)
+        .await?
+        .ok_or_else(|| AppError::AuthenticationError("Unknown refresh token".to_string()))?;
+
+        if session.revoked_at.is_some() {
+            return Err(self.reuse_detected(&session).await);
+        }
+
+        let user = UserService::new(self.db.clone(), self.cache.clone())
+            .get_user_by_id(claims.user_id)
+            .await?
+            .filter(|u| u.status == UserStatus::Active)
+            .ok_or_else(|| AppError::AuthenticationError("User is not active".to_string()))?;
+
+        let issued = auth.issue_tokens(user.id, &user.email, user.role.as_str())?;
+        let successor_id = Uuid::new_v4();
+
+        let mut tx = self.db.pool.begin().await?;
+        let revoked = sqlx::query(
+            "UPDATE sessions SET revoked_at = $1, replaced_by = $2 WHERE id = $3 AND revoked_at IS NULL",
+        )
+        .bind(timestamp(Utc::now()))
+        .bind(successor_id.to_string())
+        .bind(&session.id)
+        .execute(&mut *tx)
+        .await?;
+
+        if revoked.rows_affected() == 0 {
+            // Lost a race with a concurrent refresh of the same token
+            tx.rollback().await?;
+            return Err(self.reuse_detected(&session).await);
+        }
+
+        insert_session(&mut tx, successor_id, &issued, &session.family_id).await?;
+        tx.commit().await?;
+
+        Ok(issued.tokens)
+    }
+
+    /// Ends the session the access token belongs to.
+    pub async fn logout(&self, access_jti: &str) -> Result<()> {
+        let family_id: Option<String> =
+            sqlx::query_scalar("SELECT family_id FROM sessions WHERE access_jti = $1")
+                .bind(access_jti)
+                .fetch_optional(&self.db.pool)
+                .await?;
+
+        match family_id {
+            Some(family_id) => self.revoke_where("family_id", &family_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Ends every session of the user, on all devices.
+    pub async fn logout_all(&self, user_id: Uuid) -> Result<()> {
+        self.revoke_where("user_id", &user_id.to_string()).await
+    }
+
+    /// Whether an access token was revoked before its expiry.
+    pub async fn is_access_token_revoked(&self, jti: &str) -> Result<bool> {
+        let key = cache_key("revoked_jti", &[jti]);
+        if self.cache.get_string(&key).await.is_some() {
+            return Ok(true);
+        }
+
+        let revoked: Option<String> =
+            sqlx::query_scalar("SELECT jti FROM revoked_tokens WHERE jti = $1")
+                .bind(jti)
+                .fetch_optional(&self.db.pool)
+                .await?;
+
+        if revoked.is_some() {
+            self.cache.set_string(key, String::new()).await;
+        }
+        Ok(revoked.is_some())
+    }
+
+    /// Deletes sessions and denylist entries that have expired anyway.
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let now = timestamp(Utc::now());
+        let sessions = sqlx::query("DELETE FROM sessions WHERE expires_at < $1")
+            .bind(&now)
+            .execute(&self.db.pool)
+            .await?;
+        let tokens = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < $1")
+            .bind(&now)
+            .execute(&self.db.pool)
+            .await?;
+        Ok(sessions.rows_affected() + tokens.rows_affected())
+    }
+
+    async fn record(&self, issued: &IssuedTokens, family_id: Uuid) -> Result<()> {
+        let mut tx = self.db.pool.begin().await?;
+        insert_session(&mut tx, Uuid::new_v4(), issued, &family_id.to_string()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn reuse_detected(&self, session: &SessionRow) -> AppError {
+        tracing::warn!(
+            user_id = %session.user_id,
+            family_id = %session.family_id,
+            "Refresh token reuse detected, revoking token family"
+        );
+        if let Err(e) = self.revoke_where("family_id", &session.family_id).await {
+            return e;
+        }
+        AppError::AuthenticationError("Refresh token has been revoked".to_string())
+    }
+
+    /// Revokes matching sessions and denylists their still-valid access tokens.
+    /// `column` is always a literal from this file.
+    async fn revoke_where(&self, column: &str, value: &str) -> Result<()> {
+        let now = timestamp(Utc::now());
+        let mut tx = self.db.pool.begin().await?;
+
+        let live_tokens: Vec<(String, String)> = sqlx::query_as(&format!(
+            "SELECT access_jti, access_expires_at FROM sessions WHERE {} = $1 AND access_expires_at > $2",
+            column
+        ))
+        .bind(value)
+        .bind(&now)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (jti, expires_at) in &live_tokens {
+            sqlx::query(
+                "INSERT INTO revoked_tokens (jti, expires_at, revoked_at) VALUES ($1, $2, $3) \
+                 ON CONFLICT (jti) DO NOTHING",
+            )
+            .bind(jti)
+            .bind(expires_at)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(&format!(
+            "UPDATE sessions SET revoked_at = $1 WHERE {} = $2 AND revoked_at IS NULL",
+            column
+        ))
+        .bind(&now)
+        .bind(value)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        for (jti, _) in live_tokens {
+            self.cache
+                .set_string(cache_key("revoked_jti", &[&jti]), String::new())
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+async fn insert_session(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    id: Uuid,
+    issued: &IssuedTokens,
+    family_id: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, family_id, token_hash, access_jti, access_expires_at, \
+         expires_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(id.to_string())
+    .bind(issued.refresh_claims.user_id.to_string())
+    .bind(family_id)
+    .bind(hash_token(&issued.tokens.refresh_token))
+    .bind(&issued.access_claims.jti)
+    .bind(timestamp(from_unix(issued.access_claims.exp)))
+    .bind(timestamp(from_unix(issued.refresh_claims.exp)))
+    .bind(timestamp(Utc::now()))
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Fixed-width RFC 3339, so stored timestamps compare correctly as text.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn from_unix(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0).single().unwrap_or_else(Utc::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn setup() -> (SessionService, AuthService, User) {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let user = UserService::new(db.clone(), cache.clone())
+            .create_user_with_password(
+                "sam@example.com".to_string(),
+                "sam".to_string(),
+                "hash".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let auth = AuthService::new("test-secret".to_string(), 60, 120);
+        (SessionService::new(db, cache), auth, user)
+    }
+
+    fn jti(auth: &AuthService, tokens: &TokenPair) -> String {
+        auth.validate_access_token(&tokens.access_token).unwrap().claims.jti
+    }
+
+    #[tokio::test]
+    async fn test_rotation_and_reuse_detection() {
+        let (sessions, auth, user) = setup().await;
+
+        let first = sessions.start(&auth, &user).await.unwrap();
+        let second = sessions.rotate(&auth, &first.refresh_token).await.unwrap();
+        assert_ne!(first.refresh_token, second.refresh_token);
+
+        // Replaying the rotated token revokes the family, including the live one
+        assert!(matches!(
+            sessions.rotate(&auth, &first.refresh_token).await,
+            Err(AppError::AuthenticationError(_))
+        ));
+        assert!(sessions.rotate(&auth, &second.refresh_token).await.is_err());
+        assert!(sessions
+            .is_access_token_revoked(&jti(&auth, &second))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_logout_scopes() {
+        let (sessions, auth, user) = setup().await;
+
+        let laptop = sessions.start(&auth, &user).await.unwrap();
+        let phone = sessions.start(&auth, &user).await.unwrap();
+
+        sessions.logout(&jti(&auth, &laptop)).await.unwrap();
+        assert!(sessions.is_access_token_revoked(&jti(&auth, &laptop)).await.unwrap());
+        assert!(!sessions.is_access_token_revoked(&jti(&auth, &phone)).await.unwrap());
+        assert!(sessions.rotate(&auth, &laptop.refresh_token).await.is_err());
+
+        sessions.logout_all(user.id).await.unwrap();
+        assert!(sessions.is_access_token_revoked(&jti(&auth, &phone)).await.unwrap());
+        assert!(sessions.rotate(&auth, &phone.refresh_token).await.is_err());
+    }
+}
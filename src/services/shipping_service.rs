@@ -0,0 +1,130 @@
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::{
+    cache::CacheManager,
+    config::ShippingConfig,
+    exchange::{RateProvider, StaticRates},
+    database::{Database, Nullable},
+    error::{AppError, Result},
+    models::{ShippingOption, ShippingQuoteRequest, ShippingQuoteResponse},
+    money::Money,
+    shipping::{ShippingItem, ShippingRates},
+};
+
+pub struct ShippingService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+    rates: Arc<ShippingRates>,
+    exchange: Arc<dyn RateProvider>,
+}
+
+/// The product columns a quote needs.
+#[derive(Debug, sqlx::FromRow)]
+struct ShippableProduct {
+    sku: String,
+    price: i64,
+    #[sqlx(try_from = "Nullable<i64>")]
+    sale_price: Option<i64>,
+    currency: String,
+    status: String,
+    #[sqlx(try_from = "Nullable<f64>")]
+    weight: Option<f64>,
+    #[sqlx(try_from = "Nullable<String>")]
+    dimensions: Option<String>,
+    is_digital: i64,
+}
+
+impl ShippingService {
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
+        Self {
+            db,
+            cache,
+            rates: Arc::new(ShippingRates::new(&ShippingConfig::default())),
+            exchange: Arc::new(StaticRates::default()),
+        }
+    }
+
+    pub fn with_rates(mut self, rates: Arc<ShippingRates>) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    pub fn with_exchange_rates(mut self, exchange: Arc<dyn RateProvider>) -> Self {
+        self.exchange = exchange;
+        self
+    }
+
+    /// Prices every shipping option for the cart at catalog prices.
+    pub async fn quote(&self, request: ShippingQuoteRequest) -> Result<ShippingQuoteResponse> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+        let mut currency: Option<String> = None;
+        let mut lines = Vec::with_capacity(request.items.len());
+        let mut items = Vec::with_capacity(request.items.len());
+        for line in &request.items {
+            let product: ShippableProduct = sqlx::query_as(
+                "SELECT sku, price, sale_price, currency, status, weight, dimensions, is_digital \
+                 FROM products WHERE id = $1",
+            )
+            .bind(line.product_id.to_string())
+            .fetch_optional(&self.db.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Product {} not found", line.product_id)))?;
+
+            if product.status != "active" {
+                return Err(AppError::BadRequest(format!(
+                    "Product {} is not available",
+                    product.sku
+                )));
+            }
+            match &currency {
+                Some(c) if *c != product.currency => {
+                    return Err(AppError::BadRequest(
+                        "All items in a cart must share one currency".to_string(),
+                    ));
+                }
+                Some(_) => {}
+                None => currency = Some(product.currency.clone()),
+            }
+
+            let unit_price = Money::from_minor(product.sale_price.unwrap_or(product.price), &product.currency);
+            lines.push(unit_price.times(Decimal::from(line.quantity)));
+            items.push(ShippingItem::from_columns(
+                product.weight,
+                product.dimensions.as_deref(),
+                line.quantity as u32,
+                product.is_digital != 0,
+            )?);
+        }
+
+        let currency = currency.unwrap_or_else(|| "USD".to_string());
+        let order_value = Money::sum(&currency, &lines)?;
+        let rate = self.exchange.rate(self.rates.currency(), &currency).await?;
+        let options = self
+            .rates
+            .quote(&request.country, &items, &order_value, rate)
+            .into_iter()
+            .map(|rate| ShippingOption {
+                id: rate.id,
+                carrier: rate.carrier,
+                name: rate.name,
+                amount: rate.amount,
+                min_days: rate.min_days,
+                max_days: rate.max_days,
+            })
+            .collect();
+
+        let billable_weight = self.rates.billable_weight(&items);
+        Ok(ShippingQuoteResponse {
+            currency,
+            billable_weight: f64::try_from(billable_weight).map_err(|_| {
+                AppError::ValidationError(format!("Invalid billable weight: {}", billable_weight))
+            })?,
+            options,
+        })
+    }
+}
@@ -0,0 +1,315 @@
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{pin_mut, Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+    config::UploadConfig,
+    database::{parse_timestamp, parse_uuid, Database},
+    error::{AppError, Result},
+    storage::{BlobStore, ByteRange, ByteStream, StagedFile},
+};
+
+/// How much of the start of a file is kept to sniff its type.
+const SNIFF_BYTES: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub storage_key: String,
+    pub original_filename: String,
+    /// Sniffed from the contents, never taken from the client
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UploadRow {
+    id: String,
+    owner_id: String,
+    storage_key: String,
+    original_filename: String,
+    content_type: String,
+    size: i64,
+    sha256: String,
+    created_at: String,
+}
+
+impl TryFrom<UploadRow> for Upload {
+    type Error = AppError;
+
+    fn try_from(row: UploadRow) -> Result<Self> {
+        Ok(Upload {
+            id: parse_uuid(&row.id)?,
+            owner_id: parse_uuid(&row.owner_id)?,
+            storage_key: row.storage_key,
+            original_filename: row.original_filename,
+            content_type: row.content_type,
+            size: row.size as u64,
+            sha256: row.sha256,
+            created_at: parse_timestamp(&row.created_at)?,
+        })
+    }
+}
+
+/// Receives uploaded files into a [`BlobStore`] and records them in the
+/// `uploads` table.
+pub struct UploadService {
+    db: Arc<Database>,
+    store: Arc<dyn BlobStore>,
+    allowed_content_types: Vec<String>,
+    max_bytes: u64,
+}
+
+impl UploadService {
+    pub fn new(
+        db: Arc<Database>,
+        store: Arc<dyn BlobStore>,
+        config: &UploadConfig,
+        max_bytes: u64,
+    ) -> Self {
+        Self {
+            db,
+            store,
+            allowed_content_types: config.allowed_content_types.clone(),
+            max_bytes,
+        }
+    }
+
+    /// Streams `body` to a staging file while counting, hashing and
+    /// sniffing it, then hands it to the store. Nothing is kept if the file
+    /// is too large, empty or of a type that is not allowed.
+    pub async fn store(
+        &self,
+        owner_id: Uuid,
+        filename: &str,
+        body: impl Stream<Item = Result<Bytes>>,
+    ) -> Result<Upload> {
+        let path = std::env::temp_dir().join(format!("upload-{}", Uuid::new_v4().simple()));
+        let staged = self.stage(&path, body).await;
+        let upload = match staged {
+            Ok(staged) => self.save(owner_id, filename, &staged).await,
+            Err(e) => Err(e),
+        };
+        let _ = tokio::fs::remove_file(&path).await;
+        upload
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Upload> {
+        let row: Option<UploadRow> = sqlx::query_as(
+            "SELECT id, owner_id, storage_key, original_filename, content_type, size, sha256, \
+             created_at FROM uploads WHERE id = $1",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.db.pool)
+        .await?;
+
+        row.map(Upload::try_from)
+            .transpose()?
+            .ok_or_else(|| AppError::NotFound(format!("Upload {} not found", id)))
+    }
+
+    /// The stored bytes of `upload`, or only those in `range`.
+    pub async fn open(&self, upload: &Upload, range: Option<ByteRange>) -> Result<ByteStream> {
+        Ok(self.store.get(&upload.storage_key, range).await?)
+    }
+
+    async fn stage(
+        &self,
+        path: &std::path::Path,
+        body: impl Stream<Item = Result<Bytes>>,
+    ) -> Result<StagedFile> {
+        let io_error =
+            |e: std::io::Error| AppError::InternalError(format!("Failed to stage upload: {}", e));
+        let mut file = tokio::fs::File::create(path).await.map_err(io_error)?;
+        let mut hasher = Sha256::new();
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        let mut size = 0u64;
+
+        pin_mut!(body);
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            size += chunk.len() as u64;
+            if size > self.max_bytes {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "Files are limited to {} bytes",
+                    self.max_bytes
+                )));
+            }
+            let wanted = SNIFF_BYTES.saturating_sub(head.len()).min(chunk.len());
+            head.extend_from_slice(&chunk[..wanted]);
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(io_error)?;
+        }
+        file.flush().await.map_err(io_error)?;
+
+        if size == 0 {
+            return Err(AppError::BadRequest("The file is empty".to_string()));
+        }
+        let content_type = sniff_content_type(&head);
+        if !self.allowed_content_types.iter().any(|t| t == content_type) {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Files of type {} are not accepted",
+                content_type
+            )));
+        }
+
+        Ok(StagedFile {
+            path: path.to_path_buf(),
+            size,
+            sha256: format!("{:x}", hasher.finalize()),
+            content_type: content_type.to_string(),
+        })
+    }
+
+    async fn save(&self, owner_id: Uuid, filename: &str, staged: &StagedFile) -> Result<Upload> {
+        let id = Uuid::new_v4();
+        let upload = Upload {
+            id,
+            owner_id,
+            storage_key: format!("{}_{}", id, crate::utils::sanitize_filename(filename)),
+            original_filename: filename.to_string(),
+            content_type: staged.content_type.clone(),
+            size: staged.size,
+            sha256: staged.sha256.clone(),
+            created_at: Utc::now(),
+        };
+        self.store.put(&upload.storage_key, staged).await?;
+
+        let inserted = sqlx::query(
+            "INSERT INTO uploads (id, owner_id, storage_key, original_filename, content_type, \
+             size, sha256, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(upload.id.to_string())
+        .bind(upload.owner_id.to_string())
+        .bind(&upload.storage_key)
+        .bind(&upload.original_filename)
+        .bind(&upload.content_type)
+        .bind(upload.size as i64)
+        .bind(&upload.sha256)
+        .bind(upload.created_at.to_rfc3339())
+        .execute(&self.db.pool)
+        .await;
+        if let Err(e) = inserted {
+            let _ = self.store.delete(&upload.storage_key).await;
+            return Err(e.into());
+        }
+
+        Ok(upload)
+    }
+}
+
+/// The type a file's leading bytes identify, whatever its name or the
+/// client claims.
+pub fn sniff_content_type(head: &[u8]) -> &'static str {
+    match head {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        _ if is_text(head) => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// UTF-8 without control characters other than whitespace. The sample may
+/// end part way through a character.
+fn is_text(head: &[u8]) -> bool {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheManager;
+    use crate::services::user_service::UserService;
+    use crate::storage::LocalStore;
+    use futures::TryStreamExt;
+
+    fn chunks(parts: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes>> {
+        futures::stream::iter(
+            parts
+                .iter()
+                .map(|p| Ok(Bytes::from_static(p)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stores_sniffed_files_and_rejects_the_rest() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let owner = UserService::new(db.clone(), Arc::new(CacheManager::new()))
+            .create_user_with_password(
+                "uploader@example.com".to_string(),
+                "uploader".to_string(),
+                "hash".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let root = std::env::temp_dir().join(format!("uploads-{}", Uuid::new_v4()));
+        let uploads = UploadService::new(
+            db,
+            Arc::new(LocalStore::new(&root)),
+            &UploadConfig::default(),
+            64,
+        );
+
+        let png: &[&'static [u8]] = &[b"\x89PNG\r\n\x1a\n", b"IHDR-and-the-rest"];
+        let upload = uploads
+            .store(owner.id, "../my photo.png", chunks(png))
+            .await
+            .unwrap();
+        assert_eq!(upload.content_type, "image/png");
+        assert_eq!(upload.size, 25);
+        assert_eq!(upload.sha256, format!("{:x}", Sha256::digest(png.concat())));
+        assert!(upload.storage_key.ends_with("_.._my_photo.png"));
+
+        let stored = uploads.get(upload.id).await.unwrap();
+        let range = Some(ByteRange { start: 8, end: 11 });
+        let part: Vec<Bytes> = uploads
+            .open(&stored, range)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(part.concat(), b"IHDR");
+
+        // Named as an image, but plain text underneath
+        assert!(matches!(
+            uploads
+                .store(owner.id, "fake.png", chunks(&[b"hello"]))
+                .await,
+            Err(AppError::UnsupportedMediaType(_))
+        ));
+        assert!(matches!(
+            uploads
+                .store(owner.id, "big.png", chunks(&[&[0x89; 40], &[0; 40]]))
+                .await,
+            Err(AppError::PayloadTooLarge(_))
+        ));
+        assert!(matches!(
+            uploads.store(owner.id, "empty.png", chunks(&[])).await,
+            Err(AppError::BadRequest(_))
+        ));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
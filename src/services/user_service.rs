@@ -1,46 +1,124 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
     cache::{cache_key, CacheManager},
-    database::Database,
-    error::Result,
+    database::{map_unique_violation, parse_timestamp, parse_uuid, Database, Nullable},
+    error::{AppError, Result},
     models::{PaginationParams, UpdateUserRequest, User, UserRole, UserStatus},
 };
 
+const USER_COLUMNS: &str = "id, email, username, password_hash, first_name, last_name, \
+     avatar_url, bio, role, status, email_verified, created_at, updated_at, last_login_at";
+
 pub struct UserService {
     db: Arc<Database>,
     cache: Arc<CacheManager>,
 }
 
+/// Raw `users` row; ids, enums and timestamps are stored as TEXT and flags as 0/1.
+#[derive(Debug, sqlx::FromRow)]
+struct UserRow {
+    id: String,
+    email: String,
+    username: String,
+    password_hash: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    first_name: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    last_name: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    avatar_url: Option<String>,
+    #[sqlx(try_from = "Nullable<String>")]
+    bio: Option<String>,
+    role: String,
+    status: String,
+    email_verified: i64,
+    created_at: String,
+    updated_at: String,
+    #[sqlx(try_from = "Nullable<String>")]
+    last_login_at: Option<String>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = AppError;
+
+    fn try_from(row: UserRow) -> Result<Self> {
+        Ok(User::builder()
+            .id(parse_uuid(&row.id)?)
+            .email(row.email)
+            .username(row.username)
+            .password_hash(row.password_hash)
+            .first_name(row.first_name)
+            .last_name(row.last_name)
+            .avatar_url(row.avatar_url)
+            .bio(row.bio)
+            .role(row.role.parse::<UserRole>().map_err(AppError::InternalError)?)
+            .status(row.status.parse::<UserStatus>().map_err(AppError::InternalError)?)
+            .email_verified(row.email_verified != 0)
+            .created_at(parse_timestamp(&row.created_at)?)
+            .updated_at(parse_timestamp(&row.updated_at)?)
+            .last_login_at(row.last_login_at.as_deref().map(parse_timestamp).transpose()?)
+            .build())
+    }
+}
+
 impl UserService {
     pub fn new(db: Arc<Database>, cache: Arc<CacheManager>) -> Self {
         Self { db, cache }
     }
 
     pub async fn list_users(&self, pagination: &PaginationParams) -> Result<(Vec<User>, i64)> {
-        let offset = (pagination.page - 1) * pagination.per_page;
-        
-        // This is a simplified query - in production would use proper pagination
-        let users: Vec<User> = Vec::new(); // sqlx::query_as would be used here
-        let total: i64 = 0;
+        let per_page = pagination.per_page.clamp(1, 100);
+        let offset = (pagination.page.max(1) - 1) * per_page;
+
+        let sort_column = match pagination.sort_by.as_deref() {
+            Some("email") => "email",
+            Some("username") => "username",
+            Some("updated_at") => "updated_at",
+            Some("last_login_at") => "last_login_at",
+            _ => "created_at",
+        };
+        let sort_order = match pagination.sort_order.as_deref() {
+            Some(order) if order.eq_ignore_ascii_case("asc") => "ASC",
+            _ => "DESC",
+        };
+
+        let query = format!(
+            "SELECT {} FROM users ORDER BY {} {} LIMIT $1 OFFSET $2",
+            USER_COLUMNS, sort_column, sort_order
+        );
+        let rows: Vec<UserRow> = sqlx::query_as(&query)
+            .bind(per_page as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.db.pool)
+            .await?;
+
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.db.pool)
+            .await?;
+
+        let users = rows
+            .into_iter()
+            .map(User::try_from)
+            .collect::<Result<Vec<_>>>()?;
 
         Ok((users, total))
     }
 
     pub async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>> {
         let cache_key = cache_key("user", &[&id.to_string()]);
-        
-        if let Some(user) = self.cache.get_json::<User>(&cache_key).await {
-            return Ok(Some(user));
+
+        if let Some(cached) = self.cache.get_json::<User>(&cache_key).await {
+            return Ok(Some(cached));
         }
 
-        // Query database (simplified)
-        let user: Option<User> = None;
+        let user = self.fetch_one("id", &id.to_string()).await?;
 
         if let Some(ref u) = user {
-            let _ = self.cache.set_json(cache_key, u).await;
+            self.cache_user(u).await;
         }
 
         Ok(user)
@@ -48,36 +126,70 @@ impl UserService {
 
     pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
         let cache_key = cache_key("user:email", &[email]);
-        
-        if let Some(user) = self.cache.get_json::<User>(&cache_key).await {
-            return Ok(Some(user));
+
+        if let Some(cached) = self.cache.get_json::<User>(&cache_key).await {
+            return Ok(Some(cached));
         }
 
-        // Query database (simplified)
-        let user: Option<User> = None;
+        let user = self.fetch_one("email", email).await?;
+
+        if let Some(ref u) = user {
+            self.cache_user(u).await;
+        }
 
         Ok(user)
     }
 
+    /// The user with their password hash, always read from the database;
+    /// users from the other lookups may come from the cache without it.
+    pub async fn get_user_for_login(&self, email: &str) -> Result<Option<User>> {
+        self.fetch_one("email", email).await
+    }
+
     pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
         let cache_key = cache_key("user:username", &[username]);
-        
-        if let Some(user) = self.cache.get_json::<User>(&cache_key).await {
-            return Ok(Some(user));
+
+        if let Some(cached) = self.cache.get_json::<User>(&cache_key).await {
+            return Ok(Some(cached));
         }
 
-        // Query database (simplified)
-        let user: Option<User> = None;
+        let user = self.fetch_one("username", username).await?;
+
+        if let Some(ref u) = user {
+            self.cache_user(u).await;
+        }
 
         Ok(user)
     }
 
     pub async fn create_user(&self, user: User) -> Result<User> {
-        // Insert into database (simplified)
-        let _ = &self.db.pool;
+        sqlx::query(
+            r#"
+            INSERT INTO users (
+                id, email, username, password_hash, first_name, last_name, avatar_url, bio,
+                role, status, email_verified, created_at, updated_at, last_login_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(&user.email)
+        .bind(&user.username)
+        .bind(&user.password_hash)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.avatar_url)
+        .bind(&user.bio)
+        .bind(user.role.as_str())
+        .bind(user.status.as_str())
+        .bind(user.email_verified as i64)
+        .bind(user.created_at.to_rfc3339())
+        .bind(user.updated_at.to_rfc3339())
+        .bind(user.last_login_at.map(|t| t.to_rfc3339()))
+        .execute(&self.db.pool)
+        .await
+        .map_err(|e| map_unique_violation(e, "Email or username already in use"))?;
 
-        let cache_key = cache_key("user", &[&user.id.to_string()]);
-        let _ = self.cache.set_json(cache_key, &user).await;
+        self.cache_user(&user).await;
 
         Ok(user)
     }
@@ -112,40 +224,250 @@ impl UserService {
     }
 
     pub async fn update_user(&self, id: Uuid, request: UpdateUserRequest) -> Result<User> {
-        // Get existing user and update fields
-        let now = Utc::now();
-        let user = User::builder()
-            .id(id)
-            .email(request.email.unwrap_or_default())
-            .username(request.username.unwrap_or_default())
-            .password_hash(String::new())
-            .first_name(request.first_name)
-            .last_name(request.last_name)
-            .avatar_url(request.avatar_url)
-            .bio(request.bio)
-            .role(UserRole::User)
-            .status(UserStatus::Active)
-            .email_verified(true)
-            .created_at(now)
-            .updated_at(now)
-            .last_login_at(None)
-            .build();
+        let existing = self
+            .fetch_one("id", &id.to_string())
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("User {} not found", id)))?;
 
-        // Invalidate cache
-        let cache_key = cache_key("user", &[&id.to_string()]);
-        self.cache.delete(&cache_key).await;
+        // Invalidate under the old email/username before they change
+        self.invalidate_user(&existing).await;
+
+        let mut user = existing;
+        if let Some(email) = request.email {
+            user.email = email;
+        }
+        if let Some(username) = request.username {
+            user.username = username;
+        }
+        if request.first_name.is_some() {
+            user.first_name = request.first_name;
+        }
+        if request.last_name.is_some() {
+            user.last_name = request.last_name;
+        }
+        if request.avatar_url.is_some() {
+            user.avatar_url = request.avatar_url;
+        }
+        if request.bio.is_some() {
+            user.bio = request.bio;
+        }
+        user.updated_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET email = $1, username = $2, first_name = $3, last_name = $4,
+                avatar_url = $5, bio = $6, updated_at = $7
+            WHERE id = $8
+            "#,
+        )
+        .bind(&user.email)
+        .bind(&user.username)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(&user.avatar_url)
+        .bind(&user.bio)
+        .bind(user.updated_at.to_rfc3339())
+        .bind(id.to_string())
+        .execute(&self.db.pool)
+        .await
+        .map_err(|e| map_unique_violation(e, "Email or username already in use"))?;
+
+        self.cache_user(&user).await;
 
         Ok(user)
     }
 
+    pub async fn update_role(&self, id: Uuid, role: UserRole) -> Result<()> {
+        self.update_column(id, "role", role.as_str()).await
+    }
+
+    pub async fn update_status(&self, id: Uuid, status: UserStatus) -> Result<()> {
+        self.update_column(id, "status", status.as_str()).await
+    }
+
+    pub async fn update_password_hash(&self, id: Uuid, password_hash: &str) -> Result<()> {
+        self.update_column(id, "password_hash", password_hash).await
+    }
+
+    pub async fn record_login(&self, id: Uuid) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.update_column(id, "last_login_at", &now).await
+    }
+
     pub async fn delete_user(&self, id: Uuid) -> Result<()> {
-        // Delete from database (simplified)
-        let _ = &self.db.pool;
+        let existing = self.fetch_one("id", &id.to_string()).await?;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id.to_string())
+            .execute(&self.db.pool)
+            .await?;
 
         // Invalidate cache
-        let cache_key = cache_key("user", &[&id.to_string()]);
-        self.cache.delete(&cache_key).await;
+        match existing {
+            Some(user) => self.invalidate_user(&user).await,
+            None => {
+                let cache_key = cache_key("user", &[&id.to_string()]);
+                self.cache.delete(&cache_key).await;
+            }
+        }
 
         Ok(())
     }
+
+    async fn fetch_one(&self, column: &str, value: &str) -> Result<Option<User>> {
+        let query = format!("SELECT {} FROM users WHERE {} = $1", USER_COLUMNS, column);
+        let row: Option<UserRow> = sqlx::query_as(&query)
+            .bind(value)
+            .fetch_optional(&self.db.pool)
+            .await?;
+
+        row.map(User::try_from).transpose()
+    }
+
+    async fn update_column(&self, id: Uuid, column: &str, value: &str) -> Result<()> {
+        let existing = self
+            .fetch_one("id", &id.to_string())
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("User {} not found", id)))?;
+
+        let query = format!(
+            "UPDATE users SET {} = $1, updated_at = $2 WHERE id = $3",
+            column
+        );
+        sqlx::query(&query)
+            .bind(value)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id.to_string())
+            .execute(&self.db.pool)
+            .await?;
+
+        self.invalidate_user(&existing).await;
+
+        Ok(())
+    }
+
+    /// `User` skips `password_hash` when serialized, so the hash never
+    /// reaches the cache and cached users come back without it.
+    async fn cache_user(&self, user: &User) {
+        let keys = [
+            cache_key("user", &[&user.id.to_string()]),
+            cache_key("user:email", &[&user.email]),
+            cache_key("user:username", &[&user.username]),
+        ];
+        for key in keys {
+            let _ = self.cache.set_json(key, user).await;
+        }
+    }
+
+    async fn invalidate_user(&self, user: &User) {
+        self.cache
+            .delete(&cache_key("user", &[&user.id.to_string()]))
+            .await;
+        self.cache
+            .delete(&cache_key("user:email", &[&user.email]))
+            .await;
+        self.cache
+            .delete(&cache_key("user:username", &[&user.username]))
+            .await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn service() -> UserService {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        UserService::new(db, Arc::new(CacheManager::new()))
+    }
+
+    #[tokio::test]
+    async fn test_user_crud_roundtrip() {
+        let service = service().await;
+
+        let created = service
+            .create_user_with_password(
+                "jane@example.com".to_string(),
+                "jane".to_string(),
+                "hash".to_string(),
+                Some("Jane".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // A fresh cache must still find the row in the database
+        let uncached = UserService::new(service.db.clone(), Arc::new(CacheManager::new()));
+        let by_email = uncached
+            .get_user_by_email("jane@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(by_email.id, created.id);
+        assert_eq!(by_email.password_hash, "hash");
+        assert_eq!(by_email.role, UserRole::User);
+
+        // The hash stays out of the cache; only the login lookup returns it
+        let cached = service.get_user_by_id(created.id).await.unwrap().unwrap();
+        assert!(cached.password_hash.is_empty());
+        let login = service
+            .get_user_for_login("jane@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(login.password_hash, "hash");
+
+        let updated = service
+            .update_user(
+                created.id,
+                UpdateUserRequest::builder()
+                    .email(Some("jane.doe@example.com".to_string()))
+                    .username(None)
+                    .first_name(None)
+                    .last_name(Some("Doe".to_string()))
+                    .avatar_url(None)
+                    .bio(None)
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.first_name.as_deref(), Some("Jane"));
+        assert!(service
+            .get_user_by_email("jane@example.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        let (users, total) = service
+            .list_users(&PaginationParams::default())
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(users[0].email, "jane.doe@example.com");
+
+        service.delete_user(created.id).await.unwrap();
+        assert!(service.get_user_by_id(created.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_email_is_conflict() {
+        let service = service().await;
+
+        for username in ["first", "second"] {
+            let result = service
+                .create_user_with_password(
+                    "dup@example.com".to_string(),
+                    username.to_string(),
+                    "hash".to_string(),
+                    None,
+                    None,
+                )
+                .await;
+            if username == "second" {
+                assert!(matches!(result, Err(AppError::Conflict(_))));
+            }
+        }
+    }
 }
@@ -0,0 +1,540 @@
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    cache::{cache_key, CacheManager},
+    config::PaymentConfig,
+    database::Database,
+    error::{AppError, Result},
+    payment_gateway::{GatewayOutcome, MockGateway, PaymentGateway},
+    services::payment_service::{
+        apply_intent_event, apply_refund_event, PaymentIntentStatus, RefundStatus,
+    },
+    utils::{constant_time_eq, hmac_sha256},
+};
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`; several `v1`
+/// entries are accepted while a secret is being rotated.
+pub const SIGNATURE_HEADER: &str = "payment-signature";
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("Payment webhooks are not configured")]
+    NotConfigured,
+    #[error("Missing Payment-Signature header")]
+    MissingSignature,
+    #[error("Malformed Payment-Signature header")]
+    MalformedSignature,
+    #[error("Signature timestamp is outside the tolerance")]
+    StaleTimestamp,
+    #[error("Signature does not match")]
+    SignatureMismatch,
+}
+
+impl From<WebhookError> for AppError {
+    fn from(error: WebhookError) -> Self {
+        match error {
+            WebhookError::NotConfigured => AppError::ServiceUnavailable(error.to_string()),
+            WebhookError::MalformedSignature => AppError::BadRequest(error.to_string()),
+            _ => AppError::AuthenticationError(error.to_string()),
+        }
+    }
+}
+
+/// A gateway notification. `data` depends on `event_type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct IntentEventData {
+    reference: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    next_action_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefundEventData {
+    reference: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    Processed,
+    /// Stored for replay once its type is supported
+    Unhandled,
+    /// Already received; nothing was done
+    Duplicate,
+}
+
+impl EventStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventStatus::Processed => "processed",
+            EventStatus::Unhandled => "unhandled",
+            EventStatus::Duplicate => "duplicate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookReceipt {
+    pub event_id: String,
+    pub status: EventStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    pub unhandled: usize,
+    pub failed: usize,
+}
+
+/// Verifies, de-duplicates and applies payment gateway events.
+pub struct WebhookService {
+    db: Arc<Database>,
+    cache: Arc<CacheManager>,
+    config: PaymentConfig,
+    gateway: Arc<dyn PaymentGateway>,
+}
+
+impl WebhookService {
+    pub fn new(db: Arc<Database>, cache: Arc<CacheManager>, config: &PaymentConfig) -> Self {
+        Self {
+            db,
+            cache,
+            config: config.clone(),
+            gateway: Arc::new(MockGateway::new()),
+        }
+    }
+
+    pub fn with_gateway(mut self, gateway: Arc<dyn PaymentGateway>) -> Self {
+        self.gateway = gateway;
+        self
+    }
+
+    /// Applies a signed event exactly once. The event row and its effects
+    /// commit together, so an event that fails is retried by the gateway
+    /// rather than half-applied.
+    pub async fn receive(&self, signature: Option<&str>, payload: &[u8]) -> Result<WebhookReceipt> {
+        if self.config.webhook_secret.is_empty() {
+            return Err(WebhookError::NotConfigured.into());
+        }
+        verify_signature(
+            &self.config.webhook_secret,
+            signature,
+            payload,
+            Utc::now().timestamp(),
+            self.config.webhook_tolerance_seconds,
+        )?;
+
+        let event: PaymentEvent = serde_json::from_slice(payload)
+            .map_err(|e| AppError::BadRequest(format!("Invalid event: {}", e)))?;
+        if event.id.is_empty() {
+            return Err(AppError::BadRequest("Event id is required".to_string()));
+        }
+
+        let gateway = self.gateway.name();
+        let duplicate = WebhookReceipt {
+            event_id: event.id.clone(),
+            status: EventStatus::Duplicate,
+        };
+        let seen: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM webhook_events WHERE gateway = $1 AND event_id = $2",
+        )
+        .bind(gateway)
+        .bind(&event.id)
+        .fetch_optional(&self.db.pool)
+        .await?;
+        if seen.is_some() {
+            return Ok(duplicate);
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.db.pool.begin().await?;
+        let (status, order_id) = dispatch(&mut tx, gateway, &event, &now).await?;
+
+        // A concurrent delivery of the same event loses here and rolls back
+        let inserted = sqlx::query(
+            "INSERT INTO webhook_events (id, gateway, event_id, event_type, payload, status, \
+             received_at, processed_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(gateway)
+        .bind(&event.id)
+        .bind(&event.event_type)
+        .bind(String::from_utf8_lossy(payload).into_owned())
+        .bind(status.as_str())
+        .bind(&now)
+        .bind((status == EventStatus::Processed).then_some(&now))
+        .execute(&mut *tx)
+        .await;
+        match inserted {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => return Ok(duplicate),
+            inserted => inserted?,
+        };
+        tx.commit().await?;
+        self.forget_order(order_id).await;
+
+        tracing::info!(
+            event_id = %event.id,
+            event_type = %event.event_type,
+            status = status.as_str(),
+            "Payment webhook received"
+        );
+
+        Ok(WebhookReceipt {
+            event_id: event.id,
+            status,
+        })
+    }
+
+    /// Re-dispatches stored events whose type was not supported when they
+    /// arrived.
+    pub async fn replay_unhandled(&self) -> Result<ReplaySummary> {
+        let gateway = self.gateway.name();
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, payload FROM webhook_events WHERE gateway = $1 AND status = $2 \
+             ORDER BY received_at",
+        )
+        .bind(gateway)
+        .bind(EventStatus::Unhandled.as_str())
+        .fetch_all(&self.db.pool)
+        .await?;
+
+        let mut summary = ReplaySummary::default();
+        for (id, payload) in rows {
+            match self.replay_one(gateway, &id, &payload).await {
+                Ok(EventStatus::Unhandled) => summary.unhandled += 1,
+                Ok(_) => summary.replayed += 1,
+                Err(e) => {
+                    tracing::warn!(webhook_event_id = %id, error = %e, "Webhook replay failed");
+                    summary.failed += 1;
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    async fn replay_one(&self, gateway: &str, id: &str, payload: &str) -> Result<EventStatus> {
+        let event: PaymentEvent = serde_json::from_str(payload)?;
+        let now = Utc::now().to_rfc3339();
+
+        let mut tx = self.db.pool.begin().await?;
+        let (status, order_id) = dispatch(&mut tx, gateway, &event, &now).await?;
+        if status == EventStatus::Unhandled {
+            return Ok(status);
+        }
+        sqlx::query(
+            "UPDATE webhook_events SET status = $1, processed_at = $2 WHERE id = $3 AND status = $4",
+        )
+        .bind(status.as_str())
+        .bind(&now)
+        .bind(id)
+        .bind(EventStatus::Unhandled.as_str())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.forget_order(order_id).await;
+        Ok(status)
+    }
+
+    async fn forget_order(&self, order_id: Option<Uuid>) {
+        if let Some(order_id) = order_id {
+            self.cache
+                .delete(&cache_key("order", &[&order_id.to_string()]))
+                .await;
+        }
+    }
+}
+
+/// Applies a supported event, returning the order it may have changed.
+async fn dispatch(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    gateway: &str,
+    event: &PaymentEvent,
+    now: &str,
+) -> Result<(EventStatus, Option<Uuid>)> {
+    if let Some(status) = intent_status_for(&event.event_type) {
+        let data: IntentEventData = event_data(event)?;
+        let outcome = GatewayOutcome {
+            status,
+            payment_method: None,
+            next_action_url: data.next_action_url,
+            error: data.error,
+        };
+        let order_id = apply_intent_event(tx, gateway, &data.reference, &outcome, now).await?;
+        return Ok((EventStatus::Processed, order_id));
+    }
+    if let Some(status) = refund_status_for(&event.event_type) {
+        let data: RefundEventData = event_data(event)?;
+        let order_id = apply_refund_event(tx, gateway, &data.reference, status, now).await?;
+        return Ok((EventStatus::Processed, order_id));
+    }
+    Ok((EventStatus::Unhandled, None))
+}
+
+fn intent_status_for(event_type: &str) -> Option<PaymentIntentStatus> {
+    match event_type {
+        "payment_intent.processing" => Some(PaymentIntentStatus::Processing),
+        "payment_intent.requires_action" => Some(PaymentIntentStatus::RequiresAction),
+        "payment_intent.succeeded" => Some(PaymentIntentStatus::Succeeded),
+        "payment_intent.payment_failed" => Some(PaymentIntentStatus::Failed),
+        "payment_intent.canceled" => Some(PaymentIntentStatus::Canceled),
+        _ => None,
+    }
+}
+
+fn refund_status_for(event_type: &str) -> Option<RefundStatus> {
+    match event_type {
+        "refund.succeeded" => Some(RefundStatus::Succeeded),
+        "refund.failed" => Some(RefundStatus::Failed),
+        "refund.canceled" => Some(RefundStatus::Canceled),
+        _ => None,
+    }
+}
+
+fn event_data<T: DeserializeOwned>(event: &PaymentEvent) -> Result<T> {
+    serde_json::from_value(event.data.clone()).map_err(|e| {
+        AppError::BadRequest(format!("Invalid data for {}: {}", event.event_type, e))
+    })
+}
+
+/// The header value a gateway sends for `payload` at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+    format!("t={},v1={}", timestamp, signature(secret, timestamp, payload))
+}
+
+pub fn verify_signature(
+    secret: &str,
+    header: Option<&str>,
+    payload: &[u8],
+    now: i64,
+    tolerance_seconds: u64,
+) -> std::result::Result<(), WebhookError> {
+    let header = header.ok_or(WebhookError::MissingSignature)?;
+
+    let mut timestamp = None;
+    let mut candidates = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => {
+                timestamp = Some(
+                    value
+                        .parse::<i64>()
+                        .map_err(|_| WebhookError::MalformedSignature)?,
+                )
+            }
+            Some(("v1", value)) => candidates.push(value),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or(WebhookError::MalformedSignature)?;
+    if candidates.is_empty() {
+        return Err(WebhookError::MalformedSignature);
+    }
+    if now.abs_diff(timestamp) > tolerance_seconds {
+        return Err(WebhookError::StaleTimestamp);
+    }
+
+    let expected = signature(secret, timestamp, payload);
+    if candidates
+        .iter()
+        .any(|candidate| constant_time_eq(candidate.as_bytes(), expected.as_bytes()))
+    {
+        Ok(())
+    } else {
+        Err(WebhookError::SignatureMismatch)
+    }
+}
+
+fn signature(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(payload);
+    format!("{:x}", hmac_sha256(secret.as_bytes(), &message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        AddressRequest, CreateOrderItemRequest, CreateOrderRequest, PaymentStatus,
+    };
+    use crate::payment_gateway::test_cards;
+    use crate::services::{
+        order_service::OrderService,
+        payment_service::{CardInput, PaymentMethodRequest, PaymentMethodType, PaymentService},
+        user_service::UserService,
+    };
+
+    const SECRET: &str = "whsec_test";
+
+    #[test]
+    fn test_signature_verification() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            format!("{:x}", hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let body = br#"{"id":"evt_1"}"#;
+        let header = sign(SECRET, 1_000, body);
+        assert!(verify_signature(SECRET, Some(&header), body, 1_100, 300).is_ok());
+
+        let rotated = format!("{},v1={}", sign("whsec_old", 1_000, body), &header[header.len() - 64..]);
+        assert!(verify_signature(SECRET, Some(&rotated), body, 1_000, 300).is_ok());
+
+        assert!(matches!(
+            verify_signature(SECRET, Some(&header), br#"{"id":"evt_2"}"#, 1_000, 300),
+            Err(WebhookError::SignatureMismatch)
+        ));
+        assert!(matches!(
+            verify_signature(SECRET, Some(&header), body, 1_301, 300),
+            Err(WebhookError::StaleTimestamp)
+        ));
+        assert!(matches!(
+            verify_signature(SECRET, Some("v1=abc"), body, 1_000, 300),
+            Err(WebhookError::MalformedSignature)
+        ));
+        assert!(matches!(
+            verify_signature(SECRET, None, body, 1_000, 300),
+            Err(WebhookError::MissingSignature)
+        ));
+    }
+
+    fn event(id: &str, event_type: &str, reference: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "id": id,
+            "type": event_type,
+            "data": {"reference": reference},
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_apply_once_and_unknown_types_are_kept() {
+        let db = Arc::new(Database::in_memory().await.unwrap());
+        let cache = Arc::new(CacheManager::new());
+        let gateway: Arc<dyn PaymentGateway> = Arc::new(MockGateway::new());
+        let customer = UserService::new(db.clone(), cache.clone())
+            .create_user_with_password(
+                "hook@example.com".to_string(),
+                "hook".to_string(),
+                "hash".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let product_id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO products (id, sku, name, slug, description, price, quantity, \
+             created_at, updated_at) VALUES ($1, 'MUG', 'Mug', 'mug', '', 1200, 5, $2, $3)",
+        )
+        .bind(product_id.to_string())
+        .bind(&now)
+        .bind(&now)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        let address = AddressRequest {
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            company: None,
+            address_line_1: "1 Main St".to_string(),
+            address_line_2: None,
+            city: "London".to_string(),
+            state: None,
+            postal_code: "N1 9GU".to_string(),
+            country: "GB".to_string(),
+            phone: None,
+        };
+        let orders = OrderService::new(db.clone(), cache.clone());
+        let order = orders
+            .create_order(
+                CreateOrderRequest::builder()
+                    .customer_id(customer.id)
+                    .items(vec![CreateOrderItemRequest {
+                        product_id,
+                        variant_id: None,
+                        quantity: 1,
+                    }])
+                    .billing_address(address.clone())
+                    .shipping_address(address)
+                    .shipping_method(None)
+                    .notes(None)
+                    .coupon_code(None)
+                    .currency(None)
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        let payments = PaymentService::new(db.clone(), cache.clone()).with_gateway(gateway.clone());
+        let intent = payments.create_payment_intent(order.id).await.unwrap();
+        payments
+            .confirm_payment(
+                intent.id,
+                PaymentMethodRequest {
+                    payment_type: PaymentMethodType::Card,
+                    card: Some(CardInput {
+                        number: test_cards::REQUIRES_ACTION.to_string(),
+                        exp_month: 1,
+                        exp_year: 2099,
+                    }),
+                    billing_details: None,
+                },
+            )
+            .await
+            .unwrap();
+        let reference: String =
+            sqlx::query_scalar("SELECT gateway_reference FROM payment_intents WHERE id = $1")
+                .bind(intent.id.to_string())
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+
+        let config = PaymentConfig::builder().webhook_secret(SECRET.to_string()).build();
+        let webhooks = WebhookService::new(db.clone(), cache.clone(), &config).with_gateway(gateway);
+        let deliver = |body: Vec<u8>| {
+            let header = sign(SECRET, Utc::now().timestamp(), &body);
+            let webhooks = &webhooks;
+            async move { webhooks.receive(Some(&header), &body).await }
+        };
+
+        let succeeded = event("evt_1", "payment_intent.succeeded", &reference);
+        assert_eq!(deliver(succeeded.clone()).await.unwrap().status, EventStatus::Processed);
+        assert_eq!(deliver(succeeded).await.unwrap().status, EventStatus::Duplicate);
+
+        let paid = payments.get_payment_intent(intent.id).await.unwrap().unwrap();
+        assert_eq!(paid.status, PaymentIntentStatus::Succeeded);
+        let order = orders.get_order_by_id(order.id).await.unwrap().unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Paid);
+
+        // A late failure cannot undo a finished payment
+        let late = event("evt_2", "payment_intent.payment_failed", &reference);
+        assert_eq!(deliver(late).await.unwrap().status, EventStatus::Processed);
+        let still_paid = payments.get_payment_intent(intent.id).await.unwrap().unwrap();
+        assert_eq!(still_paid.status, PaymentIntentStatus::Succeeded);
+
+        let dispute = event("evt_3", "charge.dispute.created", &reference);
+        assert_eq!(deliver(dispute).await.unwrap().status, EventStatus::Unhandled);
+        let summary = webhooks.replay_unhandled().await.unwrap();
+        assert_eq!((summary.replayed, summary.unhandled, summary.failed), (0, 1, 0));
+
+        let unsigned = webhooks.receive(None, &event("evt_4", "refund.succeeded", "x")).await;
+        assert!(matches!(unsigned, Err(AppError::AuthenticationError(_))));
+    }
+}
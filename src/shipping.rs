@@ -0,0 +1,319 @@
+use rust_decimal::Decimal;
+
+use crate::config::{ShippingConfig, ShippingMethodConfig};
+use crate::error::{AppError, Result};
+use crate::models::ProductDimensions;
+use crate::money::Money;
+
+/// Zone for destinations no configured zone lists.
+pub const DEFAULT_ZONE: &str = "world";
+
+/// A cart line as far as shipping is concerned. Weights are in kilograms.
+#[derive(Debug, Clone)]
+pub struct ShippingItem {
+    pub weight: Decimal,
+    pub dimensions: Option<ProductDimensions>,
+    pub quantity: u32,
+    pub is_digital: bool,
+}
+
+impl ShippingItem {
+    /// Builds an item from the `weight` and JSON `dimensions` product columns.
+    /// A missing weight counts as weightless; one that isn't a finite number
+    /// is an error rather than silently shipping at the cheapest tier.
+    pub fn from_columns(
+        weight: Option<f64>,
+        dimensions: Option<&str>,
+        quantity: u32,
+        is_digital: bool,
+    ) -> Result<Self> {
+        let weight = match weight {
+            Some(w) => Decimal::try_from(w).map_err(|_| {
+                AppError::ValidationError(format!("Invalid product weight: {}", w))
+            })?,
+            None => Decimal::ZERO,
+        };
+        Ok(Self {
+            weight,
+            dimensions: dimensions.and_then(|d| serde_json::from_str(d).ok()),
+            quantity,
+            is_digital,
+        })
+    }
+
+    /// Volume in cubic centimetres, when the dimensions use a known unit.
+    fn volume_cm3(&self) -> Option<Decimal> {
+        let dimensions = self.dimensions.as_ref()?;
+        let to_cm = match dimensions.unit.to_ascii_lowercase().as_str() {
+            "mm" => Decimal::new(1, 1),
+            "cm" => Decimal::ONE,
+            "m" => Decimal::ONE_HUNDRED,
+            "in" => Decimal::new(254, 2),
+            _ => return None,
+        };
+        let side = |value: f64| Decimal::try_from(value).ok().map(|v| v * to_cm);
+        Some(side(dimensions.length)? * side(dimensions.width)? * side(dimensions.height)?)
+    }
+}
+
+/// A priced way to ship a cart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShippingRate {
+    /// `carrier:method`, the value orders pass as `shipping_method`
+    pub id: String,
+    pub carrier: String,
+    pub method: String,
+    pub name: String,
+    pub amount: Money,
+    pub min_days: u32,
+    pub max_days: u32,
+}
+
+/// Carrier rate tables from `ShippingConfig`.
+pub struct ShippingRates {
+    config: ShippingConfig,
+}
+
+impl ShippingRates {
+    pub fn new(config: &ShippingConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// The currency the rate tables are priced in.
+    pub fn currency(&self) -> &str {
+        &self.config.currency
+    }
+
+    pub fn zone_for(&self, country: &str) -> &str {
+        self.config
+            .zones
+            .iter()
+            .find(|zone| zone.countries.iter().any(|c| c.eq_ignore_ascii_case(country)))
+            .map(|zone| zone.code.as_str())
+            .unwrap_or(DEFAULT_ZONE)
+    }
+
+    /// The greater of actual and volumetric weight of the physical items,
+    /// shipped as one parcel.
+    pub fn billable_weight(&self, items: &[ShippingItem]) -> Decimal {
+        let physical = || items.iter().filter(|item| !item.is_digital);
+        let actual: Decimal = physical()
+            .map(|item| item.weight * Decimal::from(item.quantity))
+            .sum();
+        let volume: Decimal = physical()
+            .filter_map(|item| Some(item.volume_cm3()? * Decimal::from(item.quantity)))
+            .sum();
+
+        let divisor = self.config.volumetric_divisor;
+        let volumetric = if divisor > Decimal::ZERO {
+            volume / divisor
+        } else {
+            Decimal::ZERO
+        };
+        actual.max(volumetric)
+    }
+
+    /// Every method that serves the destination and weight, cheapest first,
+    /// priced in the order's currency. `rate` is the units of that currency
+    /// one unit of [`Self::currency`] buys. Empty when nothing needs shipping.
+    pub fn quote(
+        &self,
+        country: &str,
+        items: &[ShippingItem],
+        order_value: &Money,
+        rate: Decimal,
+    ) -> Vec<ShippingRate> {
+        if items.iter().all(|item| item.is_digital) {
+            return Vec::new();
+        }
+
+        let zone = self.zone_for(country);
+        let weight = self.billable_weight(items);
+
+        let mut rates: Vec<ShippingRate> = self
+            .config
+            .carriers
+            .iter()
+            .flat_map(|carrier| carrier.methods.iter().map(move |method| (carrier, method)))
+            .filter_map(|(carrier, method)| {
+                let amount = price(method, zone, weight, order_value.amount, rate)?;
+                Some(ShippingRate {
+                    id: format!("{}:{}", carrier.code, method.code),
+                    carrier: carrier.name.clone(),
+                    method: method.code.clone(),
+                    name: method.name.clone(),
+                    amount: Money::new(amount, order_value.currency.clone()).round(),
+                    min_days: method.min_days,
+                    max_days: method.max_days,
+                })
+            })
+            .collect();
+
+        rates.sort_by(|a, b| {
+            a.amount
+                .amount
+                .cmp(&b.amount.amount)
+                .then(a.max_days.cmp(&b.max_days))
+        });
+        rates
+    }
+}
+
+/// `None` when the method doesn't ship to the zone or the weight exceeds
+/// its heaviest tier. Prices and thresholds are converted by `rate` into the
+/// currency of `order_value`.
+fn price(
+    method: &ShippingMethodConfig,
+    zone: &str,
+    weight: Decimal,
+    order_value: Decimal,
+    rate: Decimal,
+) -> Option<Decimal> {
+    let zone_rate = method.rates.iter().find(|rate| rate.zone == zone)?;
+    let tier = zone_rate
+        .tiers
+        .iter()
+        .filter(|tier| tier.max_weight.is_none_or(|max| weight <= max))
+        .min_by_key(|tier| tier.max_weight.unwrap_or(Decimal::MAX))?;
+
+    if zone_rate.free_over.is_some_and(|threshold| order_value >= threshold * rate) {
+        return Some(Decimal::ZERO);
+    }
+    Some(tier.price * rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CarrierConfig, ShippingZone, WeightTier, ZoneRate};
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn usd(value: &str) -> Money {
+        Money::new(dec(value), "USD")
+    }
+
+    fn tier(max_weight: Option<&str>, price: &str) -> WeightTier {
+        WeightTier {
+            max_weight: max_weight.map(dec),
+            price: dec(price),
+        }
+    }
+
+    fn rates() -> ShippingRates {
+        ShippingRates::new(
+            &ShippingConfig::builder()
+                .zones(vec![ShippingZone {
+                    code: "domestic".to_string(),
+                    countries: vec!["US".to_string()],
+                }])
+                .carriers(vec![CarrierConfig {
+                    code: "ups".to_string(),
+                    name: "UPS".to_string(),
+                    methods: vec![
+                        ShippingMethodConfig {
+                            code: "ground".to_string(),
+                            name: "Ground".to_string(),
+                            min_days: 3,
+                            max_days: 5,
+                            rates: vec![
+                                ZoneRate {
+                                    zone: "domestic".to_string(),
+                                    tiers: vec![tier(Some("5"), "12.50"), tier(Some("1"), "6.00")],
+                                    free_over: Some(dec("100")),
+                                },
+                                ZoneRate {
+                                    zone: DEFAULT_ZONE.to_string(),
+                                    tiers: vec![tier(None, "40")],
+                                    free_over: None,
+                                },
+                            ],
+                        },
+                        ShippingMethodConfig {
+                            code: "express".to_string(),
+                            name: "Express".to_string(),
+                            min_days: 1,
+                            max_days: 2,
+                            rates: vec![ZoneRate {
+                                zone: "domestic".to_string(),
+                                tiers: vec![tier(Some("2"), "25")],
+                                free_over: None,
+                            }],
+                        },
+                    ],
+                }])
+                .build(),
+        )
+    }
+
+    fn item(weight: &str, dimensions: Option<(f64, &str)>, quantity: u32) -> ShippingItem {
+        ShippingItem {
+            weight: dec(weight),
+            dimensions: dimensions.map(|(side, unit)| ProductDimensions {
+                length: side,
+                width: side,
+                height: side,
+                unit: unit.to_string(),
+            }),
+            quantity,
+            is_digital: false,
+        }
+    }
+
+    #[test]
+    fn test_volumetric_weight_wins_for_bulky_items() {
+        let rates = rates();
+        assert_eq!(rates.billable_weight(&[item("0.5", None, 2)]), dec("1.0"));
+        // 20cm cube: 8000 cm³ / 5000 = 1.6 kg
+        assert_eq!(rates.billable_weight(&[item("0.2", Some((20.0, "cm")), 1)]), dec("1.6"));
+        assert_eq!(rates.billable_weight(&[item("0.2", Some((200.0, "mm")), 1)]), dec("1.6"));
+    }
+
+    #[test]
+    fn test_unconvertible_weight_is_rejected() {
+        assert!(matches!(
+            ShippingItem::from_columns(Some(f64::NAN), None, 1, false),
+            Err(AppError::ValidationError(_))
+        ));
+        let weightless = ShippingItem::from_columns(None, None, 1, false).unwrap();
+        assert_eq!(weightless.weight, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_quote_tiers_zones_and_thresholds() {
+        let rates = rates();
+        let light = [item("0.8", None, 1)];
+
+        let quote = rates.quote("US", &light, &usd("20"), Decimal::ONE);
+        let offered: Vec<(&str, Decimal)> =
+            quote.iter().map(|r| (r.id.as_str(), r.amount.amount)).collect();
+        assert_eq!(offered, vec![("ups:ground", dec("6.00")), ("ups:express", dec("25"))]);
+
+        // Over the express limit and into the next ground tier
+        let heavy = [item("3", None, 1)];
+        let quote = rates.quote("US", &heavy, &usd("20"), Decimal::ONE);
+        assert_eq!(quote.len(), 1);
+        assert_eq!(quote[0].amount.amount, dec("12.50"));
+
+        assert_eq!(rates.quote("US", &heavy, &usd("100"), Decimal::ONE)[0].amount.amount, Decimal::ZERO);
+        assert_eq!(rates.quote("FR", &heavy, &usd("100"), Decimal::ONE)[0].amount.amount, dec("40"));
+        assert!(rates.quote("US", &[item("6", None, 1)], &usd("20"), Decimal::ONE).is_empty());
+
+        // Rates and thresholds are converted into the order's currency
+        let eur = |amount: &str| Money::new(dec(amount), "EUR");
+        let quote = rates.quote("US", &light, &eur("20"), dec("0.8"));
+        assert_eq!(quote[0].amount, eur("4.80"));
+        let quote = rates.quote("US", &heavy, &eur("90"), dec("0.8"));
+        assert_eq!(quote[0].amount.amount, Decimal::ZERO);
+
+        let ebook = ShippingItem {
+            is_digital: true,
+            ..item("0", None, 1)
+        };
+        assert!(rates.quote("US", &[ebook], &usd("20"), Decimal::ONE).is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures::stream::BoxStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::{StorageBackend, UploadConfig};
+
+mod local;
+mod s3;
+
+pub use local::LocalStore;
+pub use s3::S3Store;
+
+/// An object's bytes as they are read.
+pub type ByteStream = BoxStream<'static, std::io::Result<Bytes>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("Object {0} not found")]
+    NotFound(String),
+    #[error("Storage I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Object store error: {0}")]
+    Backend(String),
+}
+
+/// A file received in full and checksummed, waiting on local disk to be
+/// handed to a store.
+#[derive(Debug, Clone)]
+pub struct StagedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the contents
+    pub sha256: String,
+    pub content_type: String,
+}
+
+/// Inclusive byte offsets into an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// Reads a `Range` header against an object of `size` bytes. Only a single
+/// byte range is honoured; anything else, including a malformed header or a
+/// list of ranges, gets the whole object, which RFC 9110 allows.
+pub fn parse_range(header: Option<&str>, size: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((first, last)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (first.trim(), last.trim()) {
+        // The final `n` bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(n) => (size.saturating_sub(n), size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+            _ => return RangeRequest::Full,
+        },
+    };
+    if size == 0 || range.0 >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(ByteRange {
+        start: range.0,
+        end: range.1,
+    })
+}
+
+/// Where uploaded bytes live. Keys are generated by the caller, never taken
+/// from a client, and are flat names rather than paths.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores `file` under `key`, replacing any object there. The staged
+    /// file may be moved into place; callers remove it if it is left over.
+    async fn put(&self, key: &str, file: &StagedFile) -> Result<(), StorageError>;
+
+    /// The object's bytes, or only those in `range`.
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> Result<ByteStream, StorageError>;
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+pub fn from_config(config: &UploadConfig, http_client: reqwest::Client) -> Arc<dyn BlobStore> {
+    match config.backend {
+        StorageBackend::Local => Arc::new(LocalStore::new(&config.directory)),
+        StorageBackend::S3 => Arc::new(S3Store::new(&config.s3, http_client)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let partial = |start, end| RangeRequest::Partial(ByteRange { start, end });
+        assert_eq!(parse_range(None, 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), partial(0, 9));
+        assert_eq!(parse_range(Some("bytes=90-"), 100), partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-500"), 100), partial(0, 99));
+        assert_eq!(parse_range(Some("bytes=50-500"), 100), partial(50, 99));
+        assert_eq!(
+            parse_range(Some("bytes=100-"), 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(Some("bytes=0-0"), 0),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("items=0-9"), 100), RangeRequest::Full);
+    }
+}
@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use super::{BlobStore, ByteRange, ByteStream, StagedFile, StorageError};
+
+/// Keeps each object as a file named by its key under one directory.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let mut components = Path::new(key).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(self.root.join(key)),
+            _ => Err(StorageError::Backend(format!("Invalid key {}", key))),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, file: &StagedFile) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        tokio::fs::create_dir_all(&self.root).await?;
+        if tokio::fs::rename(&file.path, &path).await.is_ok() {
+            return Ok(());
+        }
+
+        // Staging is on another filesystem: copy beside the target, then
+        // rename, so readers never see a partial file
+        let partial = self
+            .root
+            .join(format!(".{}.{}", key, Uuid::new_v4().simple()));
+        if let Err(e) = tokio::fs::copy(&file.path, &partial).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e.into());
+        }
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> Result<ByteStream, StorageError> {
+        let mut file = match tokio::fs::File::open(self.path(key)?).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match range {
+            Some(range) => {
+                file.seek(std::io::SeekFrom::Start(range.start)).await?;
+                Ok(ReaderStream::new(file.take(range.len())).boxed())
+            }
+            None => Ok(ReaderStream::new(file).boxed()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn test_put_get_range_and_delete() {
+        let root = std::env::temp_dir().join(format!("blobs-{}", Uuid::new_v4()));
+        let store = LocalStore::new(&root);
+        let staged = std::env::temp_dir().join(format!("staged-{}", Uuid::new_v4()));
+        tokio::fs::write(&staged, b"0123456789").await.unwrap();
+        let file = StagedFile {
+            path: staged,
+            size: 10,
+            sha256: String::new(),
+            content_type: "text/plain".to_string(),
+        };
+
+        store.put("digits", &file).await.unwrap();
+        let whole: Vec<_> = store
+            .get("digits", None)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(whole.concat(), b"0123456789");
+        let range = Some(ByteRange { start: 3, end: 5 });
+        let part: Vec<_> = store
+            .get("digits", range)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(part.concat(), b"345");
+
+        assert!(store.put("../escape", &file).await.is_err());
+        store.delete("digits").await.unwrap();
+        assert!(matches!(
+            store.get("digits", None).await,
+            Err(StorageError::NotFound(_))
+        ));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,190 @@
+use rust_decimal::Decimal;
+
+use crate::config::{TaxConfig, TaxJurisdiction};
+use crate::models::Address;
+use crate::money::round_amount;
+
+/// One taxable order line, after discounts, in the order's currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaxLine {
+    pub amount: Decimal,
+    pub is_digital: bool,
+}
+
+/// Tax owed per line, in the same order as the input lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaxBreakdown {
+    pub rate: Decimal,
+    /// Whether line amounts already contained the tax
+    pub inclusive: bool,
+    pub lines: Vec<Decimal>,
+}
+
+impl TaxBreakdown {
+    /// Sum of the rounded line taxes, so it always matches the items.
+    pub fn total(&self) -> Decimal {
+        self.lines.iter().sum()
+    }
+}
+
+pub trait TaxCalculator: Send + Sync {
+    /// Line taxes are rounded to `currency`'s minor unit.
+    fn calculate(&self, address: &Address, currency: &str, lines: &[TaxLine]) -> TaxBreakdown;
+}
+
+/// Rates looked up by the most specific matching jurisdiction: postal prefix
+/// over state over country, falling back to `TaxConfig::default_rate`.
+pub struct RateTable {
+    config: TaxConfig,
+}
+
+impl RateTable {
+    pub fn new(config: &TaxConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    pub fn rate_for(&self, address: &Address) -> Decimal {
+        self.config
+            .jurisdictions
+            .iter()
+            .filter_map(|j| specificity(j, address).map(|score| (score, j.rate)))
+            .fold(None, |best: Option<(u8, Decimal)>, (score, rate)| match best {
+                Some((best_score, _)) if best_score >= score => best,
+                _ => Some((score, rate)),
+            })
+            .map(|(_, rate)| rate)
+            .unwrap_or(self.config.default_rate)
+    }
+}
+
+impl TaxCalculator for RateTable {
+    fn calculate(&self, address: &Address, currency: &str, lines: &[TaxLine]) -> TaxBreakdown {
+        let rate = self.rate_for(address);
+        let inclusive = self.config.prices_include_tax;
+
+        let lines = lines
+            .iter()
+            .map(|line| {
+                if line.is_digital && self.config.exempt_digital_goods {
+                    return Decimal::ZERO;
+                }
+                let tax = if inclusive {
+                    line.amount - line.amount / (Decimal::ONE + rate)
+                } else {
+                    line.amount * rate
+                };
+                round_amount(tax, currency)
+            })
+            .collect();
+
+        TaxBreakdown {
+            rate,
+            inclusive,
+            lines,
+        }
+    }
+}
+
+/// `None` when the jurisdiction doesn't cover the address, otherwise how
+/// many levels below the country it matched on.
+fn specificity(jurisdiction: &TaxJurisdiction, address: &Address) -> Option<u8> {
+    if !jurisdiction.country.eq_ignore_ascii_case(&address.country) {
+        return None;
+    }
+    let mut score = 0;
+    if let Some(state) = &jurisdiction.state {
+        if !address
+            .state
+            .as_deref()
+            .is_some_and(|s| s.eq_ignore_ascii_case(state))
+        {
+            return None;
+        }
+        score += 1;
+    }
+    if let Some(prefix) = &jurisdiction.postal_prefix {
+        let postal_code = address.postal_code.replace(' ', "").to_ascii_uppercase();
+        if !postal_code.starts_with(&prefix.replace(' ', "").to_ascii_uppercase()) {
+            return None;
+        }
+        score += 2;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn address(country: &str, state: Option<&str>, postal_code: &str) -> Address {
+        Address {
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            company: None,
+            address_line_1: "1 Main St".to_string(),
+            address_line_2: None,
+            city: "Springfield".to_string(),
+            state: state.map(str::to_string),
+            postal_code: postal_code.to_string(),
+            country: country.to_string(),
+            phone: None,
+        }
+    }
+
+    fn jurisdiction(country: &str, state: Option<&str>, prefix: Option<&str>, rate: Decimal) -> TaxJurisdiction {
+        TaxJurisdiction {
+            country: country.to_string(),
+            state: state.map(str::to_string),
+            postal_prefix: prefix.map(str::to_string),
+            rate,
+        }
+    }
+
+    fn table(prices_include_tax: bool) -> RateTable {
+        RateTable::new(
+            &TaxConfig::builder()
+                .prices_include_tax(prices_include_tax)
+                .default_rate(Decimal::ZERO)
+                .jurisdictions(vec![
+                    jurisdiction("US", Some("NY"), Some("100"), dec("0.08875")),
+                    jurisdiction("US", Some("NY"), None, dec("0.04")),
+                    jurisdiction("US", None, None, dec("0.05")),
+                    jurisdiction("DE", None, None, dec("0.19")),
+                ])
+                .build(),
+        )
+    }
+
+    #[test]
+    fn test_most_specific_jurisdiction_wins() {
+        let table = table(false);
+        assert_eq!(table.rate_for(&address("US", Some("NY"), "10001")), dec("0.08875"));
+        assert_eq!(table.rate_for(&address("us", Some("ny"), "12201")), dec("0.04"));
+        assert_eq!(table.rate_for(&address("US", Some("CA"), "94103")), dec("0.05"));
+        assert_eq!(table.rate_for(&address("FR", None, "75001")), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_exclusive_and_inclusive_lines() {
+        let lines = [
+            TaxLine { amount: dec("19.99"), is_digital: false },
+            TaxLine { amount: dec("5.00"), is_digital: true },
+        ];
+        let berlin = address("DE", None, "10115");
+
+        let exclusive = table(false).calculate(&berlin, "EUR", &lines);
+        assert_eq!(exclusive.lines, vec![dec("3.80"), Decimal::ZERO]);
+        assert_eq!(exclusive.total(), dec("3.80"));
+
+        // 19.99 gross at 19% holds 3.19 of tax
+        let inclusive = table(true).calculate(&berlin, "EUR", &lines);
+        assert!(inclusive.inclusive);
+        assert_eq!(inclusive.lines, vec![dec("3.19"), Decimal::ZERO]);
+    }
+}
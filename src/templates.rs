@@ -1,28 +1,75 @@
 use serde::Serialize;
+use std::path::Path;
 use tera::{Context, Tera};
 
+/// Built-in templates, by the name they are rendered under. Each email has a
+/// `.subject`, a `.txt` and an `.html` part.
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("templates/base.html")),
+    ("email/welcome.subject", include_str!("templates/email/welcome.subject")),
+    ("email/welcome.txt", include_str!("templates/email/welcome.txt")),
+    ("email/welcome.html", include_str!("templates/email/welcome.html")),
+    (
+        "email/order_confirmation.subject",
+        include_str!("templates/email/order_confirmation.subject"),
+    ),
+    (
+        "email/order_confirmation.txt",
+        include_str!("templates/email/order_confirmation.txt"),
+    ),
+    (
+        "email/order_confirmation.html",
+        include_str!("templates/email/order_confirmation.html"),
+    ),
+    (
+        "email/password_reset.subject",
+        include_str!("templates/email/password_reset.subject"),
+    ),
+    ("email/password_reset.txt", include_str!("templates/email/password_reset.txt")),
+    ("email/password_reset.html", include_str!("templates/email/password_reset.html")),
+];
+
 pub struct TemplateEngine {
     tera: Tera,
 }
 
+/// The parts of one email, rendered for a locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
 impl TemplateEngine {
     pub fn new() -> Result<Self, TemplateError> {
         let mut tera = Tera::default();
 
         // Register built-in templates
-        tera.add_raw_template("base.html", include_str!("templates/base.html"))
+        tera.add_raw_templates(BUILTIN_TEMPLATES.iter().copied())
             .map_err(|e| TemplateError::ParseError(e.to_string()))?;
 
-        tera.add_raw_template("email/welcome.html", WELCOME_EMAIL_TEMPLATE)
-            .map_err(|e| TemplateError::ParseError(e.to_string()))?;
-
-        tera.add_raw_template("email/order_confirmation.html", ORDER_CONFIRMATION_TEMPLATE)
-            .map_err(|e| TemplateError::ParseError(e.to_string()))?;
+        Ok(Self { tera })
+    }
 
-        tera.add_raw_template("email/password_reset.html", PASSWORD_RESET_TEMPLATE)
+    /// The built-ins, with every file under `directory` added or replacing
+    /// the built-in of the same relative name, e.g. `email/welcome.fr.html`.
+    pub fn with_overrides(directory: &Path) -> Result<Self, TemplateError> {
+        let mut engine = Self::new()?;
+        let mut files = Vec::new();
+        collect_files(directory, directory, &mut files)?;
+        engine
+            .tera
+            .add_raw_templates(files)
             .map_err(|e| TemplateError::ParseError(e.to_string()))?;
+        Ok(engine)
+    }
 
-        Ok(Self { tera })
+    pub fn from_directory(directory: Option<&str>) -> Result<Self, TemplateError> {
+        match directory {
+            Some(directory) => Self::with_overrides(Path::new(directory)),
+            None => Self::new(),
+        }
     }
 
     pub fn render<T: Serialize>(&self, template: &str, data: &T) -> Result<String, TemplateError> {
@@ -45,6 +92,48 @@ impl TemplateEngine {
         Tera::one_off(template_content, &context, false)
             .map_err(|e| TemplateError::RenderError(e.to_string()))
     }
+
+    /// Renders `email/<name>` for `locale`, each part falling back from
+    /// `pt-BR` to `pt` to the unlocalized template.
+    pub fn render_email<T: Serialize>(
+        &self,
+        name: &str,
+        locale: Option<&str>,
+        data: &T,
+    ) -> Result<RenderedEmail, TemplateError> {
+        let part = |extension: &str| {
+            let template = self.localized(&format!("email/{}", name), extension, locale)?;
+            self.render(&template, data)
+        };
+
+        Ok(RenderedEmail {
+            subject: part("subject")?.trim().to_string(),
+            text: part("txt")?,
+            html: part("html")?,
+        })
+    }
+
+    fn localized(
+        &self,
+        stem: &str,
+        extension: &str,
+        locale: Option<&str>,
+    ) -> Result<String, TemplateError> {
+        let mut candidates = Vec::with_capacity(3);
+        if let Some(locale) = locale.filter(|l| !l.is_empty()) {
+            candidates.push(format!("{}.{}.{}", stem, locale, extension));
+            if let Some((language, _)) = locale.split_once(['-', '_']) {
+                candidates.push(format!("{}.{}.{}", stem, language, extension));
+            }
+        }
+        candidates.push(format!("{}.{}", stem, extension));
+
+        let known: Vec<&str> = self.tera.get_template_names().collect();
+        candidates
+            .into_iter()
+            .find(|candidate| known.contains(&candidate.as_str()))
+            .ok_or_else(|| TemplateError::NotFound(format!("{}.{}", stem, extension)))
+    }
 }
 
 impl Default for TemplateEngine {
@@ -53,6 +142,31 @@ impl Default for TemplateEngine {
     }
 }
 
+/// `(name relative to root, contents)` for every file below `directory`.
+fn collect_files(
+    root: &Path,
+    directory: &Path,
+    files: &mut Vec<(String, String)>,
+) -> Result<(), TemplateError> {
+    let io = |e: std::io::Error| TemplateError::ParseError(format!("{}: {}", directory.display(), e));
+    for entry in std::fs::read_dir(directory).map_err(io)? {
+        let path = entry.map_err(io)?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.push((name, std::fs::read_to_string(&path).map_err(io)?));
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TemplateError {
     #[error("Template parse error: {0}")]
@@ -65,80 +179,17 @@ pub enum TemplateError {
     NotFound(String),
 }
 
-const WELCOME_EMAIL_TEMPLATE: &str = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <title>Welcome!</title>
-</head>
-<body>
-    <h1>Welcome, {{ username }}!</h1>
-    <p>Thank you for joining our platform.</p>
-    <p>Your account has been created successfully.</p>
-    <a href="{{ verification_link }}">Verify your email</a>
-</body>
-</html>
-"#;
-
-const ORDER_CONFIRMATION_TEMPLATE: &str = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <title>Order Confirmation</title>
-</head>
-<body>
-    <h1>Order Confirmed!</h1>
-    <p>Order Number: {{ order_number }}</p>
-    <p>Total: {{ currency }}{{ total }}</p>
-    <h2>Items:</h2>
-    <ul>
-    {% for item in items %}
-        <li>{{ item.name }} x {{ item.quantity }} - {{ currency }}{{ item.total_price }}</li>
-    {% endfor %}
-    </ul>
-    <p>Shipping to:</p>
-    <address>
-        {{ shipping_address.first_name }} {{ shipping_address.last_name }}<br>
-        {{ shipping_address.address_line_1 }}<br>
-        {% if shipping_address.address_line_2 %}{{ shipping_address.address_line_2 }}<br>{% endif %}
-        {{ shipping_address.city }}, {{ shipping_address.state }} {{ shipping_address.postal_code }}<br>
-        {{ shipping_address.country }}
-    </address>
-</body>
-</html>
-"#;
-
-const PASSWORD_RESET_TEMPLATE: &str = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <title>Password Reset</title>
-</head>
-<body>
-    <h1>Password Reset Request</h1>
-    <p>You requested to reset your password.</p>
-    <p>Click the link below to reset your password:</p>
-    <a href="{{ reset_link }}">Reset Password</a>
-    <p>This link will expire in {{ expiry_hours }} hours.</p>
-    <p>If you didn't request this, please ignore this email.</p>
-</body>
-</html>
-"#;
-
 #[derive(Debug, Serialize)]
 pub struct WelcomeEmailData {
     pub username: String,
-    pub verification_link: String,
+    pub verification_link: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct OrderConfirmationData {
     pub order_number: String,
-    pub total: f64,
-    pub currency: String,
+    /// Formatted with `utils::format_currency`, as are item prices
+    pub total: String,
     pub items: Vec<OrderItemData>,
     pub shipping_address: ShippingAddressData,
 }
@@ -147,7 +198,7 @@ pub struct OrderConfirmationData {
 pub struct OrderItemData {
     pub name: String,
     pub quantity: i32,
-    pub total_price: f64,
+    pub total_price: String,
 }
 
 #[derive(Debug, Serialize)]
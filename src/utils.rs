@@ -2,9 +2,12 @@ use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::{DateTime, Duration, Utc};
 use rand::{distributions::Alphanumeric, Rng};
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use url::Url;
 
+use crate::money::Money;
+
 pub fn generate_random_string(length: usize) -> String {
     rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -51,13 +54,67 @@ pub fn decode_base64(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
     BASE64.decode(encoded)
 }
 
-pub fn format_currency(amount: f64, currency: &str) -> String {
-    match currency.to_uppercase().as_str() {
-        "USD" => format!("${:.2}", amount),
-        "EUR" => format!("€{:.2}", amount),
-        "GBP" => format!("£{:.2}", amount),
-        "JPY" => format!("¥{:.0}", amount),
-        _ => format!("{:.2} {}", amount, currency),
+/// HMAC-SHA256 (RFC 2104) of `message` under `key`.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> sha2::digest::Output<Sha256> {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+}
+
+/// Compares without an early exit, so timing does not reveal the matching prefix.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Renders `money` rounded to its currency's minor unit, with thousands
+/// separators and a symbol where one is well known: `$1,234.50`, `-€3.00`,
+/// `¥1,235`, `12.500 KWD`.
+pub fn format_currency(money: &Money) -> String {
+    let rounded = money.round();
+    let digits = rounded.amount.abs().to_string();
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits.as_str(), None),
+    };
+
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+
+    let sign = if rounded.is_negative() { "-" } else { "" };
+    let symbol = match rounded.currency.as_str() {
+        "USD" => Some("$"),
+        "EUR" => Some("€"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        _ => None,
+    };
+    match symbol {
+        Some(symbol) => format!("{}{}{}", sign, symbol, grouped),
+        None => format!("{}{} {}", sign, grouped, rounded.currency),
     }
 }
 
@@ -140,6 +197,21 @@ pub fn sanitize_html(input: &str) -> String {
         .replace('\'', "&#x27;")
 }
 
+/// Keeps letters, digits, `.`, `-` and `_`, replacing anything else with
+/// `_`, so the name is safe in a storage key or a header.
+pub fn sanitize_filename(filename: &str) -> String {
+    filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 pub fn extract_domain(url_str: &str) -> Option<String> {
     Url::parse(url_str)
         .ok()
@@ -224,6 +296,16 @@ mod tests {
         assert_eq!(format_file_size(1073741824), "1.00 GB");
     }
 
+    #[test]
+    fn test_format_currency() {
+        let money = |amount: &str, currency: &str| Money::new(amount.parse().unwrap(), currency);
+        assert_eq!(format_currency(&money("1234.5", "USD")), "$1,234.50");
+        assert_eq!(format_currency(&money("-3", "eur")), "-€3.00");
+        assert_eq!(format_currency(&money("1234567.4", "JPY")), "¥1,234,567");
+        assert_eq!(format_currency(&money("12.5", "KWD")), "12.500 KWD");
+        assert_eq!(format_currency(&money("0.004", "CHF")), "0.00 CHF");
+    }
+
     #[test]
     fn test_mask_email() {
         assert_eq!(mask_email("test@example.com"), "t**t@example.com");